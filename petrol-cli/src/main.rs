@@ -8,14 +8,22 @@ use color_eyre::eyre::{eyre, Result, WrapErr};
 use inflector::Inflector;
 use petrol_client::PetrolClient;
 use petrol_codegen::generate;
+use petrol_core::dialect::{dialect_for, MySql, Sqlite};
+use petrol_core::diff::{migration_sql, plan_migration, safe_migration_sql};
+use petrol_core::migrations::MIGRATIONS_TABLE;
 use petrol_core::schema::{
-    DatasourceBlock, Field, FieldAttribute, FieldType, GeneratorBlock, Model, ScalarType, Schema,
-    TypeModifiers,
+    DatasourceBlock, DefaultValue, Field, FieldAttribute, FieldType, GeneratorBlock, Model,
+    ModelAttribute, NativeType, RelationAttribute, RelationInfo, ScalarType, Schema, TypeModifiers,
 };
-use petrol_parser::parse_schema_file;
-use sqlx::{postgres::PgPoolOptions, Row};
+use petrol_core::sql::{schema_to_sql, updated_at_trigger_name};
+use petrol_parser::{format_schema, parse_schema_file, schema_files};
+use sqlx::{mysql::MySqlPoolOptions, postgres::PgPoolOptions, sqlite::SqlitePoolOptions, Row};
 use tracing::{info, warn};
 
+mod migrate;
+
+use migrate::{handle_migrate, MigrateCommand};
+
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Petrol - Prisma-style ORM for Rust", long_about = None)]
 struct Cli {
@@ -46,8 +54,28 @@ enum Commands {
     Push {
         #[arg(long, default_value = "schema.petrol")]
         schema: PathBuf,
-        #[arg(long, env = "DATABASE_URL")]
-        database_url: String,
+        #[arg(long, env = "DATABASE_URL", required_unless_present = "dry_run")]
+        database_url: Option<String>,
+        /// Print the statements instead of running them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the DDL for the schema, or the statements migrating another schema to it
+    Sql {
+        #[arg(long, default_value = "schema.petrol")]
+        schema: PathBuf,
+        /// Diff from this schema file instead of an empty database
+        #[arg(long, conflicts_with = "from_database")]
+        from_schema: Option<PathBuf>,
+        /// Diff from the database at this URL
+        #[arg(long)]
+        from_database: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Split up steps that would lock a table for a whole scan
+        #[arg(long)]
+        safe: bool,
     },
     /// Introspect database into schema.petrol
     Pull {
@@ -55,12 +83,25 @@ enum Commands {
         schema: PathBuf,
         #[arg(long, env = "DATABASE_URL")]
         database_url: String,
+        /// Postgres schemas to introspect (defaults to the datasource `schemas`, then `public`)
+        #[arg(long, value_delimiter = ',')]
+        schemas: Vec<String>,
+    },
+    /// Create, apply and check migrations
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
     },
     /// Format schema.petrol deterministically
     Format {
         #[arg(long, default_value = "schema.petrol")]
         schema: PathBuf,
+        /// Exit with an error instead of writing when the file is not formatted
+        #[arg(long)]
+        check: bool,
     },
+    /// Run the schema.petrol language server over stdio
+    Lsp,
 }
 
 #[tokio::main]
@@ -77,12 +118,23 @@ async fn main() -> Result<()> {
         Commands::Push {
             schema,
             database_url,
-        } => handle_push(schema, &database_url).await?,
+            dry_run,
+        } => handle_push(schema, database_url.as_deref(), dry_run).await?,
+        Commands::Sql {
+            schema,
+            from_schema,
+            from_database,
+            out,
+            safe,
+        } => handle_sql(schema, from_schema, from_database.as_deref(), out, safe).await?,
         Commands::Pull {
             schema,
             database_url,
-        } => handle_pull(schema, &database_url).await?,
-        Commands::Format { schema } => handle_format(schema)?,
+            schemas,
+        } => handle_pull(schema, &database_url, schemas).await?,
+        Commands::Migrate { command } => handle_migrate(command).await?,
+        Commands::Format { schema, check } => handle_format(schema, check)?,
+        Commands::Lsp => petrol_lsp::run_stdio().await,
     }
 
     Ok(())
@@ -90,7 +142,10 @@ async fn main() -> Result<()> {
 
 fn init_tracing() {
     let _ = tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn,sqlx=error")),
+        )
         .try_init();
 }
 
@@ -136,66 +191,486 @@ fn handle_validate(schema_path: PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn handle_push(schema_path: PathBuf, database_url: &str) -> Result<()> {
+async fn handle_push(
+    schema_path: PathBuf,
+    database_url: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
     let schema = parse_schema_file(&schema_path)?;
     schema.validate()?;
-    let client = PetrolClient::new(database_url).await?;
-    client.apply_schema(&schema).await?;
+    if dry_run {
+        print!("{}", render_statements(&schema_to_sql(&schema)?));
+        return Ok(());
+    }
+
+    let database_url = database_url.ok_or_else(|| eyre!("--database-url is required"))?;
+    match dialect_for(&schema.datasource.provider)?.name() {
+        "sqlite" => {
+            let client = PetrolClient::new_sqlite(database_url).await?;
+            client.apply_schema(&schema).await?;
+        }
+        "mysql" => {
+            let client = PetrolClient::new_mysql(database_url).await?;
+            client.apply_schema(&schema).await?;
+        }
+        _ => {
+            let client = PetrolClient::new(database_url).await?;
+            client.apply_schema(&schema).await?;
+        }
+    }
     println!("Schema pushed to database");
     Ok(())
 }
 
-async fn handle_pull(schema_path: PathBuf, database_url: &str) -> Result<()> {
-    let pool = PgPoolOptions::new()
-        .max_connections(1)
-        .connect(database_url)
-        .await?;
+async fn handle_sql(
+    schema_path: PathBuf,
+    from_schema: Option<PathBuf>,
+    from_database: Option<&str>,
+    out: Option<PathBuf>,
+    safe: bool,
+) -> Result<()> {
+    let schema = parse_schema_file(&schema_path)?;
+    schema.validate()?;
+
+    let from = match (from_schema, from_database) {
+        (Some(path), _) => {
+            let from = parse_schema_file(&path)?;
+            from.validate()?;
+            Some(from)
+        }
+        (None, Some(database_url)) => {
+            Some(introspect_database(database_url, &schema_namespaces(&schema)).await?)
+        }
+        (None, None) => None,
+    };
+    let statements = match from {
+        Some(from) if safe => safe_migration_sql(&schema, &plan_migration(&from, &schema))?,
+        Some(from) => migration_sql(&schema, &plan_migration(&from, &schema))?,
+        None => schema_to_sql(&schema)?,
+    };
+
+    let sql = render_statements(&statements);
+    match out {
+        Some(path) => {
+            fs::write(&path, sql)?;
+            println!("SQL written to {:?}", path);
+        }
+        None => print!("{}", sql),
+    }
+    Ok(())
+}
+
+/// The Postgres schemas a schema manages, `public` unless the datasource lists some.
+fn schema_namespaces(schema: &Schema) -> Vec<String> {
+    if schema.datasource.schemas.is_empty() {
+        vec!["public".to_string()]
+    } else {
+        schema.datasource.schemas.clone()
+    }
+}
+
+/// Statements separated by blank lines, or a comment saying there are none.
+fn render_statements(statements: &[String]) -> String {
+    if statements.is_empty() {
+        return "-- No changes\n".to_string();
+    }
+    statements.join("\n")
+}
+
+async fn handle_pull(schema_path: PathBuf, database_url: &str, schemas: Vec<String>) -> Result<()> {
+    if schema_path.is_dir() {
+        return Err(eyre!(
+            "{:?} is a directory, pull writes a single schema file",
+            schema_path
+        ));
+    }
 
-    let schema = introspect_schema(&pool, database_url).await?;
+    let namespaces = if !schemas.is_empty() {
+        schemas
+    } else {
+        parse_schema_file(&schema_path)
+            .ok()
+            .map(|existing| existing.datasource.schemas)
+            .filter(|schemas| !schemas.is_empty())
+            .unwrap_or_else(|| vec!["public".to_string()])
+    };
+    let schema = introspect_database(database_url, &namespaces).await?;
     fs::write(&schema_path, schema.to_string())?;
     println!("Schema written to {:?}", schema_path);
     Ok(())
 }
 
-fn handle_format(schema_path: PathBuf) -> Result<()> {
-    let schema = parse_schema_file(&schema_path)?;
-    fs::write(&schema_path, schema.to_string())?;
+/// Reads the schema of the database at `database_url`. `namespaces` are the Postgres
+/// schemas to read; the other databases have none.
+async fn introspect_database(database_url: &str, namespaces: &[String]) -> Result<Schema> {
+    if database_url.starts_with("sqlite:") {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(database_url)
+            .await?;
+        introspect_sqlite(&pool, database_url).await
+    } else if database_url.starts_with("mysql:") || database_url.starts_with("mariadb:") {
+        let pool = MySqlPoolOptions::new()
+            .max_connections(1)
+            .connect(database_url)
+            .await?;
+        introspect_mysql(&pool, database_url).await
+    } else {
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(database_url)
+            .await?;
+        introspect_schema(&pool, database_url, namespaces).await
+    }
+}
+
+fn handle_format(schema_path: PathBuf, check: bool) -> Result<()> {
+    let files = if schema_path.is_dir() {
+        schema_files(&schema_path)?
+    } else {
+        vec![schema_path]
+    };
+
+    let mut unformatted = Vec::new();
+    for file in files {
+        let source = fs::read_to_string(&file)?;
+        let formatted =
+            format_schema(&source).wrap_err_with(|| format!("failed to format {:?}", file))?;
+        if formatted == source {
+            continue;
+        }
+        if check {
+            unformatted.push(file);
+        } else {
+            fs::write(&file, formatted)?;
+            info!("formatted {:?}", file);
+        }
+    }
+
+    if check {
+        if !unformatted.is_empty() {
+            return Err(eyre!(
+                "{:?} not formatted, run `petrol format`",
+                unformatted
+            ));
+        }
+        println!("Schema is formatted ✅");
+    }
     Ok(())
 }
 
-async fn introspect_schema(pool: &sqlx::PgPool, database_url: &str) -> Result<Schema> {
+async fn introspect_schema(
+    pool: &sqlx::PgPool,
+    database_url: &str,
+    namespaces: &[String],
+) -> Result<Schema> {
     let rows = sqlx::query(
         r#"
         SELECT
+            table_schema,
             table_name,
             column_name,
             data_type,
+            udt_name::text AS udt_name,
+            character_maximum_length::int4 AS character_maximum_length,
+            numeric_precision::int4 AS numeric_precision,
+            numeric_scale::int4 AS numeric_scale,
+            datetime_precision::int4 AS datetime_precision,
             is_nullable,
-            column_default
+            column_default,
+            generation_expression,
+            col_description(
+                format('%I.%I', table_schema, table_name)::regclass,
+                ordinal_position::int
+            ) AS column_comment,
+            obj_description(
+                format('%I.%I', table_schema, table_name)::regclass,
+                'pg_class'
+            ) AS table_comment
         FROM information_schema.columns
-        WHERE table_schema = 'public'
-        ORDER BY table_name, ordinal_position
+        WHERE table_schema = ANY($1)
+          AND table_name <> $2
+        ORDER BY table_schema, table_name, ordinal_position
         "#,
     )
+    .bind(namespaces)
+    .bind(MIGRATIONS_TABLE)
     .fetch_all(pool)
     .await?;
 
-    let mut tables: BTreeMap<String, Vec<DbColumn>> = BTreeMap::new();
+    let mut tables: BTreeMap<(String, String), Vec<DbColumn>> = BTreeMap::new();
+    let mut table_comments: BTreeMap<(String, String), String> = BTreeMap::new();
     for row in rows {
+        let namespace: String = row.try_get("table_schema")?;
         let table: String = row.try_get("table_name")?;
+        if let Some(comment) = row.try_get::<Option<String>, _>("table_comment")? {
+            table_comments.insert((namespace.clone(), table.clone()), comment);
+        }
         let column = DbColumn {
             name: row.try_get("column_name")?,
             data_type: row.try_get("data_type")?,
+            udt_name: row.try_get("udt_name")?,
+            character_maximum_length: row.try_get("character_maximum_length")?,
+            numeric_precision: row.try_get("numeric_precision")?,
+            numeric_scale: row.try_get("numeric_scale")?,
+            datetime_precision: row
+                .try_get::<Option<i32>, _>("datetime_precision")?
+                .filter(|precision| *precision != DEFAULT_DATETIME_PRECISION),
             is_nullable: row.try_get::<String, _>("is_nullable")? == "YES",
             default: row.try_get("column_default").ok(),
+            generated: row.try_get("generation_expression")?,
+            updated_at: false,
+            primary_key_position: None,
+            comment: row.try_get("column_comment")?,
         };
-        tables.entry(table).or_default().push(column);
+        tables.entry((namespace, table)).or_default().push(column);
+    }
+
+    let triggers = sqlx::query(
+        r#"
+        SELECT
+            event_object_schema::text AS table_schema,
+            event_object_table::text AS table_name,
+            trigger_name::text AS trigger_name
+        FROM information_schema.triggers
+        WHERE event_object_schema = ANY($1)
+          AND event_manipulation = 'UPDATE'
+          AND trigger_name LIKE 'petrol_updated_at_%'
+        "#,
+    )
+    .bind(namespaces)
+    .fetch_all(pool)
+    .await?;
+
+    for row in triggers {
+        let key = (row.try_get("table_schema")?, row.try_get("table_name")?);
+        let trigger: String = row.try_get("trigger_name")?;
+        if let Some(columns) = tables.get_mut(&key) {
+            for column in columns.iter_mut() {
+                if updated_at_trigger_name(&column.name) == trigger {
+                    column.updated_at = true;
+                }
+            }
+        }
+    }
+
+    let primary_keys = sqlx::query(
+        r#"
+        SELECT
+            kcu.table_schema::text AS table_schema,
+            kcu.table_name::text AS table_name,
+            kcu.column_name::text AS column_name,
+            kcu.ordinal_position::int4 AS position
+        FROM information_schema.table_constraints tc
+        JOIN information_schema.key_column_usage kcu
+          ON kcu.constraint_schema = tc.constraint_schema
+         AND kcu.constraint_name = tc.constraint_name
+         AND kcu.table_schema = tc.table_schema
+         AND kcu.table_name = tc.table_name
+        WHERE tc.constraint_type = 'PRIMARY KEY'
+          AND tc.table_schema = ANY($1)
+        "#,
+    )
+    .bind(namespaces)
+    .fetch_all(pool)
+    .await?;
+
+    for row in primary_keys {
+        let key = (row.try_get("table_schema")?, row.try_get("table_name")?);
+        let column_name: String = row.try_get("column_name")?;
+        if let Some(column) = tables
+            .get_mut(&key)
+            .and_then(|columns| columns.iter_mut().find(|c| c.name == column_name))
+        {
+            column.primary_key_position = Some(row.try_get("position")?);
+        }
+    }
+
+    let check_rows = sqlx::query(
+        r#"
+        SELECT
+            n.nspname::text AS table_schema,
+            c.relname::text AS table_name,
+            con.conname::text AS constraint_name,
+            pg_get_constraintdef(con.oid) AS definition,
+            (
+                SELECT array_agg(a.attname::text)
+                FROM pg_attribute a
+                WHERE a.attrelid = con.conrelid AND a.attnum = ANY(con.conkey)
+            ) AS columns
+        FROM pg_constraint con
+        JOIN pg_class c ON c.oid = con.conrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE con.contype = 'c'
+          AND n.nspname = ANY($1)
+        ORDER BY con.conname
+        "#,
+    )
+    .bind(namespaces)
+    .fetch_all(pool)
+    .await?;
+
+    let mut checks: BTreeMap<(String, String), Vec<DbCheck>> = BTreeMap::new();
+    for row in check_rows {
+        let key = (row.try_get("table_schema")?, row.try_get("table_name")?);
+        let definition: String = row.try_get("definition")?;
+        checks.entry(key).or_default().push(DbCheck {
+            name: row.try_get("constraint_name")?,
+            expr: check_expression(&definition).to_string(),
+            columns: row
+                .try_get::<Option<Vec<String>>, _>("columns")?
+                .unwrap_or_default(),
+        });
+    }
+
+    // Expression and partial indexes have no schema equivalent and are left out.
+    let index_rows = sqlx::query(
+        r#"
+        SELECT
+            n.nspname::text AS table_schema,
+            t.relname::text AS table_name,
+            ix.indisunique AS is_unique,
+            ARRAY(
+                SELECT a.attname::text
+                FROM unnest(ix.indkey::int2[]) WITH ORDINALITY AS k(attnum, position)
+                JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = k.attnum
+                ORDER BY k.position
+            ) AS columns
+        FROM pg_index ix
+        JOIN pg_class t ON t.oid = ix.indrelid
+        JOIN pg_class i ON i.oid = ix.indexrelid
+        JOIN pg_namespace n ON n.oid = t.relnamespace
+        WHERE n.nspname = ANY($1)
+          AND NOT ix.indisprimary
+          AND ix.indexprs IS NULL
+          AND ix.indpred IS NULL
+        ORDER BY i.relname
+        "#,
+    )
+    .bind(namespaces)
+    .fetch_all(pool)
+    .await?;
+
+    let mut indexes: BTreeMap<(String, String), Vec<DbIndex>> = BTreeMap::new();
+    for row in index_rows {
+        let key = (row.try_get("table_schema")?, row.try_get("table_name")?);
+        indexes.entry(key).or_default().push(DbIndex {
+            columns: row.try_get("columns")?,
+            unique: row.try_get("is_unique")?,
+        });
     }
 
-    let models = tables
+    let foreign_key_rows = sqlx::query(
+        r#"
+        SELECT
+            n.nspname::text AS table_schema,
+            c.relname::text AS table_name,
+            rn.nspname::text AS references_schema,
+            r.relname::text AS references_table,
+            ARRAY(
+                SELECT a.attname::text
+                FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, position)
+                JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                ORDER BY k.position
+            ) AS columns,
+            ARRAY(
+                SELECT a.attname::text
+                FROM unnest(con.confkey) WITH ORDINALITY AS k(attnum, position)
+                JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+                ORDER BY k.position
+            ) AS references_columns
+        FROM pg_constraint con
+        JOIN pg_class c ON c.oid = con.conrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        JOIN pg_class r ON r.oid = con.confrelid
+        JOIN pg_namespace rn ON rn.oid = r.relnamespace
+        WHERE con.contype = 'f'
+          AND n.nspname = ANY($1)
+        ORDER BY con.conname
+        "#,
+    )
+    .bind(namespaces)
+    .fetch_all(pool)
+    .await?;
+
+    let foreign_keys = foreign_key_rows
         .into_iter()
-        .map(|(table, columns)| build_model_from_columns(&table, &columns))
-        .collect::<Result<Vec<_>, _>>()?;
+        .map(|row| {
+            Ok(DbForeignKey {
+                schema: Some(row.try_get("table_schema")?),
+                table: row.try_get("table_name")?,
+                columns: row.try_get("columns")?,
+                references_schema: Some(row.try_get("references_schema")?),
+                references_table: row.try_get("references_table")?,
+                references_columns: row.try_get("references_columns")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+    let extensions = sqlx::query(
+        "SELECT extname::text AS extname FROM pg_extension WHERE extname <> 'plpgsql' ORDER BY extname",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| row.try_get::<String, _>("extname"))
+    .collect::<Result<Vec<_>, _>>()?
+    .into_iter()
+    .map(|name| name.replace('-', "_"))
+    .collect();
+
+    let multi_schema = namespaces != ["public"];
+    let mut models: Vec<Model> = Vec::new();
+    let mut join_tables = Vec::new();
+    for (key, columns) in &tables {
+        let (namespace, table) = key;
+        if is_join_table(table, columns) {
+            join_tables.push(key.clone());
+            continue;
+        }
+        let mut model = build_model_from_columns(table, columns)?;
+        model.documentation = table_comments.get(key).cloned();
+        for check in checks.get(key).into_iter().flatten() {
+            add_check(&mut model, table, check);
+        }
+        for index in indexes.get(key).into_iter().flatten() {
+            add_index(&mut model, index);
+        }
+        if models.iter().any(|existing| existing.name == model.name) {
+            let mapped = model.table_name() != model.name;
+            model.name = format!("{}{}", namespace.to_class_case(), model.name);
+            if !mapped {
+                model.attributes.push(ModelAttribute::Map(table.clone()));
+            }
+        }
+        if multi_schema {
+            model
+                .attributes
+                .push(ModelAttribute::Schema(namespace.clone()));
+        }
+        models.push(model);
+    }
+
+    add_relations(&mut models, &foreign_keys);
+    for (namespace, table) in join_tables {
+        let references = foreign_keys
+            .iter()
+            .filter(|foreign_key| {
+                foreign_key.schema.as_ref() == Some(&namespace) && foreign_key.table == table
+            })
+            .filter_map(|foreign_key| match foreign_key.columns.as_slice() {
+                [column] => Some((
+                    column.clone(),
+                    (
+                        foreign_key.references_schema.clone(),
+                        foreign_key.references_table.clone(),
+                    ),
+                )),
+                _ => None,
+            })
+            .collect();
+        add_many_to_many(&mut models, &table, join_table_references(&references));
+    }
 
     let datasource = DatasourceBlock {
         name: "db".into(),
@@ -204,6 +679,12 @@ async fn introspect_schema(pool: &sqlx::PgPool, database_url: &str) -> Result<Sc
         raw_url: Some(format!("\"{}\"", database_url)),
         connection_limit: None,
         pool_timeout_seconds: None,
+        schemas: if multi_schema {
+            namespaces.to_vec()
+        } else {
+            Vec::new()
+        },
+        extensions,
     };
 
     let generator = GeneratorBlock::new("petrol-client-rust");
@@ -215,33 +696,918 @@ async fn introspect_schema(pool: &sqlx::PgPool, database_url: &str) -> Result<Sc
     })
 }
 
+/// SQLite keeps no catalog of generated columns or checks, so those are read back from
+/// the table's `CREATE TABLE` statement.
+async fn introspect_sqlite(pool: &sqlx::SqlitePool, database_url: &str) -> Result<Schema> {
+    let table_rows = sqlx::query(
+        "SELECT name, sql FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name <> ?1 ORDER BY name",
+    )
+    .bind(MIGRATIONS_TABLE)
+    .fetch_all(pool)
+    .await?;
+
+    let triggers: Vec<String> = sqlx::query(
+        "SELECT name FROM sqlite_master WHERE type = 'trigger' AND name LIKE '%petrol_updated_at_%'",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| row.try_get("name"))
+    .collect::<Result<_, _>>()?;
+
+    let mut models: Vec<Model> = Vec::new();
+    let mut join_tables = Vec::new();
+    let mut foreign_keys = Vec::new();
+    for table_row in table_rows {
+        let table: String = table_row.try_get("name")?;
+        let sql: String = table_row.try_get("sql")?;
+
+        // pragma_table_info leaves out generated columns, the xinfo variant marks them
+        // as hidden 2 (virtual) or 3 (stored).
+        let column_rows = sqlx::query(
+            r#"SELECT name, type, "notnull", dflt_value, pk, hidden FROM pragma_table_xinfo(?1) ORDER BY cid"#,
+        )
+        .bind(&table)
+        .fetch_all(pool)
+        .await?;
+
+        let mut columns = Vec::new();
+        for row in column_rows {
+            let name: String = row.try_get("name")?;
+            let declared: String = row.try_get("type")?;
+            let (data_type, length) = sqlite_column_type(&declared);
+            let generated = if row.try_get::<i64, _>("hidden")? >= 2 {
+                sqlite_generated_expression(&sql, &name).map(str::to_string)
+            } else {
+                None
+            };
+            let pk: i64 = row.try_get("pk")?;
+            columns.push(DbColumn {
+                updated_at: triggers.contains(&Sqlite::updated_at_trigger_name(&table, &name)),
+                name,
+                udt_name: data_type.clone(),
+                data_type,
+                character_maximum_length: length,
+                numeric_precision: None,
+                numeric_scale: None,
+                datetime_precision: None,
+                is_nullable: row.try_get::<i64, _>("notnull")? == 0,
+                default: row.try_get("dflt_value")?,
+                generated,
+                primary_key_position: (pk > 0).then_some(pk as i32),
+                comment: None,
+            });
+        }
+
+        let foreign_key_rows = sqlx::query(
+            r#"SELECT id, "table", "from", "to" FROM pragma_foreign_key_list(?1) ORDER BY id, seq"#,
+        )
+        .bind(&table)
+        .fetch_all(pool)
+        .await?;
+
+        if is_join_table(&table, &columns) {
+            let references = foreign_key_rows
+                .iter()
+                .map(|row| Ok((row.try_get("from")?, (None, row.try_get("table")?))))
+                .collect::<Result<BTreeMap<String, TableName>, sqlx::Error>>()?;
+            join_tables.push((table, join_table_references(&references)));
+            continue;
+        }
+
+        let mut keys: BTreeMap<i64, DbForeignKey> = BTreeMap::new();
+        for row in foreign_key_rows {
+            let key = keys
+                .entry(row.try_get("id")?)
+                .or_insert_with(|| DbForeignKey {
+                    schema: None,
+                    table: table.clone(),
+                    columns: Vec::new(),
+                    references_schema: None,
+                    references_table: String::new(),
+                    references_columns: Vec::new(),
+                });
+            key.references_table = row.try_get("table")?;
+            key.columns.push(row.try_get("from")?);
+            key.references_columns.push(row.try_get("to")?);
+        }
+        foreign_keys.extend(keys.into_values());
+
+        let mut model = build_model_from_columns(&table, &columns)?;
+        // A lone INTEGER primary key is SQLite's rowid and numbers itself.
+        if let [key] = columns
+            .iter()
+            .filter(|col| col.primary_key_position.is_some())
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            if key.data_type == "integer" && key.default.is_none() {
+                add_autoincrement(&mut model, &key.name);
+            }
+        }
+        for check in sqlite_checks(&sql, &table, &columns) {
+            add_check(&mut model, &table, &check);
+        }
+
+        // Origin `u` is a UNIQUE constraint and `c` a CREATE INDEX; `pk` is the primary key.
+        let index_rows = sqlx::query(
+            r#"SELECT name, "unique" FROM pragma_index_list(?1) WHERE origin <> 'pk' ORDER BY name"#,
+        )
+        .bind(&table)
+        .fetch_all(pool)
+        .await?;
+        for row in index_rows {
+            let name: String = row.try_get("name")?;
+            let columns = sqlx::query("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")
+                .bind(&name)
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .map(|row| row.try_get("name"))
+                .collect::<Result<_, _>>()?;
+            let index = DbIndex {
+                columns,
+                unique: row.try_get::<i64, _>("unique")? != 0,
+            };
+            add_index(&mut model, &index);
+        }
+        models.push(model);
+    }
+
+    add_relations(&mut models, &foreign_keys);
+    for (table, references) in join_tables {
+        add_many_to_many(&mut models, &table, references);
+    }
+
+    let datasource = DatasourceBlock {
+        name: "db".into(),
+        provider: "sqlite".into(),
+        url: Some(database_url.to_string()),
+        raw_url: Some(format!("\"{}\"", database_url)),
+        connection_limit: None,
+        pool_timeout_seconds: None,
+        schemas: Vec::new(),
+        extensions: Vec::new(),
+    };
+
+    Ok(Schema {
+        datasource,
+        generator: GeneratorBlock::new("petrol-client-rust"),
+        models,
+    })
+}
+
+/// Everything comes from `information_schema`, limited to the database the URL names.
+/// Text columns are cast to `CHAR` since MySQL 8 reports some of them as binary strings.
+async fn introspect_mysql(pool: &sqlx::MySqlPool, database_url: &str) -> Result<Schema> {
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            CAST(c.TABLE_NAME AS CHAR) AS table_name,
+            CAST(c.COLUMN_NAME AS CHAR) AS column_name,
+            CAST(c.DATA_TYPE AS CHAR) AS data_type,
+            CAST(c.COLUMN_TYPE AS CHAR) AS column_type,
+            CAST(c.CHARACTER_MAXIMUM_LENGTH AS SIGNED) AS character_maximum_length,
+            CAST(c.NUMERIC_PRECISION AS SIGNED) AS numeric_precision,
+            CAST(c.NUMERIC_SCALE AS SIGNED) AS numeric_scale,
+            CAST(c.DATETIME_PRECISION AS SIGNED) AS datetime_precision,
+            CAST(c.IS_NULLABLE AS CHAR) AS is_nullable,
+            CAST(c.COLUMN_DEFAULT AS CHAR) AS column_default,
+            CAST(c.EXTRA AS CHAR) AS extra,
+            CAST(c.GENERATION_EXPRESSION AS CHAR) AS generation_expression,
+            CAST(c.COLUMN_COMMENT AS CHAR) AS column_comment,
+            CAST(t.TABLE_COMMENT AS CHAR) AS table_comment
+        FROM information_schema.COLUMNS c
+        JOIN information_schema.TABLES t
+          ON t.TABLE_SCHEMA = c.TABLE_SCHEMA
+         AND t.TABLE_NAME = c.TABLE_NAME
+        WHERE c.TABLE_SCHEMA = DATABASE()
+          AND t.TABLE_TYPE = 'BASE TABLE'
+          AND c.TABLE_NAME <> ?
+        ORDER BY c.TABLE_NAME, c.ORDINAL_POSITION
+        "#,
+    )
+    .bind(MIGRATIONS_TABLE)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tables: BTreeMap<String, Vec<DbColumn>> = BTreeMap::new();
+    let mut table_comments: BTreeMap<String, String> = BTreeMap::new();
+    let mut autoincrement: Vec<(String, String)> = Vec::new();
+    for row in rows {
+        let table: String = row.try_get("table_name")?;
+        let table_comment: Option<String> = row.try_get("table_comment")?;
+        if let Some(comment) = table_comment.filter(|comment| !comment.is_empty()) {
+            table_comments.insert(table.clone(), comment);
+        }
+        let name: String = row.try_get("column_name")?;
+        let extra = row
+            .try_get::<Option<String>, _>("extra")?
+            .unwrap_or_default()
+            .to_lowercase();
+        if extra.contains("auto_increment") {
+            autoincrement.push((table.clone(), name.clone()));
+        }
+        let data_type = mysql_column_type(
+            &row.try_get::<String, _>("data_type")?,
+            &row.try_get::<String, _>("column_type")?,
+        );
+        let generated = row
+            .try_get::<Option<String>, _>("generation_expression")?
+            .filter(|expr| {
+                !expr.is_empty()
+                    && extra.contains("generated")
+                    && !extra.contains("default_generated")
+            });
+        let number = |column: &str| -> Result<Option<i32>, sqlx::Error> {
+            Ok(row
+                .try_get::<Option<i64>, _>(column)?
+                .map(|value| value as i32))
+        };
+        let column = DbColumn {
+            default: mysql_default(row.try_get("column_default")?, &extra, &data_type),
+            updated_at: extra.contains("on update current_timestamp"),
+            name,
+            udt_name: data_type.clone(),
+            character_maximum_length: number("character_maximum_length")?,
+            numeric_precision: number("numeric_precision")?,
+            numeric_scale: number("numeric_scale")?,
+            datetime_precision: number("datetime_precision")?
+                .filter(|precision| *precision != MySql::DEFAULT_DATETIME_PRECISION as i32),
+            data_type,
+            is_nullable: row.try_get::<String, _>("is_nullable")? == "YES",
+            generated,
+            primary_key_position: None,
+            comment: row
+                .try_get::<Option<String>, _>("column_comment")?
+                .filter(|comment| !comment.is_empty()),
+        };
+        tables.entry(table).or_default().push(column);
+    }
+
+    let key_rows = sqlx::query(
+        r#"
+        SELECT
+            CAST(TABLE_NAME AS CHAR) AS table_name,
+            CAST(COLUMN_NAME AS CHAR) AS column_name,
+            CAST(CONSTRAINT_NAME AS CHAR) AS constraint_name,
+            CAST(ORDINAL_POSITION AS SIGNED) AS position,
+            CAST(REFERENCED_TABLE_NAME AS CHAR) AS referenced_table,
+            CAST(REFERENCED_COLUMN_NAME AS CHAR) AS referenced_column
+        FROM information_schema.KEY_COLUMN_USAGE
+        WHERE TABLE_SCHEMA = DATABASE()
+        ORDER BY CONSTRAINT_NAME, ORDINAL_POSITION
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut references: BTreeMap<String, BTreeMap<String, TableName>> = BTreeMap::new();
+    let mut keys: BTreeMap<(String, String), DbForeignKey> = BTreeMap::new();
+    for row in key_rows {
+        let table: String = row.try_get("table_name")?;
+        let column_name: String = row.try_get("column_name")?;
+        let constraint: String = row.try_get("constraint_name")?;
+        if let Some(referenced) = row.try_get::<Option<String>, _>("referenced_table")? {
+            references
+                .entry(table.clone())
+                .or_default()
+                .insert(column_name.clone(), (None, referenced.clone()));
+            let key = keys
+                .entry((table.clone(), constraint.clone()))
+                .or_insert_with(|| DbForeignKey {
+                    schema: None,
+                    table: table.clone(),
+                    columns: Vec::new(),
+                    references_schema: None,
+                    references_table: referenced,
+                    references_columns: Vec::new(),
+                });
+            key.columns.push(column_name.clone());
+            key.references_columns
+                .push(row.try_get("referenced_column")?);
+        }
+        if constraint != "PRIMARY" {
+            continue;
+        }
+        if let Some(column) = tables
+            .get_mut(&table)
+            .and_then(|columns| columns.iter_mut().find(|c| c.name == column_name))
+        {
+            column.primary_key_position = Some(row.try_get::<i64, _>("position")? as i32);
+        }
+    }
+
+    let check_rows = sqlx::query(
+        r#"
+        SELECT
+            CAST(tc.TABLE_NAME AS CHAR) AS table_name,
+            CAST(cc.CONSTRAINT_NAME AS CHAR) AS constraint_name,
+            CAST(cc.CHECK_CLAUSE AS CHAR) AS check_clause
+        FROM information_schema.TABLE_CONSTRAINTS tc
+        JOIN information_schema.CHECK_CONSTRAINTS cc
+          ON cc.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA
+         AND cc.CONSTRAINT_NAME = tc.CONSTRAINT_NAME
+        WHERE tc.TABLE_SCHEMA = DATABASE()
+          AND tc.CONSTRAINT_TYPE = 'CHECK'
+        ORDER BY cc.CONSTRAINT_NAME
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut checks: BTreeMap<String, Vec<DbCheck>> = BTreeMap::new();
+    for row in check_rows {
+        let table: String = row.try_get("table_name")?;
+        let name: String = row.try_get("constraint_name")?;
+        let clause: String = row.try_get("check_clause")?;
+        let columns = tables
+            .get(&table)
+            .and_then(|columns| field_check_column(&name, &table, columns))
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        checks.entry(table).or_default().push(DbCheck {
+            expr: strip_parentheses(&clause).to_string(),
+            name,
+            columns,
+        });
+    }
+
+    // MySQL backs every foreign key with an index named after it when none is declared.
+    let index_rows = sqlx::query(
+        r#"
+        SELECT
+            CAST(TABLE_NAME AS CHAR) AS table_name,
+            CAST(INDEX_NAME AS CHAR) AS index_name,
+            CAST(NON_UNIQUE AS SIGNED) AS non_unique,
+            CAST(COLUMN_NAME AS CHAR) AS column_name
+        FROM information_schema.STATISTICS
+        WHERE TABLE_SCHEMA = DATABASE()
+          AND INDEX_NAME <> 'PRIMARY'
+          AND COLUMN_NAME IS NOT NULL
+        ORDER BY TABLE_NAME, INDEX_NAME, SEQ_IN_INDEX
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut indexes: BTreeMap<(String, String), DbIndex> = BTreeMap::new();
+    for row in index_rows {
+        let table: String = row.try_get("table_name")?;
+        let name: String = row.try_get("index_name")?;
+        let unique = row.try_get::<i64, _>("non_unique")? == 0;
+        if !unique && keys.contains_key(&(table.clone(), name.clone())) {
+            continue;
+        }
+        indexes
+            .entry((table, name))
+            .or_insert_with(|| DbIndex {
+                columns: Vec::new(),
+                unique,
+            })
+            .columns
+            .push(row.try_get("column_name")?);
+    }
+
+    let no_references = BTreeMap::new();
+    let mut models: Vec<Model> = Vec::new();
+    let mut join_tables = Vec::new();
+    for (table, columns) in &tables {
+        if is_join_table(table, columns) {
+            let references = references.get(table).unwrap_or(&no_references);
+            join_tables.push((table.clone(), join_table_references(references)));
+            continue;
+        }
+        let mut model = build_model_from_columns(table, columns)?;
+        model.documentation = table_comments.get(table).cloned();
+        for (_, column) in autoincrement.iter().filter(|(owner, _)| owner == table) {
+            add_autoincrement(&mut model, column);
+        }
+        for check in checks.get(table).into_iter().flatten() {
+            add_check(&mut model, table, check);
+        }
+        for ((_, _), index) in indexes.iter().filter(|((owner, _), _)| owner == table) {
+            add_index(&mut model, index);
+        }
+        models.push(model);
+    }
+
+    let foreign_keys: Vec<DbForeignKey> = keys.into_values().collect();
+    add_relations(&mut models, &foreign_keys);
+    for (table, references) in join_tables {
+        add_many_to_many(&mut models, &table, references);
+    }
+
+    let datasource = DatasourceBlock {
+        name: "db".into(),
+        provider: "mysql".into(),
+        url: Some(database_url.to_string()),
+        raw_url: Some(format!("\"{}\"", database_url)),
+        connection_limit: None,
+        pool_timeout_seconds: None,
+        schemas: Vec::new(),
+        extensions: Vec::new(),
+    };
+
+    Ok(Schema {
+        datasource,
+        generator: GeneratorBlock::new("petrol-client-rust"),
+        models,
+    })
+}
+
+/// A MySQL column type in the names `map_sql_type` knows. `TINYINT(1)` is how MySQL
+/// spells `BOOLEAN`, and `CHAR(36)` is how petrol stores a `Uuid`.
+fn mysql_column_type(data_type: &str, column_type: &str) -> String {
+    let data_type = data_type.to_lowercase();
+    let name = match data_type.as_str() {
+        "int" | "mediumint" => "integer",
+        "tinyint" if column_type.to_lowercase().starts_with("tinyint(1)") => "boolean",
+        "tinyint" => "smallint",
+        "float" => "real",
+        "double" => "double precision",
+        "decimal" => "numeric",
+        "char" if column_type.eq_ignore_ascii_case("char(36)") => "uuid",
+        "char" => "character",
+        "tinytext" | "mediumtext" | "longtext" => "text",
+        "datetime" => "timestamptz",
+        "json" => "jsonb",
+        "tinyblob" | "blob" | "mediumblob" | "longblob" | "varbinary" => "bytea",
+        other => other,
+    };
+    name.to_string()
+}
+
+/// A MySQL column default in the spelling `build_field` understands. MySQL 8 reports
+/// string literals unquoted and marks expressions as `DEFAULT_GENERATED`; MariaDB quotes
+/// literals and reports a missing default as `NULL`.
+fn mysql_default(default: Option<String>, extra: &str, data_type: &str) -> Option<String> {
+    let default = default.filter(|default| default != "NULL")?;
+    let lower = default.to_lowercase();
+    if lower.starts_with("current_timestamp") || lower.starts_with("now(") {
+        return Some("now()".to_string());
+    }
+    if lower == "uuid()" || lower == "uuid_to_bin(uuid())" {
+        return Some("gen_random_uuid()".to_string());
+    }
+    if extra.contains("default_generated") || default.starts_with('\'') {
+        return Some(strip_parentheses(&default).to_string());
+    }
+    Some(match data_type {
+        "boolean" if default == "1" => "true".to_string(),
+        "boolean" if default == "0" => "false".to_string(),
+        "character" | "varchar" | "text" | "jsonb" => {
+            format!("'{}'", default.replace('\'', "''"))
+        }
+        _ => default,
+    })
+}
+
+/// A declared SQLite type such as `VARCHAR(40)` in the names `map_sql_type` knows, plus
+/// its length.
+fn sqlite_column_type(declared: &str) -> (String, Option<i32>) {
+    let declared = declared.to_lowercase();
+    let (name, length) = match declared.split_once('(') {
+        Some((name, rest)) => (name.trim(), rest.trim_end_matches(')').trim().parse().ok()),
+        None => (declared.trim(), None),
+    };
+    let name = match name {
+        "int" => "integer",
+        "double" | "float" => "double precision",
+        "datetime" => "timestamptz",
+        "char" => "character",
+        "blob" => "bytea",
+        other => other,
+    };
+    (name.to_string(), length)
+}
+
+fn sqlite_generated_expression<'a>(sql: &'a str, column: &str) -> Option<&'a str> {
+    let definition = &sql[sql.find(&format!("\"{}\" ", column))?..];
+    let definition = definition
+        .split_once(",\n")
+        .map_or(definition, |(line, _)| line);
+    let start = definition.find("GENERATED ALWAYS AS ")? + "GENERATED ALWAYS AS ".len();
+    parenthesized(&definition[start..])
+}
+
+/// Named `CHECK` constraints of a `CREATE TABLE` statement.
+fn sqlite_checks(sql: &str, table: &str, columns: &[DbColumn]) -> Vec<DbCheck> {
+    let mut checks = Vec::new();
+    let mut rest = sql;
+    while let Some(start) = rest.find("CONSTRAINT \"") {
+        rest = &rest[start + "CONSTRAINT \"".len()..];
+        let Some((name, after)) = rest.split_once('"') else {
+            break;
+        };
+        let Some(expr) = after
+            .trim_start()
+            .strip_prefix("CHECK ")
+            .and_then(parenthesized)
+        else {
+            continue;
+        };
+        checks.push(DbCheck {
+            name: name.to_string(),
+            expr: strip_parentheses(expr).to_string(),
+            columns: field_check_column(name, table, columns)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        });
+    }
+    checks
+}
+
+/// The column of a check named `{table}_{column}_check`, the name Petrol gives field
+/// checks, for databases that do not record which columns a check uses.
+fn field_check_column<'a>(name: &'a str, table: &str, columns: &[DbColumn]) -> Option<&'a str> {
+    name.strip_prefix(table)
+        .and_then(|name| name.strip_prefix('_'))
+        .and_then(|name| name.strip_suffix("_check"))
+        .filter(|column| columns.iter().any(|col| col.name == *column))
+}
+
+/// The contents of the parenthesized group `text` starts with.
+fn parenthesized(text: &str) -> Option<&str> {
+    let text = text.strip_prefix('(')?;
+    let mut depth = 0;
+    let mut quoted = false;
+    for (index, ch) in text.char_indices() {
+        match ch {
+            '\'' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted && depth == 0 => return Some(&text[..index]),
+            ')' if !quoted => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Checks on a single column with Postgres' default name become `@check` on the field,
+/// everything else a `@@check` on the model.
+fn add_check(model: &mut Model, table: &str, check: &DbCheck) {
+    if let [column] = check.columns.as_slice() {
+        if check.name == format!("{}_{}_check", table, column) {
+            if let Some(field) = model.fields.iter_mut().find(|field| &field.name == column) {
+                field
+                    .attributes
+                    .push(FieldAttribute::Check(check.expr.clone()));
+                return;
+            }
+        }
+    }
+    let name = (check.name != format!("{}_check", table)).then(|| check.name.clone());
+    model.attributes.push(ModelAttribute::Check {
+        name,
+        expr: check.expr.clone(),
+    });
+}
+
+/// The expression of a `pg_get_constraintdef` result such as `CHECK ((price >= 0))`,
+/// without the redundant outer parentheses.
+fn check_expression(definition: &str) -> &str {
+    strip_parentheses(
+        definition
+            .trim()
+            .trim_end_matches(" NOT VALID")
+            .trim_start_matches("CHECK "),
+    )
+}
+
+fn strip_parentheses(expr: &str) -> &str {
+    let mut expr = expr.trim();
+    while let Some(inner) = expr
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        // Only strip when the first parenthesis closes at the very end.
+        let mut depth = 0;
+        let wraps_all = inner.chars().all(|ch| {
+            match ch {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            depth >= 0
+        });
+        if !wraps_all {
+            break;
+        }
+        expr = inner.trim();
+    }
+    expr
+}
+
+/// The tables the `A` and `B` columns of a join table reference, when both have a
+/// foreign key.
+fn join_table_references(
+    references: &BTreeMap<String, TableName>,
+) -> Option<(TableName, TableName)> {
+    Some((references.get("A")?.clone(), references.get("B")?.clone()))
+}
+
+fn add_autoincrement(model: &mut Model, column: &str) {
+    if let Some(field) = model.fields.iter_mut().find(|field| field.name == column) {
+        field
+            .attributes
+            .push(FieldAttribute::Default(DefaultValue::AutoIncrement));
+    }
+}
+
+/// Join tables of implicit many-to-many relations start with `_` and hold exactly the
+/// two columns `A` and `B`.
+fn is_join_table(table: &str, columns: &[DbColumn]) -> bool {
+    let mut names: Vec<&str> = columns.iter().map(|col| col.name.as_str()).collect();
+    names.sort();
+    table.starts_with('_') && names == ["A", "B"]
+}
+
+/// Turns a join table back into a pair of list fields. The sides come from its foreign
+/// keys or, without those, from a `_AToB` name whose halves are both models. A table
+/// named otherwise becomes the relation name. If a side is missing the join table is
+/// left out of the schema.
+fn add_many_to_many(models: &mut [Model], table: &str, references: Option<(TableName, TableName)>) {
+    let position = |name: &str| {
+        models
+            .iter()
+            .position(|model| model.table_name() == name || model.name == name)
+    };
+    let name = table.strip_prefix('_').unwrap_or(table);
+    let sides = references
+        .and_then(|((schema_a, table_a), (schema_b, table_b))| {
+            Some((
+                model_position(models, schema_a.as_deref(), &table_a)?,
+                model_position(models, schema_b.as_deref(), &table_b)?,
+            ))
+        })
+        .or_else(|| {
+            name.match_indices("To").find_map(|(index, _)| {
+                Some((position(&name[..index])?, position(&name[index + 2..])?))
+            })
+        });
+    let Some((a, b)) = sides else {
+        warn!("skipping join table {}", table);
+        return;
+    };
+    let relation_name =
+        (table != format!("_{}To{}", models[a].name, models[b].name)).then(|| name.to_string());
+
+    let list_field = |owner: &Model, target: &Model| Field {
+        name: unused_field_name(owner, &target.name.to_camel_case().to_plural()),
+        r#type: FieldType::Relation(RelationInfo {
+            model: target.name.clone(),
+            modifiers: TypeModifiers {
+                optional: false,
+                list: true,
+            },
+            attributes: Vec::new(),
+        }),
+        attributes: relation_name
+            .iter()
+            .map(|name| {
+                FieldAttribute::Relation(RelationAttribute {
+                    name: Some(name.clone()),
+                    fields: Vec::new(),
+                    references: Vec::new(),
+                })
+            })
+            .collect(),
+        documentation: None,
+    };
+    let field_a = list_field(&models[a], &models[b]);
+    models[a].fields.push(field_a);
+    let field_b = list_field(&models[b], &models[a]);
+    models[b].fields.push(field_b);
+}
+
+/// A unique index on one column becomes `@unique` on the field, any other index a
+/// `@@unique` or `@@index` on the model.
+fn add_index(model: &mut Model, index: &DbIndex) {
+    if index.unique {
+        if let [column] = index.columns.as_slice() {
+            if let Some(field) = model.fields.iter_mut().find(|field| &field.name == column) {
+                field.attributes.push(FieldAttribute::Unique);
+                return;
+            }
+        }
+        model
+            .attributes
+            .push(ModelAttribute::Unique(index.columns.clone()));
+    } else {
+        model
+            .attributes
+            .push(ModelAttribute::Index(index.columns.clone()));
+    }
+}
+
+/// Turns each foreign key into a relation field holding `@relation(fields: ...)` and its
+/// opposite on the referenced model, a list unless the key columns are unique. Several
+/// relations between the same two models are named apart.
+fn add_relations(models: &mut [Model], foreign_keys: &[DbForeignKey]) {
+    for foreign_key in foreign_keys {
+        let (Some(owner), Some(target)) = (
+            model_position(models, foreign_key.schema.as_deref(), &foreign_key.table),
+            model_position(
+                models,
+                foreign_key.references_schema.as_deref(),
+                &foreign_key.references_table,
+            ),
+        ) else {
+            continue;
+        };
+
+        let base = match foreign_key.columns.as_slice() {
+            [column] => column
+                .strip_suffix("Id")
+                .or_else(|| column.strip_suffix("_id"))
+                .filter(|base| !base.is_empty())
+                .map(str::to_string),
+            _ => None,
+        }
+        .unwrap_or_else(|| models[target].name.to_camel_case());
+        let name = unused_field_name(&models[owner], &base);
+        let parallel = foreign_keys
+            .iter()
+            .filter(|other| {
+                other.schema == foreign_key.schema
+                    && other.table == foreign_key.table
+                    && other.references_schema == foreign_key.references_schema
+                    && other.references_table == foreign_key.references_table
+            })
+            .count();
+        let relation_name = (parallel > 1).then(|| format!("{}_{}", models[owner].name, name));
+
+        let owner_model = &models[owner];
+        let optional = owner_model.fields.iter().any(|field| {
+            foreign_key.columns.contains(&field.name) && field.r#type.modifiers().optional
+        });
+        let unique = match foreign_key.columns.as_slice() {
+            [column] => owner_model
+                .fields
+                .iter()
+                .any(|field| &field.name == column && field.is_unique()),
+            columns => owner_model
+                .attributes
+                .iter()
+                .any(|attr| matches!(attr, ModelAttribute::Unique(fields) if fields == columns)),
+        };
+        let owner_name = owner_model.name.clone();
+        let target_name = models[target].name.clone();
+
+        models[owner].fields.push(Field {
+            name,
+            r#type: FieldType::Relation(RelationInfo {
+                model: target_name,
+                modifiers: TypeModifiers {
+                    optional,
+                    list: false,
+                },
+                attributes: Vec::new(),
+            }),
+            attributes: vec![FieldAttribute::Relation(RelationAttribute {
+                name: relation_name.clone(),
+                fields: foreign_key.columns.clone(),
+                references: foreign_key.references_columns.clone(),
+            })],
+            documentation: None,
+        });
+
+        let back_name = if unique {
+            owner_name.to_camel_case()
+        } else {
+            owner_name.to_camel_case().to_plural()
+        };
+        let back = Field {
+            name: unused_field_name(&models[target], &back_name),
+            r#type: FieldType::Relation(RelationInfo {
+                model: owner_name,
+                modifiers: TypeModifiers {
+                    optional: unique,
+                    list: !unique,
+                },
+                attributes: Vec::new(),
+            }),
+            attributes: relation_name
+                .map(|name| {
+                    vec![FieldAttribute::Relation(RelationAttribute {
+                        name: Some(name),
+                        fields: Vec::new(),
+                        references: Vec::new(),
+                    })]
+                })
+                .unwrap_or_default(),
+            documentation: None,
+        };
+        models[target].fields.push(back);
+    }
+}
+
+/// The model pulled from `table` in the Postgres schema `namespace`. Models pulled from
+/// `public` alone carry no `@@schema`.
+fn model_position(models: &[Model], namespace: Option<&str>, table: &str) -> Option<usize> {
+    models.iter().position(|model| {
+        model.table_name() == table
+            && namespace.is_none_or(|namespace| {
+                model.schema_name().as_deref().unwrap_or("public") == namespace
+            })
+    })
+}
+
+/// `base`, or `base` with a numeric suffix if the model already has a field by that name.
+fn unused_field_name(model: &Model, base: &str) -> String {
+    let taken = |name: &str| model.fields.iter().any(|field| field.name == name);
+    let mut name = base.to_string();
+    let mut suffix = 2;
+    while taken(&name) {
+        name = format!("{}{}", base, suffix);
+        suffix += 1;
+    }
+    name
+}
+
 fn build_model_from_columns(table: &str, columns: &[DbColumn]) -> Result<Model> {
     let model_name = table.to_class_case();
-    let fields = columns
+    let mut fields = columns
         .iter()
-        .map(|col| build_field(col))
+        .map(build_field)
         .collect::<Result<Vec<_>, _>>()?;
 
+    let mut key: Vec<&DbColumn> = columns
+        .iter()
+        .filter(|col| col.primary_key_position.is_some())
+        .collect();
+    key.sort_by_key(|col| col.primary_key_position);
+
+    let mut attributes = Vec::new();
+    match key.as_slice() {
+        [] => {
+            if let Some(field) = fields.iter_mut().find(|field| field.name == "id") {
+                field.attributes.insert(0, FieldAttribute::Id);
+            }
+        }
+        [single] => {
+            if let Some(field) = fields.iter_mut().find(|field| field.name == single.name) {
+                field.attributes.insert(0, FieldAttribute::Id);
+            }
+        }
+        composite => attributes.push(ModelAttribute::Id(
+            composite.iter().map(|col| col.name.clone()).collect(),
+        )),
+    }
+
+    if model_name != table {
+        attributes.push(ModelAttribute::Map(table.to_string()));
+    }
+
     Ok(Model {
         name: model_name,
         fields,
-        attributes: Vec::new(),
+        attributes,
+        documentation: None,
     })
 }
 
 fn build_field(col: &DbColumn) -> Result<Field> {
-    let scalar = map_sql_to_scalar(&col.data_type)
-        .ok_or_else(|| eyre!("unsupported SQL type {}", col.data_type))?;
+    let list = col.data_type == "ARRAY";
+    let mapped = if list {
+        let element = col.udt_name.trim_start_matches('_');
+        map_sql_type(&DbColumn {
+            data_type: element.to_string(),
+            udt_name: element.to_string(),
+            ..col.clone()
+        })
+    } else {
+        map_sql_type(col)
+    };
+    let (scalar, native) = mapped.ok_or_else(|| eyre!("unsupported SQL type {}", col.udt_name))?;
 
     let mut attributes = Vec::new();
-    if col.name == "id" {
-        attributes.push(FieldAttribute::Id);
+    if col.updated_at {
+        attributes.push(FieldAttribute::UpdatedAt);
+    }
+    if let Some(native) = native {
+        attributes.push(FieldAttribute::NativeType(native));
     }
-    if let Some(default) = &col.default {
+    if let Some(generated) = &col.generated {
+        attributes.push(FieldAttribute::Generated(generated.clone()));
+    } else if let Some(default) = &col.default {
         if default.contains("nextval") {
-            attributes.push(FieldAttribute::Default(
-                petrol_core::schema::DefaultValue::AutoIncrement,
-            ));
+            attributes.push(FieldAttribute::Default(DefaultValue::AutoIncrement));
+        } else if default.contains("gen_random_uuid()")
+            || default.contains("uuid_generate_v4()")
+            || default == strip_parentheses(Sqlite::RANDOM_UUID)
+        {
+            attributes.push(FieldAttribute::Default(DefaultValue::Uuid));
+        } else if default == "now()" || default == "CURRENT_TIMESTAMP" {
+            // `@updatedAt` columns default to now() on their own.
+            if !col.updated_at {
+                attributes.push(FieldAttribute::Default(DefaultValue::Now));
+            }
+        } else {
+            attributes.push(FieldAttribute::Default(literal_default(default)));
         }
     }
 
@@ -250,36 +1616,134 @@ fn build_field(col: &DbColumn) -> Result<Field> {
         r#type: FieldType::Scalar(
             scalar,
             TypeModifiers {
-                optional: col.is_nullable,
-                list: false,
+                optional: col.is_nullable && !list,
+                list,
             },
         ),
         attributes,
+        documentation: col.comment.clone(),
     })
 }
 
-fn map_sql_to_scalar(data_type: &str) -> Option<ScalarType> {
-    match data_type {
-        "integer" | "int4" => Some(ScalarType::Int),
-        "bigint" | "int8" => Some(ScalarType::BigInt),
-        "double precision" | "float8" => Some(ScalarType::Float),
-        "numeric" | "decimal" => Some(ScalarType::Decimal),
-        "text" | "character varying" | "varchar" => Some(ScalarType::String),
-        "boolean" => Some(ScalarType::Boolean),
-        "timestamp with time zone" | "timestamp without time zone" => Some(ScalarType::DateTime),
-        "date" => Some(ScalarType::Date),
-        "uuid" => Some(ScalarType::Uuid),
-        "json" | "jsonb" => Some(ScalarType::Json),
-        "bytea" => Some(ScalarType::Bytes),
-        _ => None,
+/// Plain literals such as `42`, `true` or `'draft'::text` map back to schema literals,
+/// anything else is kept verbatim as `dbgenerated(...)`.
+fn literal_default(default: &str) -> DefaultValue {
+    if let Ok(value) = default.parse() {
+        return DefaultValue::Int(value);
+    }
+    if let Ok(value) = default.parse() {
+        return DefaultValue::Float(value);
     }
+    match default {
+        "true" => return DefaultValue::Boolean(true),
+        "false" => return DefaultValue::Boolean(false),
+        _ => {}
+    }
+    let literal = default
+        .rsplit_once("::")
+        .map_or(default, |(literal, _)| literal);
+    if let Some(inner) = literal
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+    {
+        if !inner.replace("''", "").contains('\'') {
+            return DefaultValue::String(inner.replace("''", "'"));
+        }
+    }
+    DefaultValue::DbGenerated(default.to_string())
 }
 
+fn map_sql_type(col: &DbColumn) -> Option<(ScalarType, Option<NativeType>)> {
+    let length = col.character_maximum_length.map(|len| len as u32);
+    let precision = col.datetime_precision.map(|precision| precision as u32);
+
+    let mapped = match col.data_type.as_str() {
+        "integer" | "int4" => (ScalarType::Int, None),
+        "smallint" | "int2" => (ScalarType::Int, Some(NativeType::SmallInt)),
+        "bigint" | "int8" => (ScalarType::BigInt, None),
+        "double precision" | "float8" => (ScalarType::Float, None),
+        "real" | "float4" => (ScalarType::Float, Some(NativeType::Real)),
+        "numeric" | "decimal" => {
+            let args = col
+                .numeric_precision
+                .zip(col.numeric_scale)
+                .map(|(precision, scale)| (precision as u32, scale as u32));
+            (
+                ScalarType::Decimal,
+                args.map(|args| NativeType::Decimal(Some(args))),
+            )
+        }
+        "text" => (ScalarType::String, None),
+        "character varying" | "varchar" => (ScalarType::String, Some(NativeType::VarChar(length))),
+        "character" | "bpchar" => (ScalarType::String, Some(NativeType::Char(length))),
+        "boolean" | "bool" => (ScalarType::Boolean, None),
+        "timestamp with time zone" | "timestamptz" => (
+            ScalarType::DateTime,
+            precision.map(|precision| NativeType::Timestamptz(Some(precision))),
+        ),
+        "timestamp without time zone" | "timestamp" => {
+            (ScalarType::DateTime, Some(NativeType::Timestamp(precision)))
+        }
+        "time without time zone" | "time" => {
+            (ScalarType::DateTime, Some(NativeType::Time(precision)))
+        }
+        "date" => (ScalarType::Date, None),
+        "uuid" => (ScalarType::Uuid, None),
+        "jsonb" => (ScalarType::Json, None),
+        "json" => (ScalarType::Json, Some(NativeType::Json)),
+        "bytea" => (ScalarType::Bytes, None),
+        "binary" if length == Some(16) => (ScalarType::Uuid, Some(NativeType::Binary(length))),
+        "binary" => (ScalarType::Bytes, Some(NativeType::Binary(length))),
+        "citext" => (ScalarType::String, Some(NativeType::Citext)),
+        "USER-DEFINED" if col.udt_name == "citext" => {
+            (ScalarType::String, Some(NativeType::Citext))
+        }
+        _ => return None,
+    };
+    Some(mapped)
+}
+
+const DEFAULT_DATETIME_PRECISION: i32 = 6;
+
+struct DbCheck {
+    name: String,
+    expr: String,
+    columns: Vec<String>,
+}
+
+struct DbIndex {
+    columns: Vec<String>,
+    unique: bool,
+}
+
+struct DbForeignKey {
+    /// The Postgres schema of the table; the other databases have none.
+    schema: Option<String>,
+    table: String,
+    columns: Vec<String>,
+    references_schema: Option<String>,
+    references_table: String,
+    references_columns: Vec<String>,
+}
+
+/// A table and, on Postgres, the schema it is in.
+type TableName = (Option<String>, String);
+
+#[derive(Clone)]
 struct DbColumn {
     name: String,
     data_type: String,
+    udt_name: String,
+    character_maximum_length: Option<i32>,
+    numeric_precision: Option<i32>,
+    numeric_scale: Option<i32>,
+    datetime_precision: Option<i32>,
     is_nullable: bool,
     default: Option<String>,
+    generated: Option<String>,
+    updated_at: bool,
+    primary_key_position: Option<i32>,
+    comment: Option<String>,
 }
 
 const DEFAULT_SCHEMA: &str = r#"
@@ -300,3 +1764,72 @@ model User {
   createdAt DateTime @default(now())
 }
 "#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petrol_parser::parse_schema;
+
+    fn schema(models: &str) -> Schema {
+        parse_schema(&format!(
+            "datasource db {{\n  provider = \"postgresql\"\n  url = env(\"DATABASE_URL\")\n}}\n\n\
+             generator client {{\n  provider = \"petrol-client-rust\"\n}}\n\n{}",
+            models
+        ))
+        .unwrap()
+    }
+
+    fn steps(from: &Schema, to: &Schema) -> Vec<String> {
+        plan_migration(from, to)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn plans_new_tables_and_columns_with_foreign_keys_last() {
+        let from =
+            schema("model User {\n  id Int @id @default(autoincrement())\n  email String\n}\n");
+        let to = schema(
+            "model User {\n  id Int @id @default(autoincrement())\n  email String @unique\n  \
+             name String?\n  posts Post[]\n}\n\n\
+             model Post {\n  id Int @id @default(autoincrement())\n  authorId Int\n  \
+             author User @relation(fields: [authorId], references: [id])\n}\n",
+        );
+        assert_eq!(
+            steps(&from, &to),
+            vec![
+                "added table Post",
+                "added column User.name",
+                "added unique constraint (email) on User",
+                "added the foreign keys of table Post",
+            ]
+        );
+        let sql = render_statements(&migration_sql(&to, &plan_migration(&from, &to)).unwrap());
+        assert!(sql.starts_with("CREATE TABLE IF NOT EXISTS \"Post\""));
+        assert!(sql.contains("ALTER TABLE \"User\" ADD COLUMN \"name\" TEXT;\n"));
+        assert!(sql.contains(
+            "ADD CONSTRAINT \"Post_authorId_fkey\" FOREIGN KEY (\"authorId\") REFERENCES \"User\""
+        ));
+        assert!(steps(&to, &to).is_empty());
+    }
+
+    #[test]
+    fn compares_check_expressions_by_grouping() {
+        let model = |check: &str| {
+            schema(&format!(
+                "model Person {{\n  id Int @id\n  age Int\n\n  @@check(\"{}\")\n}}\n",
+                check
+            ))
+        };
+        let written = model("(age + 1) * 2 > 0");
+        assert!(steps(&written, &model("(((age + 1) * 2) > 0)")).is_empty());
+        assert_eq!(
+            steps(&written, &model("age + 1 * 2 > 0")),
+            vec![
+                "removed check Person_check on Person",
+                "added check Person_check on Person",
+            ]
+        );
+    }
+}
@@ -0,0 +1,944 @@
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+use color_eyre::eyre::{eyre, Result};
+use inflector::Inflector;
+use petrol_client::PetrolClient;
+use petrol_core::dialect::{dialect_for, Dialect};
+use petrol_core::diff::{
+    migration_sql, plan_migration, plan_rollback, possible_renames, safe_migration_sql,
+    MigrationStep, PossibleRename,
+};
+use petrol_core::migrations::Migration;
+use petrol_core::schema::{FieldAttribute, ModelAttribute, Schema};
+use petrol_parser::parse_schema_file;
+use sqlx::{mysql::MySqlPoolOptions, postgres::PgPoolOptions, Executor};
+use tracing::warn;
+
+use crate::{introspect_database, render_statements, schema_namespaces};
+
+const MIGRATION_FILE: &str = "migration.sql";
+const DOWN_FILE: &str = "down.sql";
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateCommand {
+    /// Write a migration for the schema changes and apply pending migrations
+    Dev {
+        #[arg(long, default_value = "schema.petrol")]
+        schema: PathBuf,
+        #[arg(long, default_value = "migrations")]
+        migrations: PathBuf,
+        #[arg(long, env = "DATABASE_URL")]
+        database_url: String,
+        /// Empty database to replay the migrations into
+        #[arg(long, env = "SHADOW_DATABASE_URL")]
+        shadow_database_url: Option<String>,
+        /// Create the shadow database as a temporary one on the server of `database_url`
+        #[arg(long)]
+        create_shadow_database: bool,
+        /// Name of the new migration
+        #[arg(long)]
+        name: String,
+        /// Split up steps that would lock a table for a whole scan
+        #[arg(long)]
+        safe: bool,
+    },
+    /// Apply pending migrations
+    Deploy {
+        #[arg(long, default_value = "schema.petrol")]
+        schema: PathBuf,
+        #[arg(long, default_value = "migrations")]
+        migrations: PathBuf,
+        #[arg(long, env = "DATABASE_URL")]
+        database_url: String,
+    },
+    /// Write an initial migration from an existing database and mark it applied there
+    Baseline {
+        #[arg(long, default_value = "schema.petrol")]
+        schema: PathBuf,
+        #[arg(long, default_value = "migrations")]
+        migrations: PathBuf,
+        #[arg(long, env = "DATABASE_URL")]
+        database_url: String,
+        /// Empty database to check the migration against
+        #[arg(long, env = "SHADOW_DATABASE_URL")]
+        shadow_database_url: Option<String>,
+        /// Create the shadow database as a temporary one on the server of `database_url`
+        #[arg(long)]
+        create_shadow_database: bool,
+        /// Name of the migration
+        #[arg(long, default_value = "baseline")]
+        name: String,
+    },
+    /// Warn about schema changes that would lock tables for long
+    Lint {
+        #[arg(long, default_value = "schema.petrol")]
+        schema: PathBuf,
+        #[arg(long, default_value = "migrations")]
+        migrations: PathBuf,
+        #[arg(long, env = "DATABASE_URL")]
+        database_url: String,
+        /// Empty database to replay the migrations into
+        #[arg(long, env = "SHADOW_DATABASE_URL")]
+        shadow_database_url: Option<String>,
+        /// Create the shadow database as a temporary one on the server of `database_url`
+        #[arg(long)]
+        create_shadow_database: bool,
+        /// Lint the steps as `migrate dev --safe` writes them
+        #[arg(long)]
+        safe: bool,
+    },
+    /// Undo the last applied migration, or every one applied after `--to`
+    Rollback {
+        #[arg(long, default_value = "schema.petrol")]
+        schema: PathBuf,
+        #[arg(long, default_value = "migrations")]
+        migrations: PathBuf,
+        #[arg(long, env = "DATABASE_URL")]
+        database_url: String,
+        /// Last migration to keep applied
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Report how a database drifted from the migration history
+    Diff {
+        #[arg(long, default_value = "schema.petrol")]
+        schema: PathBuf,
+        /// Compare from the state the migrations in this directory produce
+        #[arg(long, default_value = "migrations")]
+        from_migrations: PathBuf,
+        /// Compare to the database at this URL
+        #[arg(long)]
+        to_database: String,
+        /// Empty database to replay the migrations into
+        #[arg(long, env = "SHADOW_DATABASE_URL")]
+        shadow_database_url: Option<String>,
+        /// Create the shadow database as a temporary one on the server of `database_url`
+        #[arg(long)]
+        create_shadow_database: bool,
+        /// Write the reconciling SQL to this file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+pub async fn handle_migrate(command: MigrateCommand) -> Result<()> {
+    match command {
+        MigrateCommand::Dev {
+            schema,
+            migrations,
+            database_url,
+            shadow_database_url,
+            create_shadow_database,
+            name,
+            safe,
+        } => {
+            handle_dev(
+                &schema,
+                &migrations,
+                &database_url,
+                shadow_database_url.as_deref(),
+                create_shadow_database,
+                &name,
+                safe,
+            )
+            .await
+        }
+        MigrateCommand::Deploy {
+            schema,
+            migrations,
+            database_url,
+        } => handle_deploy(&schema, &migrations, &database_url).await,
+        MigrateCommand::Baseline {
+            schema,
+            migrations,
+            database_url,
+            shadow_database_url,
+            create_shadow_database,
+            name,
+        } => {
+            handle_baseline(
+                &schema,
+                &migrations,
+                &database_url,
+                shadow_database_url.as_deref(),
+                create_shadow_database,
+                &name,
+            )
+            .await
+        }
+        MigrateCommand::Lint {
+            schema,
+            migrations,
+            database_url,
+            shadow_database_url,
+            create_shadow_database,
+            safe,
+        } => {
+            handle_lint(
+                &schema,
+                &migrations,
+                &database_url,
+                shadow_database_url.as_deref(),
+                create_shadow_database,
+                safe,
+            )
+            .await
+        }
+        MigrateCommand::Rollback {
+            schema,
+            migrations,
+            database_url,
+            to,
+        } => handle_rollback(&schema, &migrations, &database_url, to.as_deref()).await,
+        MigrateCommand::Diff {
+            schema,
+            from_migrations,
+            to_database,
+            shadow_database_url,
+            create_shadow_database,
+            out,
+        } => {
+            handle_diff(
+                &schema,
+                &from_migrations,
+                &to_database,
+                shadow_database_url.as_deref(),
+                create_shadow_database,
+                out,
+            )
+            .await
+        }
+    }
+}
+
+async fn handle_dev(
+    schema_path: &Path,
+    migrations_dir: &Path,
+    database_url: &str,
+    shadow_database_url: Option<&str>,
+    create_shadow_database: bool,
+    name: &str,
+    safe: bool,
+) -> Result<()> {
+    let mut schema = parse_schema_file(schema_path)?;
+    schema.validate()?;
+    let mut migrations = read_migrations(migrations_dir)?;
+
+    let current = replay_migrations(
+        &schema,
+        database_url,
+        shadow_database_url,
+        create_shadow_database,
+        &migrations,
+    )
+    .await?;
+    let mut steps = plan_migration(&current, &schema);
+    let mut asked = Vec::new();
+    while let Some(candidate) = possible_renames(&steps)
+        .into_iter()
+        .find(|candidate| !asked.contains(candidate))
+    {
+        if confirm_rename(&schema, &candidate)? {
+            add_rename_hint(&mut schema, &candidate);
+            steps = plan_migration(&current, &schema);
+        }
+        asked.push(candidate);
+    }
+    if steps.is_empty() {
+        println!("No schema changes since the last migration");
+    } else {
+        let lossy: Vec<MigrationStep> = steps
+            .iter()
+            .filter(|step| step.loses_data())
+            .cloned()
+            .collect();
+        let down = down_script(&current, &schema, &lossy)?;
+
+        let migration = Migration {
+            name: format!(
+                "{}_{}",
+                chrono::Utc::now().format("%Y%m%d%H%M%S"),
+                name.to_snake_case()
+            ),
+            sql: render_statements(&if safe {
+                safe_migration_sql(&schema, &steps)?
+            } else {
+                migration_sql(&schema, &steps)?
+            }),
+            down: Some(down),
+        };
+        let dir = migrations_dir.join(&migration.name);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(MIGRATION_FILE), &migration.sql)?;
+        if let Some(down) = &migration.down {
+            fs::write(dir.join(DOWN_FILE), down)?;
+        }
+        println!("Created migration {}:", migration.name);
+        print_steps(&steps);
+        if !lossy.is_empty() {
+            println!("Rolling it back cannot restore the data of:");
+            print_steps(&lossy);
+        }
+        print_lock_warnings(&lock_warnings(&schema, &steps, safe)?);
+        migrations.push(migration);
+    }
+
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    report_deployed(&deploy(database_url, dialect.as_ref(), &migrations).await?);
+    Ok(())
+}
+
+async fn handle_deploy(
+    schema_path: &Path,
+    migrations_dir: &Path,
+    database_url: &str,
+) -> Result<()> {
+    let schema = parse_schema_file(schema_path)?;
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    let migrations = read_migrations(migrations_dir)?;
+    report_deployed(&deploy(database_url, dialect.as_ref(), &migrations).await?);
+    Ok(())
+}
+
+/// Writes the migration creating what the database has, checks that replaying it gives
+/// the same schema, and records it as applied without running it. It has no down script,
+/// so rolling it back is refused rather than dropping everything.
+async fn handle_baseline(
+    schema_path: &Path,
+    migrations_dir: &Path,
+    database_url: &str,
+    shadow_database_url: Option<&str>,
+    create_shadow_database: bool,
+    name: &str,
+) -> Result<()> {
+    if !read_migrations(migrations_dir)?.is_empty() {
+        return Err(eyre!(
+            "{:?} already has migrations, a baseline has to be the first",
+            migrations_dir
+        ));
+    }
+    let namespaces = parse_schema_file(schema_path).map_or_else(
+        |_| vec!["public".to_string()],
+        |schema| schema_namespaces(&schema),
+    );
+    let live = introspect_database(database_url, &namespaces).await?;
+    if live.models.is_empty() {
+        return Err(eyre!("the database has no tables to baseline"));
+    }
+
+    let empty = Schema {
+        models: Vec::new(),
+        ..live.clone()
+    };
+    let migration = Migration {
+        name: format!(
+            "{}_{}",
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            name.to_snake_case()
+        ),
+        sql: render_statements(&migration_sql(&live, &plan_migration(&empty, &live))?),
+        down: None,
+    };
+    let replayed = replay_migrations(
+        &live,
+        database_url,
+        shadow_database_url,
+        create_shadow_database,
+        std::slice::from_ref(&migration),
+    )
+    .await?;
+    let drift = plan_migration(&replayed, &live);
+    if !drift.is_empty() {
+        warn!(
+            "the baseline does not reproduce the database exactly, it misses: {}",
+            drift
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+    }
+
+    let dir = migrations_dir.join(&migration.name);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(MIGRATION_FILE), &migration.sql)?;
+    println!("Created migration {}", migration.name);
+
+    let dialect = dialect_for(&live.datasource.provider)?;
+    let dialect = dialect.as_ref();
+    match dialect.name() {
+        "sqlite" => {
+            PetrolClient::new_sqlite(database_url)
+                .await?
+                .mark_migration_applied(dialect, &migration)
+                .await?
+        }
+        "mysql" => {
+            PetrolClient::new_mysql(database_url)
+                .await?
+                .mark_migration_applied(dialect, &migration)
+                .await?
+        }
+        _ => {
+            PetrolClient::new(database_url)
+                .await?
+                .mark_migration_applied(dialect, &migration)
+                .await?
+        }
+    }
+    println!("Marked migration {} as applied", migration.name);
+    Ok(())
+}
+
+/// Lints the steps `migrate dev` would write for the schema changes since the last
+/// migration, failing when one of them locks a table for long.
+async fn handle_lint(
+    schema_path: &Path,
+    migrations_dir: &Path,
+    database_url: &str,
+    shadow_database_url: Option<&str>,
+    create_shadow_database: bool,
+    safe: bool,
+) -> Result<()> {
+    let schema = parse_schema_file(schema_path)?;
+    schema.validate()?;
+    let migrations = read_migrations(migrations_dir)?;
+    let current = replay_migrations(
+        &schema,
+        database_url,
+        shadow_database_url,
+        create_shadow_database,
+        &migrations,
+    )
+    .await?;
+    let steps = plan_migration(&current, &schema);
+
+    let warnings = lock_warnings(&schema, &steps, safe)?;
+    if warnings.is_empty() {
+        println!("No schema changes that lock tables for long");
+        return Ok(());
+    }
+    print_lock_warnings(&warnings);
+    if !safe {
+        let split = warnings.len() - lock_warnings(&schema, &steps, true)?.len();
+        if split > 0 {
+            println!("`migrate dev --safe` splits up {} of them", split);
+        }
+    }
+    Err(eyre!(
+        "{} schema changes lock tables for long",
+        warnings.len()
+    ))
+}
+
+async fn handle_rollback(
+    schema_path: &Path,
+    migrations_dir: &Path,
+    database_url: &str,
+    to: Option<&str>,
+) -> Result<()> {
+    let schema = parse_schema_file(schema_path)?;
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    let dialect = dialect.as_ref();
+    let migrations = read_migrations(migrations_dir)?;
+    let rolled_back = match dialect.name() {
+        "sqlite" => {
+            PetrolClient::new_sqlite(database_url)
+                .await?
+                .rollback_migrations(dialect, &migrations, to)
+                .await?
+        }
+        "mysql" => {
+            PetrolClient::new_mysql(database_url)
+                .await?
+                .rollback_migrations(dialect, &migrations, to)
+                .await?
+        }
+        _ => {
+            PetrolClient::new(database_url)
+                .await?
+                .rollback_migrations(dialect, &migrations, to)
+                .await?
+        }
+    };
+    if rolled_back.is_empty() {
+        println!("No migrations to roll back");
+    }
+    for name in rolled_back {
+        println!("Rolled back migration {}", name);
+    }
+    Ok(())
+}
+
+/// Introspects the migrations the target database applied, replayed into a shadow
+/// database, and the target the same way, so anything that differs was changed outside
+/// the migrations. Migrations the target has not applied yet are listed as pending.
+async fn handle_diff(
+    schema_path: &Path,
+    migrations_dir: &Path,
+    database_url: &str,
+    shadow_database_url: Option<&str>,
+    create_shadow_database: bool,
+    out: Option<PathBuf>,
+) -> Result<()> {
+    let schema = parse_schema_file(schema_path)?;
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    let applied = applied_migration_names(database_url, dialect.as_ref()).await?;
+    let migrations = read_migrations(migrations_dir)?;
+    for name in &applied {
+        if !migrations.iter().any(|migration| &migration.name == name) {
+            warn!("applied migration {} is missing locally", name);
+        }
+    }
+    let (applied, pending): (Vec<_>, Vec<_>) = migrations
+        .into_iter()
+        .partition(|migration| applied.contains(&migration.name));
+    if !pending.is_empty() {
+        println!("Pending migrations the database has not applied:");
+        for migration in &pending {
+            println!("  {}", migration.name);
+        }
+    }
+
+    let expected = replay_migrations(
+        &schema,
+        database_url,
+        shadow_database_url,
+        create_shadow_database,
+        &applied,
+    )
+    .await?;
+    let actual = introspect_database(database_url, &schema_namespaces(&schema)).await?;
+
+    let steps = plan_migration(&expected, &actual);
+    if steps.is_empty() {
+        println!("No drift between the migrations and the database");
+        return Ok(());
+    }
+    println!("The database drifted from the migrations:");
+    print_steps(&steps);
+
+    let sql = render_statements(&migration_sql(&actual, &steps)?);
+    match out {
+        Some(path) => {
+            fs::write(&path, sql)?;
+            println!("SQL bringing the migrations in line written to {:?}", path);
+        }
+        None => print!("\n{}", sql),
+    }
+    Ok(())
+}
+
+/// The script undoing the migration from `current` to `schema`, headed by a comment
+/// listing the `lossy` steps whose data it cannot bring back.
+fn down_script(current: &Schema, schema: &Schema, lossy: &[MigrationStep]) -> Result<String> {
+    let mut down = String::new();
+    if !lossy.is_empty() {
+        down.push_str("-- Rolling back cannot restore the data this migration removes:\n");
+        for step in lossy {
+            down.push_str(&format!("--   {}\n", step));
+        }
+        down.push('\n');
+    }
+    down.push_str(&render_statements(&migration_sql(
+        current,
+        &plan_rollback(current, schema),
+    )?));
+    Ok(down)
+}
+
+/// Asks whether a drop and an add are one rename. Without a terminal to ask on, the
+/// pair stays a drop and an add, with a warning.
+fn confirm_rename(schema: &Schema, candidate: &PossibleRename) -> Result<bool> {
+    let (question, hint) = match candidate {
+        PossibleRename::Table { from, to, .. } => (
+            format!("Was table {} renamed to {}?", from, to),
+            format!("@@renamedFrom(\"{}\")", from),
+        ),
+        PossibleRename::Column {
+            table, from, to, ..
+        } => (
+            format!("Was column {}.{} renamed to {}?", table, from, to),
+            format!("@renamedFrom(\"{}\")", from),
+        ),
+    };
+    if rename_target(schema, candidate).is_none() {
+        return Ok(false);
+    }
+    if !io::stdin().is_terminal() {
+        warn!(
+            "{} It is dropped and added, losing its data; add {} if it was.",
+            question, hint
+        );
+        return Ok(false);
+    }
+
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// The model, and for a column the field, a rename hint belongs on.
+fn rename_target(schema: &Schema, candidate: &PossibleRename) -> Option<(usize, Option<usize>)> {
+    let (namespace, table) = match candidate {
+        PossibleRename::Table { schema, to, .. } => (schema, to),
+        PossibleRename::Column { schema, table, .. } => (schema, table),
+    };
+    let model = schema.models.iter().position(|model| {
+        &model.table_name() == table && &schema.namespace_for(model) == namespace
+    })?;
+    match candidate {
+        PossibleRename::Table { .. } => Some((model, None)),
+        PossibleRename::Column { to, .. } => {
+            let field = schema.models[model]
+                .fields
+                .iter()
+                .position(|field| field.is_scalar() && &field.column_name() == to)?;
+            Some((model, Some(field)))
+        }
+    }
+}
+
+fn add_rename_hint(schema: &mut Schema, candidate: &PossibleRename) {
+    let Some((model, field)) = rename_target(schema, candidate) else {
+        return;
+    };
+    let model = &mut schema.models[model];
+    match (candidate, field) {
+        (PossibleRename::Table { from, .. }, _) => model
+            .attributes
+            .push(ModelAttribute::RenamedFrom(from.clone())),
+        (PossibleRename::Column { from, .. }, Some(field)) => model.fields[field]
+            .attributes
+            .push(FieldAttribute::RenamedFrom(from.clone())),
+        (PossibleRename::Column { .. }, None) => {}
+    }
+}
+
+/// Each step that locks a table for long, with why and what to do instead.
+fn lock_warnings(schema: &Schema, steps: &[MigrationStep], safe: bool) -> Result<Vec<String>> {
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    Ok(steps
+        .iter()
+        .filter_map(|step| {
+            dialect
+                .lock_warning(step, safe)
+                .map(|warning| format!("{}\n    {}", step, warning))
+        })
+        .collect())
+}
+
+fn print_lock_warnings(warnings: &[String]) {
+    if warnings.is_empty() {
+        return;
+    }
+    println!("Steps that lock tables for long:");
+    for warning in warnings {
+        println!("  - {}", warning);
+    }
+}
+
+fn print_steps(steps: &[MigrationStep]) {
+    for step in steps {
+        println!("  - {}", step);
+    }
+}
+
+fn report_deployed(deployed: &[String]) {
+    if deployed.is_empty() {
+        println!("No pending migrations");
+    }
+    for name in deployed {
+        println!("Applied migration {}", name);
+    }
+}
+
+/// Every `<name>/migration.sql` under `dir`, with its `down.sql` if there is one, in
+/// name order; no directory means no migrations yet.
+fn read_migrations(dir: &Path) -> Result<Vec<Migration>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut migrations = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file = path.join(MIGRATION_FILE);
+        if !file.is_file() {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| eyre!("migration directory {:?} is not valid UTF-8", path))?;
+        migrations.push(Migration {
+            name: name.to_string(),
+            sql: fs::read_to_string(&file)?,
+            down: match path.join(DOWN_FILE) {
+                down if down.is_file() => Some(fs::read_to_string(down)?),
+                _ => None,
+            },
+        });
+    }
+    migrations.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(migrations)
+}
+
+async fn deploy(
+    database_url: &str,
+    dialect: &dyn Dialect,
+    migrations: &[Migration],
+) -> Result<Vec<String>> {
+    Ok(match dialect.name() {
+        "sqlite" => {
+            PetrolClient::new_sqlite(database_url)
+                .await?
+                .deploy_migrations(dialect, migrations)
+                .await?
+        }
+        "mysql" => {
+            PetrolClient::new_mysql(database_url)
+                .await?
+                .deploy_migrations(dialect, migrations)
+                .await?
+        }
+        _ => {
+            PetrolClient::new(database_url)
+                .await?
+                .deploy_migrations(dialect, migrations)
+                .await?
+        }
+    })
+}
+
+async fn applied_migration_names(database_url: &str, dialect: &dyn Dialect) -> Result<Vec<String>> {
+    Ok(match dialect.name() {
+        "sqlite" => {
+            PetrolClient::new_sqlite(database_url)
+                .await?
+                .applied_migration_names(dialect)
+                .await?
+        }
+        "mysql" => {
+            PetrolClient::new_mysql(database_url)
+                .await?
+                .applied_migration_names(dialect)
+                .await?
+        }
+        _ => {
+            PetrolClient::new(database_url)
+                .await?
+                .applied_migration_names(dialect)
+                .await?
+        }
+    })
+}
+
+/// The schema the migrations produce, read back from a shadow database. Without a
+/// shadow URL a temporary database is created next to `database_url` and dropped after,
+/// which has to be asked for with `create_shadow_database` unless it is a SQLite file.
+async fn replay_migrations(
+    schema: &Schema,
+    database_url: &str,
+    shadow_database_url: Option<&str>,
+    create_shadow_database: bool,
+    migrations: &[Migration],
+) -> Result<Schema> {
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    let namespaces = schema_namespaces(schema);
+    let replay = |url: String| {
+        let dialect = dialect.as_ref();
+        let namespaces = &namespaces;
+        async move {
+            deploy(&url, dialect, migrations).await?;
+            introspect_database(&url, namespaces).await
+        }
+    };
+
+    if let Some(url) = shadow_database_url {
+        if !introspect_database(url, &namespaces)
+            .await?
+            .models
+            .is_empty()
+        {
+            return Err(eyre!("the shadow database at {} is not empty", url));
+        }
+        return replay(url.to_string()).await;
+    }
+
+    if !create_shadow_database && dialect.name() != "sqlite" {
+        return Err(eyre!(
+            "pass --shadow-database-url, or --create-shadow-database to create a temporary \
+             database on the server of the database URL"
+        ));
+    }
+    let shadow = format!("petrol_shadow_{}", std::process::id());
+    match dialect.name() {
+        "sqlite" => {
+            let path = std::env::temp_dir().join(format!("{}.db", shadow));
+            let result = replay(format!("sqlite:{}", path.display())).await;
+            let _ = fs::remove_file(&path);
+            result
+        }
+        "mysql" => {
+            let admin = MySqlPoolOptions::new()
+                .max_connections(1)
+                .connect(database_url)
+                .await?;
+            let name = dialect.quote_identifier(&shadow);
+            admin
+                .execute(format!("CREATE DATABASE {}", name).as_str())
+                .await?;
+            let result = replay(with_database(database_url, &shadow)).await;
+            admin
+                .execute(format!("DROP DATABASE {}", name).as_str())
+                .await?;
+            result
+        }
+        _ => {
+            let admin = PgPoolOptions::new()
+                .max_connections(1)
+                .connect(database_url)
+                .await?;
+            let name = dialect.quote_identifier(&shadow);
+            admin
+                .execute(format!("CREATE DATABASE {}", name).as_str())
+                .await?;
+            let result = replay(with_database(database_url, &shadow)).await;
+            // The replay's pools may still be closing their connections.
+            admin
+                .execute(format!("DROP DATABASE {} WITH (FORCE)", name).as_str())
+                .await?;
+            result
+        }
+    }
+}
+
+/// `url` pointing at another database on the same server.
+fn with_database(url: &str, database: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, format!("?{}", query)),
+        None => (url, String::new()),
+    };
+    let authority = base.find("://").map_or(0, |index| index + 3);
+    let path = base[authority..]
+        .find('/')
+        .map_or(base.len(), |index| authority + index);
+    format!("{}/{}{}", &base[..path], database, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petrol_core::dialect::Sqlite;
+    use petrol_parser::parse_schema;
+
+    fn schema(models: &str) -> Schema {
+        parse_schema(&format!(
+            "datasource db {{\n  provider = \"postgresql\"\n  url = env(\"DATABASE_URL\")\n}}\n\n\
+             generator client {{\n  provider = \"petrol-client-rust\"\n}}\n\n{}",
+            models
+        ))
+        .unwrap()
+    }
+
+    fn steps(steps: &[MigrationStep]) -> Vec<String> {
+        steps.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn rollback_undoes_the_migration_and_flags_lost_data() {
+        let current = schema("model User {\n  id Int @id\n  email String\n  age Int\n}\n");
+        let next = schema(
+            "model User {\n  id Int @id\n  mail String @renamedFrom(\"email\")\n}\n\n\
+             model Post {\n  id Int @id\n}\n",
+        );
+        let forward = plan_migration(&current, &next);
+        assert_eq!(
+            steps(&plan_rollback(&current, &next)),
+            vec![
+                "renamed column User.mail to email",
+                "removed table Post",
+                "added column User.age",
+            ]
+        );
+
+        let lossy: Vec<_> = forward
+            .iter()
+            .filter(|step| step.loses_data())
+            .cloned()
+            .collect();
+        let down = down_script(&current, &next, &lossy).unwrap();
+        assert_eq!(
+            down,
+            "-- Rolling back cannot restore the data this migration removes:\n\
+             --   removed column User.age\n\n\
+             ALTER TABLE \"User\" RENAME COLUMN \"mail\" TO \"email\";\n\n\
+             DROP TABLE \"Post\";\n\n\
+             ALTER TABLE \"User\" ADD COLUMN \"age\" INTEGER NOT NULL;\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn baseline_records_the_existing_tables_once() {
+        let dir = std::env::temp_dir().join(format!("petrol_baseline_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite:{}", dir.join("app.db").display());
+        let client = PetrolClient::new_sqlite(&url).await.unwrap();
+        client
+            .apply_statements(
+                &Sqlite,
+                &[
+                    "CREATE TABLE \"User\" (\"id\" INTEGER PRIMARY KEY AUTOINCREMENT, \
+                     \"email\" TEXT NOT NULL);\n"
+                        .to_string(),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let schema = dir.join("schema.petrol");
+        let migrations = dir.join("migrations");
+        handle_baseline(&schema, &migrations, &url, None, false, "baseline")
+            .await
+            .unwrap();
+        let created = read_migrations(&migrations).unwrap();
+        assert_eq!(created.len(), 1);
+        assert!(created[0]
+            .sql
+            .contains("CREATE TABLE IF NOT EXISTS \"User\""));
+        assert!(created[0].down.is_none());
+        assert_eq!(
+            client.applied_migration_names(&Sqlite).await.unwrap(),
+            vec![created[0].name.clone()]
+        );
+
+        assert!(
+            handle_baseline(&schema, &migrations, &url, None, false, "baseline")
+                .await
+                .is_err()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn with_database_replaces_only_the_path() {
+        assert_eq!(
+            with_database("postgres://user:pw@host:5432/app?sslmode=require", "shadow"),
+            "postgres://user:pw@host:5432/shadow?sslmode=require"
+        );
+        assert_eq!(
+            with_database("mysql://root@localhost", "shadow"),
+            "mysql://root@localhost/shadow"
+        );
+        assert_eq!(
+            with_database("postgres://host/app", "shadow"),
+            "postgres://host/shadow"
+        );
+    }
+}
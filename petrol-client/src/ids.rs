@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+const BASE36: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const NANOID_DEFAULT_SIZE: usize = 21;
+
+static CUID_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+pub fn uuid_v4() -> Uuid {
+    Uuid::new_v4()
+}
+
+pub fn uuid_v7() -> Uuid {
+    Uuid::now_v7()
+}
+
+/// Collision-resistant id in the cuid layout: `c`, timestamp, counter, fingerprint, random.
+pub fn cuid() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default();
+    let counter = CUID_COUNTER.fetch_add(1, Ordering::Relaxed) as u64;
+    let fingerprint = std::process::id() as u64;
+    let random = Uuid::new_v4().as_u128() as u64;
+
+    let mut id = String::from("c");
+    id.push_str(&base36(millis, 8));
+    id.push_str(&base36(counter, 4));
+    id.push_str(&base36(fingerprint, 4));
+    id.push_str(&base36(random, 8));
+    id
+}
+
+pub fn nanoid(size: Option<usize>) -> String {
+    let size = size.unwrap_or(NANOID_DEFAULT_SIZE);
+    nanoid::nanoid!(size)
+}
+
+fn base36(mut value: u64, width: usize) -> String {
+    let mut digits = Vec::with_capacity(width);
+    for _ in 0..width {
+        digits.push(BASE36[(value % 36) as usize]);
+        value /= 36;
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap_or_default()
+}
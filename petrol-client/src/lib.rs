@@ -1,13 +1,30 @@
-use petrol_core::{schema::Schema, sql::schema_to_tables, PetrolError};
+use petrol_core::dialect::{dialect_for, Dialect};
+use petrol_core::migrations::{
+    applied_migrations_sql, forget_migration_sql, migrations_table, record_migration_sql, Migration,
+};
+use petrol_core::{schema::Schema, sql::schema_to_sql, PetrolError};
+use std::str::FromStr;
+
+use sqlx::mysql::MySqlPoolOptions;
+use sqlx::pool::PoolConnection;
 use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{
+    Database, Executor, IntoArguments, MySql, MySqlPool, PgPool, Pool, Postgres, Row, Sqlite,
+    SqlitePool,
+};
 use thiserror::Error;
 
+pub mod ids;
+pub mod query;
+
 pub use sqlx;
 
+/// A connection pool for one database. Postgres is the default, `PetrolClient<MySql>` and
+/// `PetrolClient<Sqlite>` talk to MySQL and SQLite.
 #[derive(Clone)]
-pub struct PetrolClient {
-    pool: PgPool,
+pub struct PetrolClient<DB: Database = Postgres> {
+    pool: Pool<DB>,
 }
 
 impl PetrolClient {
@@ -22,26 +39,329 @@ impl PetrolClient {
     pub fn from_pool(pool: PgPool) -> Self {
         Self { pool }
     }
+}
+
+impl PetrolClient<Sqlite> {
+    /// Connects to a `sqlite:` URL, creating the database file if it does not exist yet.
+    pub async fn new_sqlite(database_url: &str) -> Result<Self, ClientError> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await?;
+        Ok(Self { pool })
+    }
+
+    pub fn from_sqlite_pool(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+impl PetrolClient<MySql> {
+    pub async fn new_mysql(database_url: &str) -> Result<Self, ClientError> {
+        let pool = MySqlPoolOptions::new()
+            .max_connections(5)
+            .connect(database_url)
+            .await?;
+        Ok(Self { pool })
+    }
+
+    pub fn from_mysql_pool(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
 
-    pub fn pool(&self) -> &PgPool {
+impl<DB: Database> PetrolClient<DB>
+where
+    for<'c> &'c Pool<DB>: Executor<'c, Database = DB>,
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+{
+    pub fn pool(&self) -> &Pool<DB> {
         &self.pool
     }
 
     pub async fn ping(&self) -> Result<(), ClientError> {
-        sqlx::query("SELECT 1")
-            .execute(self.pool())
-            .await?
-            .rows_affected();
+        sqlx::query("SELECT 1").execute(self.pool()).await?;
         Ok(())
     }
 
     pub async fn apply_schema(&self, schema: &Schema) -> Result<(), ClientError> {
-        for table in schema_to_tables(schema) {
-            let sql = table.to_sql();
-            sqlx::query(&sql).execute(self.pool()).await?;
+        let dialect = dialect_for(&schema.datasource.provider)?;
+        self.apply_statements(dialect.as_ref(), &schema_to_sql(schema)?)
+            .await
+    }
+
+    /// Runs DDL as one transaction where the dialect allows it, under the dialect's
+    /// migration lock. A statement that cannot run in a transaction, such as
+    /// `CREATE INDEX CONCURRENTLY`, makes every statement run on its own instead.
+    pub async fn apply_statements(
+        &self,
+        dialect: &dyn Dialect,
+        statements: &[String],
+    ) -> Result<(), ClientError> {
+        // Dialects may change session settings while the schema is applied, so every
+        // statement runs on one connection, which is dropped rather than reused on error.
+        let mut conn = self.pool.acquire().await?;
+        let result = async {
+            lock(&mut conn, dialect).await?;
+            run_batch(&mut conn, dialect, statements).await?;
+            unlock(&mut conn, dialect).await
         }
-        Ok(())
+        .await;
+        if result.is_err() {
+            // Closing the connection rolls back the transaction and releases the lock.
+            conn.close_on_drop();
+        }
+        result
+    }
+
+    /// Applies the migrations `_petrol_migrations` does not list yet, each in its own
+    /// batch together with its bookkeeping row, and returns their names.
+    pub async fn deploy_migrations(
+        &self,
+        dialect: &dyn Dialect,
+        migrations: &[Migration],
+    ) -> Result<Vec<String>, ClientError>
+    where
+        for<'r> String: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+        usize: sqlx::ColumnIndex<DB::Row>,
+    {
+        let mut conn = self.pool.acquire().await?;
+        let result = async {
+            lock(&mut conn, dialect).await?;
+            let applied = applied_migrations(&mut conn, dialect).await?;
+            for (name, _) in &applied {
+                if !migrations.iter().any(|migration| &migration.name == name) {
+                    tracing::warn!("applied migration {} is missing locally", name);
+                }
+            }
+
+            let mut deployed = Vec::new();
+            for migration in migrations {
+                match applied.iter().find(|(name, _)| name == &migration.name) {
+                    Some((_, checksum)) => {
+                        if checksum != &migration.checksum() {
+                            tracing::warn!(
+                                "migration {} was modified after it was applied",
+                                migration.name
+                            );
+                        }
+                    }
+                    None => {
+                        let mut statements = migration.statements(dialect);
+                        statements.push(record_migration_sql(dialect, migration));
+                        run_batch(&mut conn, dialect, &statements).await?;
+                        deployed.push(migration.name.clone());
+                    }
+                }
+            }
+            unlock(&mut conn, dialect).await?;
+            Ok(deployed)
+        }
+        .await;
+        if result.is_err() {
+            conn.close_on_drop();
+        }
+        result
+    }
+
+    /// Names of the migrations `_petrol_migrations` lists, oldest first.
+    pub async fn applied_migration_names(
+        &self,
+        dialect: &dyn Dialect,
+    ) -> Result<Vec<String>, ClientError>
+    where
+        for<'r> String: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+        usize: sqlx::ColumnIndex<DB::Row>,
+    {
+        let mut conn = self.pool.acquire().await?;
+        let applied = applied_migrations(&mut conn, dialect).await?;
+        Ok(applied.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Records a migration in `_petrol_migrations` without running it, for a database
+    /// that already has what the migration creates.
+    pub async fn mark_migration_applied(
+        &self,
+        dialect: &dyn Dialect,
+        migration: &Migration,
+    ) -> Result<(), ClientError>
+    where
+        for<'r> String: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+        usize: sqlx::ColumnIndex<DB::Row>,
+    {
+        let mut conn = self.pool.acquire().await?;
+        let result = async {
+            lock(&mut conn, dialect).await?;
+            let applied = applied_migrations(&mut conn, dialect).await?;
+            if applied.iter().any(|(name, _)| name == &migration.name) {
+                return Err(ClientError::msg(format!(
+                    "migration {} is already applied",
+                    migration.name
+                )));
+            }
+            run_batch(
+                &mut conn,
+                dialect,
+                &[record_migration_sql(dialect, migration)],
+            )
+            .await?;
+            unlock(&mut conn, dialect).await
+        }
+        .await;
+        if result.is_err() {
+            conn.close_on_drop();
+        }
+        result
+    }
+
+    /// Runs the down scripts of the applied migrations after `to`, or of the last one
+    /// without it, newest first and each in its own batch together with removing its
+    /// bookkeeping row, and returns their names.
+    pub async fn rollback_migrations(
+        &self,
+        dialect: &dyn Dialect,
+        migrations: &[Migration],
+        to: Option<&str>,
+    ) -> Result<Vec<String>, ClientError>
+    where
+        for<'r> String: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+        usize: sqlx::ColumnIndex<DB::Row>,
+    {
+        let mut conn = self.pool.acquire().await?;
+        let result = async {
+            lock(&mut conn, dialect).await?;
+            let applied = applied_migrations(&mut conn, dialect).await?;
+            let keep = match to {
+                Some(target) => {
+                    applied
+                        .iter()
+                        .position(|(name, _)| name == target)
+                        .ok_or_else(|| {
+                            ClientError::msg(format!("migration {} has not been applied", target))
+                        })?
+                        + 1
+                }
+                None => applied.len().saturating_sub(1),
+            };
+
+            // Every script is found before the first runs, so a missing one changes nothing.
+            let mut scripts = Vec::new();
+            for (name, _) in applied[keep..].iter().rev() {
+                let migration = migrations
+                    .iter()
+                    .find(|migration| &migration.name == name)
+                    .ok_or_else(|| {
+                        ClientError::msg(format!("applied migration {} is missing locally", name))
+                    })?;
+                let statements = migration.down_statements(dialect).ok_or_else(|| {
+                    ClientError::msg(format!("migration {} has no down script", name))
+                })?;
+                scripts.push((name, statements));
+            }
+
+            let mut rolled_back = Vec::new();
+            for (name, mut statements) in scripts {
+                statements.push(forget_migration_sql(dialect, name));
+                run_batch(&mut conn, dialect, &statements).await?;
+                rolled_back.push(name.clone());
+            }
+            unlock(&mut conn, dialect).await?;
+            Ok(rolled_back)
+        }
+        .await;
+        if result.is_err() {
+            conn.close_on_drop();
+        }
+        result
+    }
+}
+
+async fn lock<DB: Database>(
+    conn: &mut PoolConnection<DB>,
+    dialect: &dyn Dialect,
+) -> Result<(), ClientError>
+where
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+{
+    if let Some((lock, _)) = dialect.migration_lock_sql() {
+        sqlx::query(&lock).execute(&mut **conn).await?;
+    }
+    Ok(())
+}
+
+async fn unlock<DB: Database>(
+    conn: &mut PoolConnection<DB>,
+    dialect: &dyn Dialect,
+) -> Result<(), ClientError>
+where
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+{
+    if let Some((_, unlock)) = dialect.migration_lock_sql() {
+        sqlx::query(&unlock).execute(&mut **conn).await?;
+    }
+    Ok(())
+}
+
+/// Names and checksums from `_petrol_migrations`, creating the table on first use.
+async fn applied_migrations<DB: Database>(
+    conn: &mut PoolConnection<DB>,
+    dialect: &dyn Dialect,
+) -> Result<Vec<(String, String)>, ClientError>
+where
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'r> String: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+    usize: sqlx::ColumnIndex<DB::Row>,
+{
+    let create = dialect.create_table_sql(&migrations_table())?;
+    sqlx::query(&create).execute(&mut **conn).await?;
+    let rows = sqlx::query(&applied_migrations_sql(dialect))
+        .fetch_all(&mut **conn)
+        .await?;
+    rows.iter()
+        .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+        .collect()
+}
+
+/// Runs statements between `BEGIN` and `COMMIT` when the dialect can roll DDL back.
+async fn run_batch<DB: Database>(
+    conn: &mut PoolConnection<DB>,
+    dialect: &dyn Dialect,
+    statements: &[String],
+) -> Result<(), ClientError>
+where
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+{
+    let mut transactional = dialect.transactional_ddl();
+    if let Some(statement) = statements
+        .iter()
+        .find(|statement| transactional && dialect.needs_autocommit(statement))
+    {
+        tracing::warn!(
+            "running statements one by one without a transaction, a failure leaves the \
+             schema partially applied: {} has to run outside a transaction",
+            statement.trim()
+        );
+        transactional = false;
+    }
+
+    if transactional {
+        sqlx::query("BEGIN").execute(&mut **conn).await?;
+    }
+    for sql in statements {
+        sqlx::query(sql).execute(&mut **conn).await?;
+    }
+    if transactional {
+        sqlx::query("COMMIT").execute(&mut **conn).await?;
     }
+    Ok(())
 }
 
 #[derive(Debug, Error)]
@@ -61,6 +381,7 @@ impl ClientError {
 }
 
 pub mod prelude {
+    pub use crate::query::{ScalarListFilter, ScalarListUpdate};
     pub use crate::{ClientError, PetrolClient};
     pub use sqlx;
 }
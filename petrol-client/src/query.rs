@@ -0,0 +1,75 @@
+use sqlx::postgres::PgHasArrayType;
+use sqlx::{Encode, Postgres, QueryBuilder, Type};
+
+/// A Postgres array predicate on a scalar list column, pushed onto a hand-written query;
+/// generated code does not use it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalarListFilter<T> {
+    Equals(Vec<T>),
+    Has(T),
+    HasSome(Vec<T>),
+    HasEvery(Vec<T>),
+    IsEmpty(bool),
+}
+
+impl<T> ScalarListFilter<T>
+where
+    T: for<'q> Encode<'q, Postgres> + Type<Postgres> + PgHasArrayType + Send + 'static,
+{
+    pub fn push_sql(self, column: &str, builder: &mut QueryBuilder<'_, Postgres>) {
+        let column = quote_ident(column);
+        match self {
+            ScalarListFilter::Equals(values) => {
+                builder.push(format!("{} = ", column));
+                builder.push_bind(values);
+            }
+            ScalarListFilter::Has(value) => {
+                builder.push_bind(value);
+                builder.push(format!(" = ANY({})", column));
+            }
+            ScalarListFilter::HasSome(values) => {
+                builder.push(format!("{} && ", column));
+                builder.push_bind(values);
+            }
+            ScalarListFilter::HasEvery(values) => {
+                builder.push(format!("{} @> ", column));
+                builder.push_bind(values);
+            }
+            ScalarListFilter::IsEmpty(empty) => {
+                let op = if empty { "=" } else { ">" };
+                builder.push(format!("cardinality({}) {} 0", column, op));
+            }
+        }
+    }
+}
+
+/// A Postgres `SET` assignment for a scalar list column, pushed onto a hand-written query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalarListUpdate<T> {
+    Set(Vec<T>),
+    Push(Vec<T>),
+}
+
+impl<T> ScalarListUpdate<T>
+where
+    T: for<'q> Encode<'q, Postgres> + Type<Postgres> + PgHasArrayType + Send + 'static,
+{
+    pub fn push_sql(self, column: &str, builder: &mut QueryBuilder<'_, Postgres>) {
+        let column = quote_ident(column);
+        match self {
+            ScalarListUpdate::Set(values) => {
+                builder.push(format!("{} = ", column));
+                builder.push_bind(values);
+            }
+            ScalarListUpdate::Push(values) => {
+                builder.push(format!("{} = array_cat({}, ", column, column));
+                builder.push_bind(values);
+                builder.push(")");
+            }
+        }
+    }
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
@@ -1,5 +1,11 @@
+use inflector::cases::pascalcase::to_pascal_case;
 use inflector::cases::snakecase::to_snake_case;
-use petrol_core::schema::{Field, FieldType, Model, ScalarType, Schema};
+use petrol_core::dialect::{dialect_for, Dialect, MySql, Postgres, Sqlite};
+use petrol_core::schema::{
+    DefaultValue, Field, FieldType, Model, ModelAttribute, NativeType, ScalarType, Schema,
+    UpdatedAtStrategy,
+};
+use petrol_core::sql::SqlTable;
 use petrol_core::PetrolError;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
@@ -28,15 +34,21 @@ pub fn generate_with_options(
         return Err(PetrolError::validation("schema contains no models"));
     }
 
+    let backend = Backend::for_schema(schema)?;
     let module_ident = format_ident!("{}", options.module_name);
     let mut modules = Vec::new();
     let mut re_exports = Vec::new();
 
     for model in &schema.models {
-        modules.push(render_model_module(model));
+        let where_unique = render_where_unique(model, backend);
         let module_name = format_ident!("{}", to_snake_case(&model.name));
         let struct_ident = format_ident!("{}", model.name);
         re_exports.push(quote! { pub use self::#module_ident::#module_name::#struct_ident; });
+        if !where_unique.is_empty() {
+            let enum_ident = format_ident!("{}WhereUnique", model.name);
+            re_exports.push(quote! { pub use self::#module_ident::#module_name::#enum_ident; });
+        }
+        modules.push(render_model_module(schema, model, where_unique, backend));
     }
 
     let tokens: TokenStream = quote! {
@@ -50,7 +62,86 @@ pub fn generate_with_options(
     Ok(tokens.to_string())
 }
 
-fn render_model_module(model: &Model) -> TokenStream {
+/// The sqlx driver generated code is written against, picked by the datasource provider.
+#[derive(Clone, Copy)]
+enum Backend {
+    Postgres,
+    MySql,
+    Sqlite,
+}
+
+impl Backend {
+    fn for_schema(schema: &Schema) -> Result<Self, PetrolError> {
+        Ok(match dialect_for(&schema.datasource.provider)?.name() {
+            "mysql" => Backend::MySql,
+            "sqlite" => Backend::Sqlite,
+            _ => Backend::Postgres,
+        })
+    }
+
+    fn dialect(self) -> &'static dyn Dialect {
+        match self {
+            Backend::Postgres => &Postgres,
+            Backend::MySql => &MySql,
+            Backend::Sqlite => &Sqlite,
+        }
+    }
+
+    fn database(self) -> TokenStream {
+        match self {
+            Backend::Postgres => quote! { petrol_client::sqlx::Postgres },
+            Backend::MySql => quote! { petrol_client::sqlx::MySql },
+            Backend::Sqlite => quote! { petrol_client::sqlx::Sqlite },
+        }
+    }
+
+    fn row(self) -> TokenStream {
+        match self {
+            Backend::Postgres => quote! { petrol_client::sqlx::postgres::PgRow },
+            Backend::MySql => quote! { petrol_client::sqlx::mysql::MySqlRow },
+            Backend::Sqlite => quote! { petrol_client::sqlx::sqlite::SqliteRow },
+        }
+    }
+
+    /// Outside Postgres uuids are text unless stored as `@db.Binary`; sqlx reads and
+    /// writes the text form through `Hyphenated`.
+    fn text_uuid(self, field: &Field) -> bool {
+        !matches!(self, Backend::Postgres)
+            && matches!(field.r#type, FieldType::Scalar(ScalarType::Uuid, _))
+            && !matches!(field.native_type(), Some(NativeType::Binary(_)))
+    }
+
+    /// `value` as it is bound to a query parameter for `field`.
+    fn bind_value(self, field: &Field, value: TokenStream) -> TokenStream {
+        if self.text_uuid(field) {
+            quote! { #value.hyphenated() }
+        } else {
+            value
+        }
+    }
+
+    fn get_column(self, field: &Field) -> TokenStream {
+        let column = field.column_name();
+        if !self.text_uuid(field) {
+            return quote! { row.try_get(#column)? };
+        }
+        if field.r#type.modifiers().optional {
+            quote! {
+                row.try_get::<Option<uuid::fmt::Hyphenated>, _>(#column)?
+                    .map(uuid::fmt::Hyphenated::into_uuid)
+            }
+        } else {
+            quote! { row.try_get::<uuid::fmt::Hyphenated, _>(#column)?.into_uuid() }
+        }
+    }
+}
+
+fn render_model_module(
+    schema: &Schema,
+    model: &Model,
+    where_unique: TokenStream,
+    backend: Backend,
+) -> TokenStream {
     let module_ident = format_ident!("{}", to_snake_case(&model.name));
     let struct_ident = format_ident!("{}", model.name);
 
@@ -65,20 +156,614 @@ fn render_model_module(model: &Model) -> TokenStream {
         .map(|field| render_struct_field(field))
         .collect();
 
+    let touch = match schema.generator.updated_at {
+        UpdatedAtStrategy::Client => render_touch(model),
+        UpdatedAtStrategy::Database => quote! {},
+    };
+
+    let generated_defaults = render_generated_defaults(model);
+    let from_row = render_from_row(model, &fields, backend);
+    let writes = render_writes(schema, model, &fields, !where_unique.is_empty(), backend);
+    let many_to_many = render_many_to_many(schema, model, backend);
+
+    let docs = render_docs(model.documentation.as_deref());
+
     let module_tokens: TokenStream = quote! {
         pub mod #module_ident {
             use super::*;
 
+            #docs
             #[derive(Debug, Clone, Serialize, Deserialize)]
             pub struct #struct_ident {
                 #( #struct_fields ),*
             }
+
+            #from_row
+            #writes
+            #touch
+            #generated_defaults
+            #where_unique
+            #many_to_many
         }
     };
 
     module_tokens
 }
 
+fn render_generated_defaults(model: &Model) -> TokenStream {
+    let struct_ident = format_ident!("{}", model.name);
+    let generators: Vec<_> = model
+        .fields
+        .iter()
+        .filter_map(|field| {
+            // `uuid()` is filled by the database, but `create` binds every column, so it
+            // gets a generator too.
+            let default = field
+                .default_value()
+                .filter(|d| d.is_client_generated() || matches!(d, DefaultValue::Uuid))?;
+            let fn_ident = format_ident!("generate_{}", to_snake_case(&field.name));
+            let as_uuid = matches!(field.r#type, FieldType::Scalar(ScalarType::Uuid, _));
+            let value = match default {
+                DefaultValue::Uuid if as_uuid => quote! { petrol_client::ids::uuid_v4() },
+                DefaultValue::Uuid => quote! { petrol_client::ids::uuid_v4().to_string() },
+                DefaultValue::UuidV7 if as_uuid => quote! { petrol_client::ids::uuid_v7() },
+                DefaultValue::UuidV7 => quote! { petrol_client::ids::uuid_v7().to_string() },
+                DefaultValue::Cuid => quote! { petrol_client::ids::cuid() },
+                DefaultValue::NanoId(size) => {
+                    let size = match size {
+                        Some(size) => {
+                            let size = *size as usize;
+                            quote! { Some(#size) }
+                        }
+                        None => quote! { None },
+                    };
+                    quote! { petrol_client::ids::nanoid(#size) }
+                }
+                _ => return None,
+            };
+            let ty = field_base_type(field);
+            Some(quote! {
+                pub fn #fn_ident() -> #ty {
+                    #value
+                }
+            })
+        })
+        .collect();
+
+    if generators.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        impl #struct_ident {
+            #( #generators )*
+        }
+    }
+}
+
+fn render_from_row(model: &Model, fields: &[&Field], backend: Backend) -> TokenStream {
+    let struct_ident = format_ident!("{}", model.name);
+    let row_type = backend.row();
+    let assignments: Vec<_> = fields
+        .iter()
+        .map(|field| {
+            let field_ident = format_ident!("{}", to_snake_case(&field.name));
+            let value = backend.get_column(field);
+            quote! { #field_ident: #value }
+        })
+        .collect();
+
+    quote! {
+        impl #struct_ident {
+            pub fn from_row(
+                row: &#row_type,
+            ) -> Result<Self, petrol_client::sqlx::Error> {
+                use petrol_client::sqlx::Row;
+                Ok(Self {
+                    #( #assignments ),*
+                })
+            }
+        }
+    }
+}
+
+/// `create` and `update` for the record, binding every column the client writes: `create`
+/// leaves autoincrement, `dbgenerated` and `@generated` columns to the database, `update`
+/// skips the primary key and `@generated` columns. `@updatedAt` columns are written with
+/// the current time when the client maintains them.
+fn render_writes(
+    schema: &Schema,
+    model: &Model,
+    fields: &[&Field],
+    has_where_unique: bool,
+    backend: Backend,
+) -> TokenStream {
+    let struct_ident = format_ident!("{}", model.name);
+    let dialect = backend.dialect();
+    let table = dialect.qualified_name(schema.namespace_for(model).as_deref(), &model.table_name());
+    let client_updated_at = matches!(schema.generator.updated_at, UpdatedAtStrategy::Client);
+    let value = |field: &Field| {
+        let field_ident = format_ident!("{}", to_snake_case(&field.name));
+        if client_updated_at && field.is_updated_at() {
+            let now = current_time(field);
+            return if field.r#type.modifiers().optional {
+                quote! { Some(#now) }
+            } else {
+                now
+            };
+        }
+        if backend.text_uuid(field) && field.r#type.modifiers().optional {
+            quote! { self.#field_ident.map(|value| value.hyphenated()) }
+        } else {
+            backend.bind_value(field, quote! { self.#field_ident.clone() })
+        }
+    };
+
+    let inserted: Vec<_> = fields
+        .iter()
+        .filter(|field| field.generated_expression().is_none())
+        .filter(|field| {
+            !matches!(
+                field.default_value(),
+                Some(DefaultValue::AutoIncrement | DefaultValue::DbGenerated(_))
+            )
+        })
+        .collect();
+    let insert_sql = format!(
+        "INSERT INTO {} ({}) VALUES (",
+        table,
+        inserted
+            .iter()
+            .map(|field| dialect.quote_identifier(&field.column_name()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let insert_separators = (0..inserted.len()).map(|index| if index == 0 { "" } else { ", " });
+    let insert_values: Vec<_> = inserted.iter().map(|field| value(field)).collect();
+
+    let id_fields = model.id_fields();
+    let updated: Vec<_> = fields
+        .iter()
+        .filter(|field| !id_fields.contains(&field.name) && field.generated_expression().is_none())
+        .collect();
+    let update_columns = updated.iter().enumerate().map(|(index, field)| {
+        format!(
+            "{}{} = ",
+            if index == 0 { "" } else { ", " },
+            dialect.quote_identifier(&field.column_name())
+        )
+    });
+    let update_values: Vec<_> = updated.iter().map(|field| value(field)).collect();
+
+    let database = backend.database();
+    let create = (!inserted.is_empty()).then(|| {
+        quote! {
+            pub async fn create(
+                &self,
+                client: &petrol_client::PetrolClient<#database>,
+            ) -> Result<(), petrol_client::ClientError> {
+                let mut builder = petrol_client::sqlx::QueryBuilder::<#database>::new(#insert_sql);
+                #( builder.push(#insert_separators); builder.push_bind(#insert_values); )*
+                builder.push(")");
+                builder.build().execute(client.pool()).await?;
+                Ok(())
+            }
+        }
+    });
+    let update = (has_where_unique && !updated.is_empty()).then(|| {
+        let where_unique = format_ident!("{}WhereUnique", model.name);
+        let update_sql = format!("UPDATE {} SET ", table);
+        quote! {
+            /// Writes this record over the row `key` selects.
+            pub async fn update(
+                &self,
+                client: &petrol_client::PetrolClient<#database>,
+                key: #where_unique,
+            ) -> Result<(), petrol_client::ClientError> {
+                let mut builder = petrol_client::sqlx::QueryBuilder::<#database>::new(#update_sql);
+                #( builder.push(#update_columns); builder.push_bind(#update_values); )*
+                builder.push(" WHERE ");
+                key.push_sql(&mut builder);
+                builder.build().execute(client.pool()).await?;
+                Ok(())
+            }
+        }
+    });
+
+    quote! {
+        impl #struct_ident {
+            #create
+            #update
+        }
+    }
+}
+
+/// connect/disconnect/set/load helpers for each implicit many-to-many field, all going
+/// through the relation's join table.
+fn render_many_to_many(schema: &Schema, model: &Model, backend: Backend) -> TokenStream {
+    let struct_ident = format_ident!("{}", model.name);
+    let Some(own_id) = model.fields.iter().find(|field| field.is_id()) else {
+        return quote! {};
+    };
+    let own_id_type = field_base_type(own_id);
+
+    let relations = schema.many_to_many();
+    let helpers: Vec<_> = model
+        .fields
+        .iter()
+        .filter_map(|field| {
+            let (relation, (own_column, other_column, other_name)) = relations
+                .iter()
+                .find_map(|relation| Some((relation, relation.side(&model.name, &field.name)?)))?;
+            let other = schema.find_model(other_name)?;
+            let other_id = other.fields.iter().find(|field| field.is_id())?;
+            let other_id_type = field_base_type(other_id);
+            let dialect = backend.dialect();
+            let join_table = dialect.table_name(&SqlTable::join_table(schema, relation)?);
+            let other_table = dialect
+                .qualified_name(schema.namespace_for(other).as_deref(), &other.table_name());
+            let other_ident = format_ident!("{}", other.name);
+            let other_module = format_ident!("{}", to_snake_case(&other.name));
+
+            let field = to_snake_case(&field.name);
+            let connect = format_ident!("connect_{}", field);
+            let disconnect = format_ident!("disconnect_{}", field);
+            let set = format_ident!("set_{}", field);
+            let load = format_ident!("load_{}", field);
+
+            if !matches!(backend, Backend::Postgres) {
+                let own_bind = if backend.text_uuid(own_id) {
+                    quote! { id.hyphenated() }
+                } else {
+                    quote! { id.clone() }
+                };
+                let other_bind = backend.bind_value(other_id, quote! { other });
+                let insert = match backend {
+                    Backend::MySql => "INSERT IGNORE INTO",
+                    _ => "INSERT OR IGNORE INTO",
+                };
+                let own_column = dialect.quote_identifier(own_column);
+                let other_column = dialect.quote_identifier(other_column);
+                let insert_sql = format!(
+                    "{} {} ({}, {}) VALUES (?, ?)",
+                    insert, join_table, own_column, other_column
+                );
+                let delete_sql = format!(
+                    "DELETE FROM {} WHERE {} = ? AND {} = ?",
+                    join_table, own_column, other_column
+                );
+                let clear_sql = format!("DELETE FROM {} WHERE {} = ?", join_table, own_column);
+                let load_sql = format!(
+                    "SELECT t.* FROM {} t JOIN {} j ON j.{} = t.{} WHERE j.{} = ?",
+                    other_table,
+                    join_table,
+                    other_column,
+                    dialect.quote_identifier(&other_id.column_name()),
+                    own_column
+                );
+                let database = backend.database();
+
+                // Arrays cannot be bound here, so each id is its own statement in one
+                // transaction.
+                return Some(quote! {
+                    pub async fn #connect(
+                        client: &petrol_client::PetrolClient<#database>,
+                        id: #own_id_type,
+                        ids: &[#other_id_type],
+                    ) -> Result<(), petrol_client::ClientError> {
+                        let mut tx = client.pool().begin().await?;
+                        for other in ids {
+                            petrol_client::sqlx::query(#insert_sql)
+                                .bind(#own_bind)
+                                .bind(#other_bind)
+                                .execute(&mut *tx)
+                                .await?;
+                        }
+                        tx.commit().await?;
+                        Ok(())
+                    }
+
+                    pub async fn #disconnect(
+                        client: &petrol_client::PetrolClient<#database>,
+                        id: #own_id_type,
+                        ids: &[#other_id_type],
+                    ) -> Result<(), petrol_client::ClientError> {
+                        let mut tx = client.pool().begin().await?;
+                        for other in ids {
+                            petrol_client::sqlx::query(#delete_sql)
+                                .bind(#own_bind)
+                                .bind(#other_bind)
+                                .execute(&mut *tx)
+                                .await?;
+                        }
+                        tx.commit().await?;
+                        Ok(())
+                    }
+
+                    /// Replaces every connection of this record with `ids`.
+                    pub async fn #set(
+                        client: &petrol_client::PetrolClient<#database>,
+                        id: #own_id_type,
+                        ids: &[#other_id_type],
+                    ) -> Result<(), petrol_client::ClientError> {
+                        let mut tx = client.pool().begin().await?;
+                        petrol_client::sqlx::query(#clear_sql)
+                            .bind(#own_bind)
+                            .execute(&mut *tx)
+                            .await?;
+                        for other in ids {
+                            petrol_client::sqlx::query(#insert_sql)
+                                .bind(#own_bind)
+                                .bind(#other_bind)
+                                .execute(&mut *tx)
+                                .await?;
+                        }
+                        tx.commit().await?;
+                        Ok(())
+                    }
+
+                    pub async fn #load(
+                        client: &petrol_client::PetrolClient<#database>,
+                        id: #own_id_type,
+                    ) -> Result<Vec<super::#other_module::#other_ident>, petrol_client::ClientError> {
+                        let rows = petrol_client::sqlx::query(#load_sql)
+                            .bind(#own_bind)
+                            .fetch_all(client.pool())
+                            .await?;
+                        Ok(rows
+                            .iter()
+                            .map(super::#other_module::#other_ident::from_row)
+                            .collect::<Result<_, _>>()?)
+                    }
+                });
+            }
+
+            let insert_sql = format!(
+                "INSERT INTO {} (\"{}\", \"{}\") SELECT $1, UNNEST($2) ON CONFLICT DO NOTHING",
+                join_table, own_column, other_column
+            );
+            let delete_sql = format!(
+                "DELETE FROM {} WHERE \"{}\" = $1 AND \"{}\" = ANY($2)",
+                join_table, own_column, other_column
+            );
+            let clear_sql = format!("DELETE FROM {} WHERE \"{}\" = $1", join_table, own_column);
+            let load_sql = format!(
+                "SELECT t.* FROM {} t JOIN {} j ON j.\"{}\" = t.\"{}\" WHERE j.\"{}\" = $1",
+                other_table,
+                join_table,
+                other_column,
+                other_id.column_name(),
+                own_column
+            );
+
+            Some(quote! {
+                pub async fn #connect(
+                    client: &petrol_client::PetrolClient,
+                    id: #own_id_type,
+                    ids: &[#other_id_type],
+                ) -> Result<(), petrol_client::ClientError> {
+                    petrol_client::sqlx::query(#insert_sql)
+                        .bind(id)
+                        .bind(ids.to_vec())
+                        .execute(client.pool())
+                        .await?;
+                    Ok(())
+                }
+
+                pub async fn #disconnect(
+                    client: &petrol_client::PetrolClient,
+                    id: #own_id_type,
+                    ids: &[#other_id_type],
+                ) -> Result<(), petrol_client::ClientError> {
+                    petrol_client::sqlx::query(#delete_sql)
+                        .bind(id)
+                        .bind(ids.to_vec())
+                        .execute(client.pool())
+                        .await?;
+                    Ok(())
+                }
+
+                /// Replaces every connection of this record with `ids`.
+                pub async fn #set(
+                    client: &petrol_client::PetrolClient,
+                    id: #own_id_type,
+                    ids: &[#other_id_type],
+                ) -> Result<(), petrol_client::ClientError> {
+                    let mut tx = client.pool().begin().await?;
+                    petrol_client::sqlx::query(#clear_sql)
+                        .bind(id.clone())
+                        .execute(&mut *tx)
+                        .await?;
+                    petrol_client::sqlx::query(#insert_sql)
+                        .bind(id)
+                        .bind(ids.to_vec())
+                        .execute(&mut *tx)
+                        .await?;
+                    tx.commit().await?;
+                    Ok(())
+                }
+
+                pub async fn #load(
+                    client: &petrol_client::PetrolClient,
+                    id: #own_id_type,
+                ) -> Result<Vec<super::#other_module::#other_ident>, petrol_client::ClientError> {
+                    let rows = petrol_client::sqlx::query(#load_sql)
+                        .bind(id)
+                        .fetch_all(client.pool())
+                        .await?;
+                    Ok(rows
+                        .iter()
+                        .map(super::#other_module::#other_ident::from_row)
+                        .collect::<Result<_, _>>()?)
+                }
+                })
+        })
+        .collect();
+
+    if helpers.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        impl #struct_ident {
+            #( #helpers )*
+        }
+    }
+}
+
+/// One variant per unique key of the model: its primary key, `@unique` fields and
+/// `@@unique` lists. Compound keys become struct variants.
+fn render_where_unique(model: &Model, backend: Backend) -> TokenStream {
+    let enum_ident = format_ident!("{}WhereUnique", model.name);
+
+    let mut keys: Vec<Vec<String>> = vec![model.id_fields()];
+    keys.extend(
+        model
+            .fields
+            .iter()
+            .filter(|field| field.is_unique())
+            .map(|field| vec![field.name.clone()]),
+    );
+    keys.extend(model.attributes.iter().filter_map(|attr| match attr {
+        ModelAttribute::Unique(fields) => Some(fields.clone()),
+        _ => None,
+    }));
+
+    let mut variants = Vec::new();
+    let mut arms = Vec::new();
+    let mut seen = Vec::new();
+    for key in keys {
+        let fields: Option<Vec<&Field>> = key
+            .iter()
+            .map(|name| model.fields.iter().find(|field| &field.name == name))
+            .collect();
+        let Some(fields) = fields.filter(|fields| !fields.is_empty()) else {
+            continue;
+        };
+        let variant = format_ident!("{}", to_pascal_case(&key.join("_")));
+        if seen.contains(&variant) {
+            continue;
+        }
+        seen.push(variant.clone());
+
+        let idents: Vec<_> = fields
+            .iter()
+            .map(|field| format_ident!("{}", to_snake_case(&field.name)))
+            .collect();
+        let types: Vec<_> = fields.iter().map(|field| unique_key_type(field)).collect();
+        let binds: Vec<_> = fields
+            .iter()
+            .zip(&idents)
+            .map(|(field, ident)| backend.bind_value(field, quote! { #ident }))
+            .collect();
+        let predicates: Vec<_> = fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let joiner = if index == 0 { "" } else { " AND " };
+                format!(
+                    "{}{} = ",
+                    joiner,
+                    backend.dialect().quote_identifier(&field.column_name())
+                )
+            })
+            .collect();
+
+        if fields.len() == 1 {
+            let ident = &idents[0];
+            let ty = &types[0];
+            variants.push(quote! { #variant(#ty) });
+            arms.push(quote! {
+                Self::#variant(#ident) => {
+                    #( builder.push(#predicates); builder.push_bind(#binds); )*
+                }
+            });
+        } else {
+            variants.push(quote! { #variant { #( #idents: #types ),* } });
+            arms.push(quote! {
+                Self::#variant { #( #idents ),* } => {
+                    #( builder.push(#predicates); builder.push_bind(#binds); )*
+                }
+            });
+        }
+    }
+
+    if variants.is_empty() {
+        return quote! {};
+    }
+
+    let database = backend.database();
+    quote! {
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum #enum_ident {
+            #( #variants ),*
+        }
+
+        impl #enum_ident {
+            /// Appends the `WHERE` predicate selecting the row this key identifies.
+            pub fn push_sql(
+                self,
+                builder: &mut petrol_client::sqlx::QueryBuilder<'_, #database>,
+            ) {
+                match self {
+                    #( #arms )*
+                }
+            }
+        }
+    }
+}
+
+fn unique_key_type(field: &Field) -> TokenStream {
+    let base = field_base_type(field);
+    if field.r#type.modifiers().list {
+        quote! { Vec<#base> }
+    } else {
+        base
+    }
+}
+
+fn render_touch(model: &Model) -> TokenStream {
+    let struct_ident = format_ident!("{}", model.name);
+    let assignments: Vec<_> = model
+        .fields
+        .iter()
+        .filter(|field| field.is_updated_at())
+        .map(|field| {
+            let field_ident = format_ident!("{}", to_snake_case(&field.name));
+            let now = current_time(field);
+            if field.r#type.modifiers().optional {
+                quote! { self.#field_ident = Some(#now); }
+            } else {
+                quote! { self.#field_ident = #now; }
+            }
+        })
+        .collect();
+
+    if assignments.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        impl #struct_ident {
+            /// Sets every `@updatedAt` field to the current time; `create` and `update` write
+            /// the current time on their own.
+            pub fn touch(&mut self) {
+                #( #assignments )*
+            }
+        }
+    }
+}
+
+/// The current time as the Rust type of an `@updatedAt` field.
+fn current_time(field: &Field) -> TokenStream {
+    match field.native_type() {
+        Some(NativeType::Timestamp(_)) => quote! { chrono::Utc::now().naive_utc() },
+        Some(NativeType::Time(_)) => quote! { chrono::Utc::now().time() },
+        Some(NativeType::Date) => quote! { chrono::Utc::now().date_naive() },
+        _ => quote! { chrono::Utc::now() },
+    }
+}
+
 fn render_struct_field(field: &Field) -> TokenStream {
     let field_ident = format_ident!("{}", to_snake_case(&field.name));
     let ty = scalar_rust_type(field);
@@ -89,19 +774,70 @@ fn render_struct_field(field: &Field) -> TokenStream {
         quote! {}
     };
 
+    let docs = render_docs(field.documentation.as_deref());
+    let generated = field.generated_expression().map(|expr| {
+        let line = format!(" Read-only, generated by the database as `{}`.", expr);
+        quote! { #[doc = #line] }
+    });
+
     quote! {
+        #docs
+        #generated
         #serde_attr
         pub #field_ident: #ty
     }
 }
 
+fn render_docs(documentation: Option<&str>) -> TokenStream {
+    let lines = documentation
+        .into_iter()
+        .flat_map(str::lines)
+        .map(|line| format!(" {}", line));
+    quote! { #( #[doc = #lines] )* }
+}
+
+pub fn rust_type_name(field: &Field) -> Option<String> {
+    match field.r#type {
+        FieldType::Scalar(_, _) => Some(scalar_rust_type(field).to_string().replace(' ', "")),
+        FieldType::Relation(_) => None,
+    }
+}
+
 fn scalar_rust_type(field: &Field) -> TokenStream {
-    let (scalar, modifiers) = match &field.r#type {
-        FieldType::Scalar(scalar, modifiers) => (scalar, modifiers),
+    let modifiers = field.r#type.modifiers();
+    let base = field_base_type(field);
+
+    let ty = if modifiers.list {
+        quote! { Vec<#base> }
+    } else {
+        base
+    };
+
+    if modifiers.optional {
+        quote! { Option<#ty> }
+    } else {
+        ty
+    }
+}
+
+fn field_base_type(field: &Field) -> TokenStream {
+    let scalar = match &field.r#type {
+        FieldType::Scalar(scalar, _) => scalar,
         FieldType::Relation(_) => panic!("relation fields not supported here"),
     };
 
-    let base = match scalar {
+    match (scalar, field.native_type()) {
+        (_, Some(NativeType::SmallInt)) => quote! { i16 },
+        (_, Some(NativeType::Real)) => quote! { f32 },
+        (ScalarType::DateTime, Some(NativeType::Timestamp(_))) => quote! { chrono::NaiveDateTime },
+        (ScalarType::DateTime, Some(NativeType::Time(_))) => quote! { chrono::NaiveTime },
+        (ScalarType::DateTime, Some(NativeType::Date)) => quote! { chrono::NaiveDate },
+        (scalar, _) => scalar_base_type(scalar),
+    }
+}
+
+fn scalar_base_type(scalar: &ScalarType) -> TokenStream {
+    match scalar {
         ScalarType::Int => quote! { i32 },
         ScalarType::BigInt => quote! { i64 },
         ScalarType::Float => quote! { f64 },
@@ -113,17 +849,5 @@ fn scalar_rust_type(field: &Field) -> TokenStream {
         ScalarType::Uuid => quote! { uuid::Uuid },
         ScalarType::Json => quote! { serde_json::Value },
         ScalarType::Bytes => quote! { Vec<u8> },
-    };
-
-    let ty = if modifiers.list {
-        quote! { Vec<#base> }
-    } else {
-        base
-    };
-
-    if modifiers.optional {
-        quote! { Option<#ty> }
-    } else {
-        ty
     }
 }
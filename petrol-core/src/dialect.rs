@@ -0,0 +1,412 @@
+use crate::diff::MigrationStep;
+use crate::error::PetrolError;
+use crate::schema::{DefaultValue, Schema};
+use crate::sql::{updated_at_trigger_name, SqlColumn, SqlForeignKey, SqlTable, SqlType};
+
+mod mysql;
+mod postgres;
+mod sqlite;
+
+pub use mysql::MySql;
+pub use postgres::Postgres;
+pub use sqlite::Sqlite;
+
+/// How one database spells the tables Petrol builds. The datasource `provider` selects
+/// the dialect; everything that differs between databases goes through this trait.
+pub trait Dialect {
+    /// The canonical `provider` value.
+    fn name(&self) -> &'static str;
+
+    /// Rejects schema features this database cannot express.
+    fn check_schema(&self, _schema: &Schema) -> Result<(), PetrolError> {
+        Ok(())
+    }
+
+    /// Whether a backslash escapes the next character of a quoted string.
+    fn backslash_escapes(&self) -> bool {
+        false
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn qualified_name(&self, schema: Option<&str>, name: &str) -> String {
+        match schema {
+            Some(schema) => format!(
+                "{}.{}",
+                self.quote_identifier(schema),
+                self.quote_identifier(name)
+            ),
+            None => self.quote_identifier(name),
+        }
+    }
+
+    fn table_name(&self, table: &SqlTable) -> String {
+        self.qualified_name(table.schema.as_deref(), &table.name)
+    }
+
+    fn quote_columns(&self, columns: &[String]) -> String {
+        columns
+            .iter()
+            .map(|name| self.quote_identifier(name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn render_type(&self, sql_type: &SqlType) -> Result<String, PetrolError>;
+
+    fn render_default(
+        &self,
+        column: &SqlColumn,
+        default: &DefaultValue,
+    ) -> Result<String, PetrolError>;
+
+    /// Statements that must run before any table is created.
+    fn setup_sql(&self, _schema: &Schema) -> Result<Vec<String>, PetrolError> {
+        Ok(Vec::new())
+    }
+
+    fn column_sql(&self, column: &SqlColumn) -> Result<String, PetrolError> {
+        let mut fragment = format!(
+            "  {} {}",
+            self.quote_identifier(&column.name),
+            self.render_type(&column.sql_type)?
+        );
+        if !column.nullable {
+            fragment.push_str(" NOT NULL");
+        }
+        if let Some(default) = &column.default {
+            fragment.push_str(&format!(
+                " DEFAULT {}",
+                self.render_default(column, default)?
+            ));
+        }
+        if let Some(generated) = &column.generated {
+            fragment.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", generated));
+        }
+        Ok(fragment)
+    }
+
+    fn create_table_sql(&self, table: &SqlTable) -> Result<String, PetrolError> {
+        let mut fragments = table
+            .columns
+            .iter()
+            .map(|column| self.column_sql(column))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !table.primary_key.is_empty() {
+            fragments.push(format!(
+                "PRIMARY KEY ({})",
+                self.quote_columns(&table.primary_key)
+            ));
+        }
+        for unique in &table.uniques {
+            fragments.push(format!("UNIQUE ({})", self.quote_columns(unique)));
+        }
+        for check in &table.checks {
+            fragments.push(format!(
+                "CONSTRAINT {} CHECK ({})",
+                self.quote_identifier(&check.name),
+                check.expr
+            ));
+        }
+
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n{}\n);\n",
+            self.table_name(table),
+            fragments.join(",\n")
+        ))
+    }
+
+    fn foreign_key_constraint(&self, table: &SqlTable, foreign_key: &SqlForeignKey) -> String {
+        format!(
+            "CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({}) ON DELETE {} ON UPDATE CASCADE",
+            self.quote_identifier(&foreign_key_name(table, foreign_key)),
+            self.quote_columns(&foreign_key.columns),
+            self.qualified_name(
+                foreign_key.references_schema.as_deref(),
+                &foreign_key.references_table
+            ),
+            self.quote_columns(&foreign_key.references_columns),
+            foreign_key.on_delete
+        )
+    }
+
+    /// Foreign keys are added once every table exists, so tables may reference each other
+    /// (or themselves) in any order.
+    fn foreign_keys_sql(&self, table: &SqlTable) -> Result<Vec<String>, PetrolError>;
+
+    fn indexes_sql(&self, table: &SqlTable) -> Vec<String> {
+        table
+            .indexes
+            .iter()
+            .map(|columns| {
+                format!(
+                    "CREATE INDEX IF NOT EXISTS {} ON {} ({});\n",
+                    self.quote_identifier(&index_name(table, columns)),
+                    self.table_name(table),
+                    self.quote_columns(columns)
+                )
+            })
+            .collect()
+    }
+
+    fn comments_sql(&self, _table: &SqlTable) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Statements that run last, after [`Dialect::setup_sql`] and every table.
+    fn finish_sql(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Keeps `@updatedAt` columns current when the generator asks the database to.
+    fn updated_at_sql(&self, _tables: &[SqlTable]) -> Result<Vec<String>, PetrolError> {
+        Err(PetrolError::Unsupported(format!(
+            "updatedAt = \"database\" is not available for {}, use updatedAt = \"client\"",
+            self.name()
+        )))
+    }
+
+    /// The statements for one step of a migration plan.
+    fn step_sql(&self, step: &MigrationStep) -> Result<Vec<String>, PetrolError> {
+        standard_step_sql(self, step)
+    }
+
+    /// The statements for a step, split so that no lock is held for a whole table scan
+    /// where the database allows it.
+    fn safe_step_sql(&self, step: &MigrationStep) -> Result<Vec<String>, PetrolError> {
+        self.step_sql(step)
+    }
+
+    /// Why running a step, as [`Dialect::step_sql`] renders it or with `safe` as
+    /// [`Dialect::safe_step_sql`] does, locks a table for as long as it takes to scan or
+    /// rewrite it, and what to do instead.
+    fn lock_warning(&self, _step: &MigrationStep, _safe: bool) -> Option<String> {
+        None
+    }
+
+    /// Whether DDL can be rolled back, so a push either applies completely or not at all.
+    fn transactional_ddl(&self) -> bool {
+        false
+    }
+
+    /// Whether a statement has to run outside a transaction block, because it refuses to
+    /// run inside one or only helps once the statements before it have committed.
+    fn needs_autocommit(&self, _statement: &str) -> bool {
+        false
+    }
+
+    /// Statements that take and release a lock held while a schema is applied, so two
+    /// deploys cannot migrate the same database at once.
+    fn migration_lock_sql(&self) -> Option<(String, String)> {
+        None
+    }
+}
+
+pub fn dialect_for(provider: &str) -> Result<Box<dyn Dialect>, PetrolError> {
+    match provider {
+        "postgresql" | "postgres" => Ok(Box::new(Postgres)),
+        "sqlite" => Ok(Box::new(Sqlite)),
+        "mysql" => Ok(Box::new(MySql)),
+        other => Err(PetrolError::Unsupported(format!(
+            "provider \"{}\", expected \"postgresql\", \"mysql\" or \"sqlite\"",
+            other
+        ))),
+    }
+}
+
+pub fn index_name(table: &SqlTable, columns: &[String]) -> String {
+    format!("{}_{}_idx", table.name, columns.join("_"))
+}
+
+pub fn foreign_key_name(table: &SqlTable, foreign_key: &SqlForeignKey) -> String {
+    format!("{}_{}_fkey", table.name, foreign_key.columns.join("_"))
+}
+
+/// Postgres' own name for an unnamed `UNIQUE` constraint.
+pub fn unique_name(table: &SqlTable, columns: &[String]) -> String {
+    format!("{}_{}_key", table.name, columns.join("_"))
+}
+
+/// Postgres' own name for a primary key constraint.
+pub fn primary_key_name(table: &SqlTable) -> String {
+    format!("{}_pkey", table.name)
+}
+
+/// Migration steps in standard SQL, which Postgres accepts as is. Dialects that spell a
+/// step differently override [`Dialect::step_sql`] and fall back to this for the rest.
+pub(crate) fn standard_step_sql<D: Dialect + ?Sized>(
+    dialect: &D,
+    step: &MigrationStep,
+) -> Result<Vec<String>, PetrolError> {
+    let alter = |table: &SqlTable, action: String| {
+        format!("ALTER TABLE {} {};\n", dialect.table_name(table), action)
+    };
+    Ok(match step {
+        MigrationStep::CreateTable(table) => {
+            let mut statements = vec![dialect.create_table_sql(table)?];
+            statements.extend(dialect.indexes_sql(table));
+            statements.extend(dialect.comments_sql(table));
+            if !table.updated_at.is_empty() {
+                statements.extend(dialect.updated_at_sql(std::slice::from_ref(table))?);
+            }
+            statements
+        }
+        MigrationStep::CreateForeignKeys(table) => dialect.foreign_keys_sql(table)?,
+        MigrationStep::DropTable(table) => {
+            vec![format!("DROP TABLE {};\n", dialect.table_name(table))]
+        }
+        MigrationStep::RenameTable { table, from } => vec![format!(
+            "ALTER TABLE {} RENAME TO {};\n",
+            dialect.qualified_name(table.schema.as_deref(), from),
+            dialect.quote_identifier(&table.name)
+        )],
+        MigrationStep::RenameColumn { table, from, to } => vec![alter(
+            table,
+            format!(
+                "RENAME COLUMN {} TO {}",
+                dialect.quote_identifier(from),
+                dialect.quote_identifier(to)
+            ),
+        )],
+        MigrationStep::AddColumn { table, column } => vec![alter(
+            table,
+            format!("ADD COLUMN {}", dialect.column_sql(column)?.trim_start()),
+        )],
+        MigrationStep::DropColumn { table, column } => vec![alter(
+            table,
+            format!("DROP COLUMN {}", dialect.quote_identifier(&column.name)),
+        )],
+        MigrationStep::AlterColumn { table, from, to } => {
+            vec![alter(table, alter_column_actions(dialect, from, to)?)]
+        }
+        MigrationStep::AddPrimaryKey(table) => vec![alter(
+            table,
+            format!(
+                "ADD PRIMARY KEY ({})",
+                dialect.quote_columns(&table.primary_key)
+            ),
+        )],
+        MigrationStep::DropPrimaryKey(table) => vec![alter(
+            table,
+            format!(
+                "DROP CONSTRAINT {}",
+                dialect.quote_identifier(&primary_key_name(table))
+            ),
+        )],
+        MigrationStep::AddUnique { table, columns } => vec![alter(
+            table,
+            format!(
+                "ADD CONSTRAINT {} UNIQUE ({})",
+                dialect.quote_identifier(&unique_name(table, columns)),
+                dialect.quote_columns(columns)
+            ),
+        )],
+        MigrationStep::DropUnique { table, columns } => vec![alter(
+            table,
+            format!(
+                "DROP CONSTRAINT {}",
+                dialect.quote_identifier(&unique_name(table, columns))
+            ),
+        )],
+        MigrationStep::AddIndex { table, columns } => vec![format!(
+            "CREATE INDEX {} ON {} ({});\n",
+            dialect.quote_identifier(&index_name(table, columns)),
+            dialect.table_name(table),
+            dialect.quote_columns(columns)
+        )],
+        MigrationStep::DropIndex { table, columns } => vec![format!(
+            "DROP INDEX {};\n",
+            dialect.qualified_name(table.schema.as_deref(), &index_name(table, columns))
+        )],
+        MigrationStep::AddCheck { table, check } => vec![alter(
+            table,
+            format!(
+                "ADD CONSTRAINT {} CHECK ({})",
+                dialect.quote_identifier(&check.name),
+                check.expr
+            ),
+        )],
+        MigrationStep::DropCheck { table, check } => vec![alter(
+            table,
+            format!("DROP CONSTRAINT {}", dialect.quote_identifier(&check.name)),
+        )],
+        MigrationStep::AddForeignKey { table, foreign_key } => vec![alter(
+            table,
+            format!("ADD {}", dialect.foreign_key_constraint(table, foreign_key)),
+        )],
+        MigrationStep::DropForeignKey { table, foreign_key } => vec![alter(
+            table,
+            format!(
+                "DROP CONSTRAINT {}",
+                dialect.quote_identifier(&foreign_key_name(table, foreign_key))
+            ),
+        )],
+        MigrationStep::SetTableComment(_) | MigrationStep::SetColumnComment { .. } => Vec::new(),
+        MigrationStep::AddUpdatedAt { table, column } => {
+            let mut table = table.clone();
+            table.updated_at = vec![column.clone()];
+            dialect.updated_at_sql(&[table])?
+        }
+        MigrationStep::DropUpdatedAt { table, column } => {
+            return Err(PetrolError::Unsupported(format!(
+                "cannot drop the @updatedAt trigger {} on {} for {}",
+                dialect.quote_identifier(&updated_at_trigger_name(column)),
+                dialect.table_name(table),
+                dialect.name()
+            )))
+        }
+    })
+}
+
+/// The `ALTER COLUMN` actions turning `from` into `to`. A changed type drops the old
+/// default first, since Postgres will not cast it along with the column.
+fn alter_column_actions<D: Dialect + ?Sized>(
+    dialect: &D,
+    from: &SqlColumn,
+    to: &SqlColumn,
+) -> Result<String, PetrolError> {
+    let column = dialect.quote_identifier(&to.name);
+    let retyped = from.sql_type != to.sql_type;
+    if retyped && from.sql_type.without_serial() == to.sql_type.without_serial() {
+        return Err(PetrolError::Unsupported(format!(
+            "changing autoincrement on column {} is not available for {}, add a new column instead",
+            to.name,
+            dialect.name()
+        )));
+    }
+
+    let mut actions = Vec::new();
+    if retyped && from.default.is_some() {
+        actions.push(format!("ALTER COLUMN {} DROP DEFAULT", column));
+    }
+    if retyped {
+        let sql_type = dialect.render_type(&to.sql_type.without_serial())?;
+        actions.push(format!(
+            "ALTER COLUMN {} TYPE {} USING {}::{}",
+            column, sql_type, column, sql_type
+        ));
+    }
+    if from.nullable != to.nullable {
+        let action = if to.nullable { "DROP" } else { "SET" };
+        actions.push(format!("ALTER COLUMN {} {} NOT NULL", column, action));
+    }
+    if retyped || from.default != to.default {
+        match &to.default {
+            Some(default) => actions.push(format!(
+                "ALTER COLUMN {} SET DEFAULT {}",
+                column,
+                dialect.render_default(to, default)?
+            )),
+            None if !retyped => actions.push(format!("ALTER COLUMN {} DROP DEFAULT", column)),
+            None => {}
+        }
+    }
+    Ok(actions.join(", "))
+}
+
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -0,0 +1,470 @@
+use super::{foreign_key_name, index_name, quote_literal, standard_step_sql, unique_name, Dialect};
+use crate::diff::MigrationStep;
+use crate::error::PetrolError;
+use crate::schema::{DefaultValue, FieldType, Schema};
+use crate::sql::{SqlColumn, SqlTable, SqlType};
+
+/// Longest string column that can still be indexed as utf8mb4 on older InnoDB row formats.
+const KEY_VARCHAR_LENGTH: u32 = 191;
+
+/// MySQL and MariaDB.
+pub struct MySql;
+
+impl Dialect for MySql {
+    fn name(&self) -> &'static str {
+        "mysql"
+    }
+
+    fn backslash_escapes(&self) -> bool {
+        true
+    }
+
+    fn check_schema(&self, schema: &Schema) -> Result<(), PetrolError> {
+        if !schema.datasource.schemas.is_empty() {
+            return Err(PetrolError::Unsupported(
+                "datasource schemas are not available for mysql".into(),
+            ));
+        }
+        if !schema.datasource.extensions.is_empty() {
+            return Err(PetrolError::Unsupported(
+                "datasource extensions are not available for mysql".into(),
+            ));
+        }
+        for model in &schema.models {
+            for field in &model.fields {
+                if let FieldType::Scalar(_, modifiers) = &field.r#type {
+                    if modifiers.list {
+                        return Err(PetrolError::Unsupported(format!(
+                            "scalar list {}.{} is not available for mysql",
+                            model.name, field.name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("`{}`", name.replace('`', "``"))
+    }
+
+    fn render_type(&self, sql_type: &SqlType) -> Result<String, PetrolError> {
+        Ok(match sql_type {
+            SqlType::SmallSerial | SqlType::SmallInt => "SMALLINT".into(),
+            SqlType::Serial | SqlType::Integer => "INT".into(),
+            SqlType::BigSerial | SqlType::BigInt => "BIGINT".into(),
+            SqlType::Real => "FLOAT".into(),
+            SqlType::Float => "DOUBLE".into(),
+            SqlType::Decimal(None) => "DECIMAL(65, 30)".into(),
+            SqlType::Decimal(Some((precision, scale))) => {
+                format!("DECIMAL({}, {})", precision, scale)
+            }
+            SqlType::Text | SqlType::Citext => "TEXT".into(),
+            SqlType::VarChar(len) => format!("VARCHAR({})", len.unwrap_or(KEY_VARCHAR_LENGTH)),
+            SqlType::Char(Some(len)) => format!("CHAR({})", len),
+            SqlType::Char(None) => "CHAR".into(),
+            SqlType::Boolean => "BOOLEAN".into(),
+            SqlType::Timestamp(precision) => format!(
+                "TIMESTAMP({})",
+                precision.unwrap_or(MySql::DEFAULT_DATETIME_PRECISION)
+            ),
+            SqlType::Timestamptz(precision) => format!(
+                "DATETIME({})",
+                precision.unwrap_or(MySql::DEFAULT_DATETIME_PRECISION)
+            ),
+            SqlType::Time(Some(precision)) => format!("TIME({})", precision),
+            SqlType::Time(None) => "TIME".into(),
+            SqlType::Date => "DATE".into(),
+            SqlType::Uuid => "CHAR(36)".into(),
+            SqlType::Json | SqlType::Jsonb => "JSON".into(),
+            SqlType::Bytes => "LONGBLOB".into(),
+            SqlType::Binary(Some(len)) => format!("BINARY({})", len),
+            SqlType::Binary(None) => "BINARY".into(),
+            SqlType::Array(_) => {
+                return Err(PetrolError::Unsupported(
+                    "array columns are not available for mysql".into(),
+                ))
+            }
+        })
+    }
+
+    /// `TEXT`, `BLOB` and `JSON` columns only take parenthesized expression defaults.
+    fn render_default(
+        &self,
+        column: &SqlColumn,
+        default: &DefaultValue,
+    ) -> Result<String, PetrolError> {
+        Ok(match default {
+            DefaultValue::Uuid => match column.sql_type {
+                SqlType::Binary(_) => "(UUID_TO_BIN(UUID()))".into(),
+                _ => "(UUID())".into(),
+            },
+            DefaultValue::Now => match column.sql_type {
+                SqlType::Date => "(CURRENT_DATE)".into(),
+                SqlType::Time(_) => "(CURRENT_TIME)".into(),
+                _ => current_timestamp(&column.sql_type),
+            },
+            DefaultValue::Boolean(value) => value.to_string(),
+            DefaultValue::Int(value) => value.to_string(),
+            DefaultValue::Float(value) => value.to_string(),
+            DefaultValue::String(value) => match column.sql_type {
+                SqlType::Text
+                | SqlType::Citext
+                | SqlType::Json
+                | SqlType::Jsonb
+                | SqlType::Bytes => format!("({})", string_literal(value)),
+                _ => string_literal(value),
+            },
+            DefaultValue::DbGenerated(expr) => format!("({})", expr),
+            other => {
+                return Err(PetrolError::Unsupported(format!(
+                    "@default({}) on column {} has no SQL form",
+                    other, column.name
+                )))
+            }
+        })
+    }
+
+    /// Lets tables reference tables that are created after them.
+    fn setup_sql(&self, _schema: &Schema) -> Result<Vec<String>, PetrolError> {
+        Ok(vec!["SET FOREIGN_KEY_CHECKS = 0;\n".into()])
+    }
+
+    fn finish_sql(&self) -> Vec<String> {
+        vec!["SET FOREIGN_KEY_CHECKS = 1;\n".into()]
+    }
+
+    /// MySQL has neither `ADD CONSTRAINT IF NOT EXISTS` nor `CREATE INDEX IF NOT EXISTS`,
+    /// so foreign keys, indexes and comments are all part of the `CREATE TABLE`.
+    fn create_table_sql(&self, table: &SqlTable) -> Result<String, PetrolError> {
+        let mut fragments = table
+            .columns
+            .iter()
+            .map(|column| self.column_fragment(table, column))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !table.primary_key.is_empty() {
+            fragments.push(format!(
+                "PRIMARY KEY ({})",
+                self.quote_columns(&table.primary_key)
+            ));
+        }
+        for unique in &table.uniques {
+            fragments.push(format!(
+                "CONSTRAINT {} UNIQUE ({})",
+                self.quote_identifier(&unique_name(table, unique)),
+                self.quote_columns(unique)
+            ));
+        }
+        for columns in &table.indexes {
+            fragments.push(format!(
+                "INDEX {} ({})",
+                self.quote_identifier(&index_name(table, columns)),
+                self.quote_columns(columns)
+            ));
+        }
+        for check in &table.checks {
+            fragments.push(format!(
+                "CONSTRAINT {} CHECK ({})",
+                self.quote_identifier(&check.name),
+                check.expr
+            ));
+        }
+        for foreign_key in &table.foreign_keys {
+            fragments.push(self.foreign_key_constraint(table, foreign_key));
+        }
+
+        let comment = table
+            .comment
+            .as_ref()
+            .map(|comment| format!(" COMMENT = {}", string_literal(comment)))
+            .unwrap_or_default();
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n{}\n){};\n",
+            self.table_name(table),
+            fragments.join(",\n"),
+            comment
+        ))
+    }
+
+    fn foreign_keys_sql(&self, _table: &SqlTable) -> Result<Vec<String>, PetrolError> {
+        Ok(Vec::new())
+    }
+
+    fn indexes_sql(&self, _table: &SqlTable) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// `@updatedAt` columns get `ON UPDATE CURRENT_TIMESTAMP` in create_table_sql.
+    fn updated_at_sql(&self, _tables: &[SqlTable]) -> Result<Vec<String>, PetrolError> {
+        Ok(Vec::new())
+    }
+
+    /// Columns change by restating them with `MODIFY COLUMN`, which also covers their
+    /// comment and `ON UPDATE`.
+    fn step_sql(&self, step: &MigrationStep) -> Result<Vec<String>, PetrolError> {
+        let alter = |table: &SqlTable, action: String| {
+            format!("ALTER TABLE {} {};\n", self.table_name(table), action)
+        };
+        Ok(match step {
+            MigrationStep::AddColumn { table, column } => vec![alter(
+                table,
+                format!(
+                    "ADD COLUMN {}",
+                    self.column_fragment(table, column)?.trim_start()
+                ),
+            )],
+            MigrationStep::AlterColumn {
+                table, to: column, ..
+            }
+            | MigrationStep::SetColumnComment { table, column } => {
+                vec![self.modify_column_sql(table, column)?]
+            }
+            MigrationStep::AddUpdatedAt { table, column }
+            | MigrationStep::DropUpdatedAt { table, column } => table
+                .columns
+                .iter()
+                .filter(|other| &other.name == column)
+                .map(|column| self.modify_column_sql(table, column))
+                .collect::<Result<_, _>>()?,
+            MigrationStep::AddPrimaryKey(table) => {
+                let mut statements = self.key_columns_sql(table, &table.primary_key)?;
+                statements.extend(standard_step_sql(self, step)?);
+                statements
+            }
+            MigrationStep::AddUnique { table, columns }
+            | MigrationStep::AddIndex { table, columns } => {
+                let mut statements = self.key_columns_sql(table, columns)?;
+                statements.extend(standard_step_sql(self, step)?);
+                statements
+            }
+            MigrationStep::AddForeignKey { table, foreign_key } => {
+                let mut statements = self.key_columns_sql(table, &foreign_key.columns)?;
+                statements.extend(standard_step_sql(self, step)?);
+                statements
+            }
+            MigrationStep::DropPrimaryKey(table) => {
+                vec![alter(table, "DROP PRIMARY KEY".to_string())]
+            }
+            MigrationStep::DropUnique { table, columns } => vec![alter(
+                table,
+                format!(
+                    "DROP INDEX {}",
+                    self.quote_identifier(&unique_name(table, columns))
+                ),
+            )],
+            MigrationStep::DropIndex { table, columns } => vec![alter(
+                table,
+                format!(
+                    "DROP INDEX {}",
+                    self.quote_identifier(&index_name(table, columns))
+                ),
+            )],
+            MigrationStep::DropForeignKey { table, foreign_key } => vec![alter(
+                table,
+                format!(
+                    "DROP FOREIGN KEY {}",
+                    self.quote_identifier(&foreign_key_name(table, foreign_key))
+                ),
+            )],
+            MigrationStep::SetTableComment(table) => vec![alter(
+                table,
+                format!(
+                    "COMMENT = {}",
+                    string_literal(table.comment.as_deref().unwrap_or_default())
+                ),
+            )],
+            _ => standard_step_sql(self, step)?,
+        })
+    }
+
+    fn lock_warning(&self, step: &MigrationStep, _safe: bool) -> Option<String> {
+        match step {
+            MigrationStep::AlterColumn { from, to, .. } if from.sql_type != to.sql_type => Some(
+                "changing the type copies the table, blocking writes to it meanwhile; add a \
+                 column of the new type, backfill it and switch over instead"
+                    .to_string(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl MySql {
+    /// Fractional second digits of `DateTime` columns without an explicit precision.
+    pub const DEFAULT_DATETIME_PRECISION: u32 = 3;
+
+    /// Like `column_sql`, but string columns that are part of a key become `VARCHAR(191)`
+    /// since MySQL cannot index `TEXT`.
+    fn column_fragment(&self, table: &SqlTable, column: &SqlColumn) -> Result<String, PetrolError> {
+        let keyed = table.primary_key.contains(&column.name)
+            || table
+                .uniques
+                .iter()
+                .flatten()
+                .any(|name| name == &column.name)
+            || table
+                .indexes
+                .iter()
+                .flatten()
+                .any(|name| name == &column.name)
+            || table
+                .foreign_keys
+                .iter()
+                .any(|foreign_key| foreign_key.columns.contains(&column.name));
+        let sql_type = match column.sql_type {
+            SqlType::Text | SqlType::Citext if keyed => {
+                format!("VARCHAR({})", KEY_VARCHAR_LENGTH)
+            }
+            ref other => self.render_type(other)?,
+        };
+
+        let mut fragment = format!("  {} {}", self.quote_identifier(&column.name), sql_type);
+        if let Some(generated) = &column.generated {
+            fragment.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", generated));
+        }
+        if !column.nullable {
+            fragment.push_str(" NOT NULL");
+        }
+        if matches!(
+            column.sql_type,
+            SqlType::SmallSerial | SqlType::Serial | SqlType::BigSerial
+        ) {
+            fragment.push_str(" AUTO_INCREMENT");
+        }
+        if let Some(default) = &column.default {
+            fragment.push_str(&format!(
+                " DEFAULT {}",
+                self.render_default(column, default)?
+            ));
+        }
+        if table.updated_at.contains(&column.name) {
+            fragment.push_str(&format!(
+                " ON UPDATE {}",
+                current_timestamp(&column.sql_type)
+            ));
+        }
+        if let Some(comment) = &column.comment {
+            fragment.push_str(&format!(" COMMENT {}", string_literal(comment)));
+        }
+        Ok(fragment)
+    }
+
+    fn modify_column_sql(
+        &self,
+        table: &SqlTable,
+        column: &SqlColumn,
+    ) -> Result<String, PetrolError> {
+        Ok(format!(
+            "ALTER TABLE {} MODIFY COLUMN {};\n",
+            self.table_name(table),
+            self.column_fragment(table, column)?.trim_start()
+        ))
+    }
+
+    /// Turns `TEXT` columns about to become part of a key into `VARCHAR(191)`.
+    fn key_columns_sql(
+        &self,
+        table: &SqlTable,
+        columns: &[String],
+    ) -> Result<Vec<String>, PetrolError> {
+        table
+            .columns
+            .iter()
+            .filter(|column| columns.contains(&column.name))
+            .filter(|column| matches!(column.sql_type, SqlType::Text | SqlType::Citext))
+            .map(|column| self.modify_column_sql(table, column))
+            .collect()
+    }
+}
+
+/// `CURRENT_TIMESTAMP` with the column's precision, which MySQL requires to match.
+fn current_timestamp(sql_type: &SqlType) -> String {
+    let precision = match sql_type {
+        SqlType::Timestamp(precision) | SqlType::Timestamptz(precision) => {
+            precision.unwrap_or(MySql::DEFAULT_DATETIME_PRECISION)
+        }
+        _ => MySql::DEFAULT_DATETIME_PRECISION,
+    };
+    format!("CURRENT_TIMESTAMP({})", precision)
+}
+
+/// Backslashes start escape sequences in MySQL strings unless `NO_BACKSLASH_ESCAPES` is set.
+fn string_literal(value: &str) -> String {
+    quote_literal(&value.replace('\\', "\\\\"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::SqlCheck;
+
+    fn column(name: &str, sql_type: SqlType) -> SqlColumn {
+        SqlColumn {
+            name: name.to_string(),
+            sql_type,
+            nullable: false,
+            default: None,
+            generated: None,
+            comment: None,
+        }
+    }
+
+    fn table(columns: Vec<SqlColumn>, primary_key: &[&str]) -> SqlTable {
+        SqlTable {
+            schema: None,
+            name: "Post".to_string(),
+            columns,
+            primary_key: primary_key
+                .iter()
+                .map(|column| column.to_string())
+                .collect(),
+            uniques: Vec::new(),
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+            checks: Vec::new(),
+            updated_at: Vec::new(),
+            comment: None,
+            renamed_from: None,
+            renamed_columns: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn serial_primary_key_uses_auto_increment() {
+        let mut post = table(
+            vec![
+                column("id", SqlType::Serial),
+                column("title", SqlType::Text),
+            ],
+            &["id"],
+        );
+        post.checks.push(SqlCheck {
+            name: "Post_title_check".to_string(),
+            expr: "char_length(title) > 0".to_string(),
+        });
+        assert_eq!(
+            MySql.create_table_sql(&post).unwrap(),
+            "CREATE TABLE IF NOT EXISTS `Post` (\n  `id` INT NOT NULL AUTO_INCREMENT,\n  \
+             `title` TEXT NOT NULL,\nPRIMARY KEY (`id`),\n\
+             CONSTRAINT `Post_title_check` CHECK (char_length(title) > 0)\n);\n"
+        );
+    }
+
+    #[test]
+    fn composite_primary_key_is_a_table_constraint() {
+        let tag = table(
+            vec![
+                column("postId", SqlType::Integer),
+                column("tag", SqlType::VarChar(Some(191))),
+            ],
+            &["postId", "tag"],
+        );
+        assert_eq!(
+            MySql.create_table_sql(&tag).unwrap(),
+            "CREATE TABLE IF NOT EXISTS `Post` (\n  `postId` INT NOT NULL,\n  \
+             `tag` VARCHAR(191) NOT NULL,\nPRIMARY KEY (`postId`, `tag`)\n);\n"
+        );
+    }
+}
@@ -0,0 +1,507 @@
+use super::{
+    foreign_key_name, index_name, primary_key_name, quote_literal, standard_step_sql, unique_name,
+    Dialect,
+};
+use crate::diff::MigrationStep;
+use crate::error::PetrolError;
+use crate::schema::{DefaultValue, Schema};
+use crate::sql::{
+    extension_sql_name, updated_at_trigger_name, SqlColumn, SqlTable, SqlType, UPDATED_AT_FUNCTION,
+};
+
+pub struct Postgres;
+
+/// The `pg_advisory_lock` key held while a schema is applied, `petrol` in ASCII.
+const MIGRATION_LOCK_ID: i64 = 0x7065_7472_6f6c;
+
+impl Dialect for Postgres {
+    fn name(&self) -> &'static str {
+        "postgresql"
+    }
+
+    fn render_type(&self, sql_type: &SqlType) -> Result<String, PetrolError> {
+        Ok(match sql_type {
+            SqlType::SmallSerial => "SMALLSERIAL".into(),
+            SqlType::Serial => "SERIAL".into(),
+            SqlType::BigSerial => "BIGSERIAL".into(),
+            SqlType::SmallInt => "SMALLINT".into(),
+            SqlType::Integer => "INTEGER".into(),
+            SqlType::BigInt => "BIGINT".into(),
+            SqlType::Real => "REAL".into(),
+            SqlType::Float => "DOUBLE PRECISION".into(),
+            SqlType::Decimal(None) => "DECIMAL".into(),
+            SqlType::Decimal(Some((precision, scale))) => {
+                format!("DECIMAL({}, {})", precision, scale)
+            }
+            SqlType::Text => "TEXT".into(),
+            SqlType::VarChar(len) => with_length("VARCHAR", *len),
+            SqlType::Char(len) => with_length("CHAR", *len),
+            SqlType::Citext => "CITEXT".into(),
+            SqlType::Boolean => "BOOLEAN".into(),
+            SqlType::Timestamp(precision) => with_length("TIMESTAMP", *precision),
+            SqlType::Timestamptz(precision) => with_length("TIMESTAMPTZ", *precision),
+            SqlType::Time(precision) => with_length("TIME", *precision),
+            SqlType::Date => "DATE".into(),
+            SqlType::Uuid => "UUID".into(),
+            SqlType::Json => "JSON".into(),
+            SqlType::Jsonb => "JSONB".into(),
+            SqlType::Bytes => "BYTEA".into(),
+            SqlType::Binary(_) => {
+                return Err(PetrolError::Unsupported(
+                    "@db.Binary is not available for postgresql, use @db.ByteA".into(),
+                ))
+            }
+            SqlType::Array(element) => format!("{}[]", self.render_type(element)?),
+        })
+    }
+
+    fn render_default(
+        &self,
+        column: &SqlColumn,
+        default: &DefaultValue,
+    ) -> Result<String, PetrolError> {
+        Ok(match default {
+            DefaultValue::Uuid => match column.sql_type {
+                SqlType::Uuid => "gen_random_uuid()".into(),
+                _ => "gen_random_uuid()::text".into(),
+            },
+            DefaultValue::Now => "now()".into(),
+            DefaultValue::Boolean(value) => value.to_string(),
+            DefaultValue::Int(value) => value.to_string(),
+            DefaultValue::Float(value) => value.to_string(),
+            DefaultValue::String(value) => quote_literal(value),
+            DefaultValue::DbGenerated(expr) => expr.clone(),
+            other => {
+                return Err(PetrolError::Unsupported(format!(
+                    "@default({}) on column {} has no SQL form",
+                    other, column.name
+                )))
+            }
+        })
+    }
+
+    fn setup_sql(&self, schema: &Schema) -> Result<Vec<String>, PetrolError> {
+        let namespaces = schema.datasource.schemas.iter().map(|name| {
+            format!(
+                "CREATE SCHEMA IF NOT EXISTS {};\n",
+                self.quote_identifier(name)
+            )
+        });
+        let extensions = schema.datasource.extensions.iter().map(|name| {
+            format!(
+                "CREATE EXTENSION IF NOT EXISTS {};\n",
+                self.quote_identifier(extension_sql_name(name))
+            )
+        });
+        Ok(namespaces.chain(extensions).collect())
+    }
+
+    /// `ADD CONSTRAINT` has no `IF NOT EXISTS`, so constraints that already exist are
+    /// skipped by catching the error instead.
+    fn foreign_keys_sql(&self, table: &SqlTable) -> Result<Vec<String>, PetrolError> {
+        Ok(table
+            .foreign_keys
+            .iter()
+            .map(|foreign_key| {
+                format!(
+                    "DO $$ BEGIN\n  ALTER TABLE {} ADD {};\nEXCEPTION WHEN duplicate_object THEN NULL;\nEND $$;\n",
+                    self.table_name(table),
+                    self.foreign_key_constraint(table, foreign_key)
+                )
+            })
+            .collect())
+    }
+
+    fn comments_sql(&self, table: &SqlTable) -> Vec<String> {
+        let mut statements = Vec::new();
+        if let Some(comment) = &table.comment {
+            statements.push(format!(
+                "COMMENT ON TABLE {} IS {};\n",
+                self.table_name(table),
+                quote_literal(comment)
+            ));
+        }
+        for column in &table.columns {
+            if let Some(comment) = &column.comment {
+                statements.push(format!(
+                    "COMMENT ON COLUMN {}.{} IS {};\n",
+                    self.table_name(table),
+                    self.quote_identifier(&column.name),
+                    quote_literal(comment)
+                ));
+            }
+        }
+        statements
+    }
+
+    fn updated_at_sql(&self, tables: &[SqlTable]) -> Result<Vec<String>, PetrolError> {
+        let mut statements = vec![format!(
+            r#"CREATE OR REPLACE FUNCTION "{}"() RETURNS TRIGGER AS $$
+BEGIN
+  NEW := jsonb_populate_record(NEW, jsonb_build_object(TG_ARGV[0], now()));
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+"#,
+            UPDATED_AT_FUNCTION
+        )];
+        for table in tables {
+            for column in &table.updated_at {
+                let trigger = self.quote_identifier(&updated_at_trigger_name(column));
+                statements.push(format!(
+                    "DROP TRIGGER IF EXISTS {} ON {};\n",
+                    trigger,
+                    self.table_name(table)
+                ));
+                statements.push(format!(
+                    "CREATE TRIGGER {} BEFORE UPDATE ON {} FOR EACH ROW EXECUTE FUNCTION \"{}\"({});\n",
+                    trigger,
+                    self.table_name(table),
+                    UPDATED_AT_FUNCTION,
+                    quote_literal(column)
+                ));
+            }
+        }
+        Ok(statements)
+    }
+
+    fn step_sql(&self, step: &MigrationStep) -> Result<Vec<String>, PetrolError> {
+        Ok(match step {
+            MigrationStep::AddColumn { table, column } => {
+                let mut statements = standard_step_sql(self, step)?;
+                if column.comment.is_some() {
+                    statements.push(self.column_comment_sql(table, column));
+                }
+                statements
+            }
+            MigrationStep::SetTableComment(table) => vec![format!(
+                "COMMENT ON TABLE {} IS {};\n",
+                self.table_name(table),
+                comment_literal(&table.comment)
+            )],
+            MigrationStep::SetColumnComment { table, column } => {
+                vec![self.column_comment_sql(table, column)]
+            }
+            MigrationStep::DropUpdatedAt { table, column } => vec![format!(
+                "DROP TRIGGER IF EXISTS {} ON {};\n",
+                self.quote_identifier(&updated_at_trigger_name(column)),
+                self.table_name(table)
+            )],
+            _ => standard_step_sql(self, step)?,
+        })
+    }
+
+    /// Indexes are built `CONCURRENTLY`, and constraints are added `NOT VALID` and
+    /// validated after, which only blocks writes for as long as adding them takes.
+    fn safe_step_sql(&self, step: &MigrationStep) -> Result<Vec<String>, PetrolError> {
+        let alter = |table: &SqlTable, action: String| {
+            format!("ALTER TABLE {} {};\n", self.table_name(table), action)
+        };
+        let validate = |table: &SqlTable, name: &str| {
+            alter(
+                table,
+                format!("VALIDATE CONSTRAINT {}", self.quote_identifier(name)),
+            )
+        };
+        let unique_index = |table: &SqlTable, name: &str, columns: &[String], kind: &str| {
+            let name = self.quote_identifier(name);
+            vec![
+                format!(
+                    "CREATE UNIQUE INDEX CONCURRENTLY {} ON {} ({});\n",
+                    name,
+                    self.table_name(table),
+                    self.quote_columns(columns)
+                ),
+                alter(
+                    table,
+                    format!("ADD CONSTRAINT {} {} USING INDEX {}", name, kind, name),
+                ),
+            ]
+        };
+        Ok(match step {
+            MigrationStep::AddIndex { table, columns } => vec![format!(
+                "CREATE INDEX CONCURRENTLY {} ON {} ({});\n",
+                self.quote_identifier(&index_name(table, columns)),
+                self.table_name(table),
+                self.quote_columns(columns)
+            )],
+            MigrationStep::AddUnique { table, columns } => {
+                unique_index(table, &unique_name(table, columns), columns, "UNIQUE")
+            }
+            MigrationStep::AddPrimaryKey(table) => unique_index(
+                table,
+                &primary_key_name(table),
+                &table.primary_key,
+                "PRIMARY KEY",
+            ),
+            MigrationStep::AddForeignKey { table, foreign_key } => vec![
+                alter(
+                    table,
+                    format!(
+                        "ADD {} NOT VALID",
+                        self.foreign_key_constraint(table, foreign_key)
+                    ),
+                ),
+                validate(table, &foreign_key_name(table, foreign_key)),
+            ],
+            MigrationStep::AddCheck { table, check } => vec![
+                alter(
+                    table,
+                    format!(
+                        "ADD CONSTRAINT {} CHECK ({}) NOT VALID",
+                        self.quote_identifier(&check.name),
+                        check.expr
+                    ),
+                ),
+                validate(table, &check.name),
+            ],
+            // SET NOT NULL skips its scan when a validated check already proves it.
+            MigrationStep::AlterColumn { table, from, to } if from.nullable && !to.nullable => {
+                let name = format!("{}_{}_not_null", table.name, to.name);
+                let mut statements = vec![
+                    alter(
+                        table,
+                        format!(
+                            "ADD CONSTRAINT {} CHECK ({} IS NOT NULL) NOT VALID",
+                            self.quote_identifier(&name),
+                            self.quote_identifier(&to.name)
+                        ),
+                    ),
+                    validate(table, &name),
+                ];
+                statements.extend(self.step_sql(step)?);
+                statements.push(alter(
+                    table,
+                    format!("DROP CONSTRAINT {}", self.quote_identifier(&name)),
+                ));
+                statements
+            }
+            _ => self.step_sql(step)?,
+        })
+    }
+
+    fn lock_warning(&self, step: &MigrationStep, safe: bool) -> Option<String> {
+        Some(
+            match step {
+                MigrationStep::AddColumn { column, .. } if volatile_default(column) => {
+                    "the default is computed for every row, rewriting the table under an exclusive \
+                 lock; add the column without it, backfill in batches, then set the default"
+                }
+                MigrationStep::AlterColumn { from, to, .. }
+                    if from.sql_type != to.sql_type
+                        && !widens_in_place(&from.sql_type, &to.sql_type) =>
+                {
+                    "changing the type rewrites the table under an exclusive lock; add a column \
+                 of the new type, backfill it and switch over instead"
+                }
+                _ if safe => return None,
+                MigrationStep::AlterColumn { from, to, .. } if from.nullable && !to.nullable => {
+                    "SET NOT NULL scans the table under an exclusive lock; validate a NOT VALID \
+                 CHECK (... IS NOT NULL) constraint first"
+                }
+                MigrationStep::AddIndex { .. } => {
+                    "CREATE INDEX blocks writes to the table while it builds; use CREATE INDEX \
+                 CONCURRENTLY"
+                }
+                MigrationStep::AddUnique { .. } | MigrationStep::AddPrimaryKey(_) => {
+                    "building the index blocks the table; build a unique index CONCURRENTLY and \
+                 attach it with ADD CONSTRAINT ... USING INDEX"
+                }
+                MigrationStep::AddForeignKey { .. } => {
+                    "checking the existing rows blocks writes to both tables; add the constraint \
+                 NOT VALID, then VALIDATE CONSTRAINT"
+                }
+                MigrationStep::AddCheck { .. } => {
+                    "checking the existing rows blocks writes to the table; add the constraint \
+                 NOT VALID, then VALIDATE CONSTRAINT"
+                }
+                _ => return None,
+            }
+            .to_string(),
+        )
+    }
+
+    fn transactional_ddl(&self) -> bool {
+        true
+    }
+
+    /// `VALIDATE CONSTRAINT` only avoids the lock `NOT VALID` took once that has committed.
+    fn needs_autocommit(&self, statement: &str) -> bool {
+        let statement = statement.trim_start().to_uppercase();
+        let words: Vec<&str> = statement.split_whitespace().collect();
+        words.contains(&"CONCURRENTLY")
+            || words
+                .windows(2)
+                .any(|pair| pair == ["VALIDATE", "CONSTRAINT"])
+            || ["VACUUM", "CREATE DATABASE", "DROP DATABASE", "ALTER SYSTEM"]
+                .iter()
+                .any(|prefix| statement.starts_with(prefix))
+    }
+
+    fn migration_lock_sql(&self) -> Option<(String, String)> {
+        Some((
+            format!("SELECT pg_advisory_lock({});\n", MIGRATION_LOCK_ID),
+            format!("SELECT pg_advisory_unlock({});\n", MIGRATION_LOCK_ID),
+        ))
+    }
+}
+
+impl Postgres {
+    fn column_comment_sql(&self, table: &SqlTable, column: &SqlColumn) -> String {
+        format!(
+            "COMMENT ON COLUMN {}.{} IS {};\n",
+            self.table_name(table),
+            self.quote_identifier(&column.name),
+            comment_literal(&column.comment)
+        )
+    }
+}
+
+/// Defaults Postgres evaluates once per row, so adding a column with one fills it in by
+/// rewriting the table. `now()` is evaluated once per statement; a `dbgenerated`
+/// expression may call anything, so it counts as volatile.
+fn volatile_default(column: &SqlColumn) -> bool {
+    column.sql_type.without_serial() != column.sql_type
+        || matches!(
+            column.default,
+            Some(DefaultValue::Uuid | DefaultValue::DbGenerated(_))
+        )
+}
+
+/// Type changes Postgres makes without touching the rows.
+fn widens_in_place(from: &SqlType, to: &SqlType) -> bool {
+    match (from, to) {
+        (SqlType::VarChar(_), SqlType::Text) | (SqlType::VarChar(_), SqlType::VarChar(None)) => {
+            true
+        }
+        (SqlType::VarChar(Some(from)), SqlType::VarChar(Some(to))) => to >= from,
+        _ => false,
+    }
+}
+
+fn comment_literal(comment: &Option<String>) -> String {
+    comment
+        .as_deref()
+        .map_or_else(|| "NULL".to_string(), quote_literal)
+}
+
+fn with_length(name: &str, length: Option<u32>) -> String {
+    match length {
+        Some(length) => format!("{}({})", name, length),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{SqlCheck, SqlForeignKey};
+
+    fn column(name: &str, nullable: bool, default: Option<DefaultValue>) -> SqlColumn {
+        SqlColumn {
+            name: name.to_string(),
+            sql_type: SqlType::Text,
+            nullable,
+            default,
+            generated: None,
+            comment: None,
+        }
+    }
+
+    fn table() -> SqlTable {
+        SqlTable {
+            schema: None,
+            name: "Post".to_string(),
+            columns: vec![column("id", false, None), column("title", true, None)],
+            primary_key: vec!["id".to_string()],
+            uniques: Vec::new(),
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+            checks: Vec::new(),
+            updated_at: Vec::new(),
+            comment: None,
+            renamed_from: None,
+            renamed_columns: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn safe_steps_build_concurrently_and_validate_after() {
+        let index = MigrationStep::AddIndex {
+            table: table(),
+            columns: vec!["title".to_string()],
+        };
+        assert_eq!(
+            Postgres.safe_step_sql(&index).unwrap(),
+            vec!["CREATE INDEX CONCURRENTLY \"Post_title_idx\" ON \"Post\" (\"title\");\n"]
+        );
+
+        let check = MigrationStep::AddCheck {
+            table: table(),
+            check: SqlCheck {
+                name: "Post_title_check".to_string(),
+                expr: "length(title) > 0".to_string(),
+            },
+        };
+        assert_eq!(
+            Postgres.safe_step_sql(&check).unwrap(),
+            vec![
+                "ALTER TABLE \"Post\" ADD CONSTRAINT \"Post_title_check\" \
+                 CHECK (length(title) > 0) NOT VALID;\n",
+                "ALTER TABLE \"Post\" VALIDATE CONSTRAINT \"Post_title_check\";\n",
+            ]
+        );
+
+        let foreign_key = MigrationStep::AddForeignKey {
+            table: table(),
+            foreign_key: SqlForeignKey {
+                columns: vec!["title".to_string()],
+                references_schema: None,
+                references_table: "Title".to_string(),
+                references_columns: vec!["id".to_string()],
+                on_delete: "CASCADE",
+            },
+        };
+        let statements = Postgres.safe_step_sql(&foreign_key).unwrap();
+        assert!(statements[0].ends_with(" NOT VALID;\n"));
+        assert!(statements[1].contains("VALIDATE CONSTRAINT"));
+    }
+
+    #[test]
+    fn warns_about_locks_the_safe_steps_avoid() {
+        let index = MigrationStep::AddIndex {
+            table: table(),
+            columns: vec!["title".to_string()],
+        };
+        assert!(Postgres.lock_warning(&index, false).is_some());
+        assert!(Postgres.lock_warning(&index, true).is_none());
+
+        let not_null = MigrationStep::AlterColumn {
+            table: table(),
+            from: column("title", true, None),
+            to: column("title", false, None),
+        };
+        assert!(Postgres.lock_warning(&not_null, false).is_some());
+        assert!(Postgres.lock_warning(&not_null, true).is_none());
+    }
+
+    #[test]
+    fn warns_about_volatile_defaults_even_when_safe() {
+        let add = |default| MigrationStep::AddColumn {
+            table: table(),
+            column: column("slug", false, Some(default)),
+        };
+        assert!(Postgres
+            .lock_warning(
+                &add(DefaultValue::DbGenerated("md5(now()::text)".into())),
+                true
+            )
+            .is_some());
+        assert!(Postgres
+            .lock_warning(&add(DefaultValue::Uuid), true)
+            .is_some());
+        assert!(Postgres
+            .lock_warning(&add(DefaultValue::Now), false)
+            .is_none());
+    }
+}
@@ -0,0 +1,316 @@
+use super::{quote_literal, standard_step_sql, Dialect};
+use crate::diff::MigrationStep;
+use crate::error::PetrolError;
+use crate::schema::{DefaultValue, FieldType, ScalarType, Schema};
+use crate::sql::{updated_at_trigger_name, SqlColumn, SqlTable, SqlType};
+
+pub struct Sqlite;
+
+impl Dialect for Sqlite {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn check_schema(&self, schema: &Schema) -> Result<(), PetrolError> {
+        if !schema.datasource.schemas.is_empty() {
+            return Err(PetrolError::Unsupported(
+                "datasource schemas are not available for sqlite".into(),
+            ));
+        }
+        if !schema.datasource.extensions.is_empty() {
+            return Err(PetrolError::Unsupported(
+                "datasource extensions are not available for sqlite".into(),
+            ));
+        }
+        for model in &schema.models {
+            for field in &model.fields {
+                let FieldType::Scalar(scalar, modifiers) = &field.r#type else {
+                    continue;
+                };
+                if modifiers.list {
+                    return Err(PetrolError::Unsupported(format!(
+                        "scalar list {}.{} is not available for sqlite",
+                        model.name, field.name
+                    )));
+                }
+                if *scalar == ScalarType::Decimal {
+                    return Err(PetrolError::Unsupported(format!(
+                        "Decimal field {}.{} is not available for sqlite, use Float or String",
+                        model.name, field.name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn render_type(&self, sql_type: &SqlType) -> Result<String, PetrolError> {
+        Ok(match sql_type {
+            // Serial columns become `INTEGER PRIMARY KEY AUTOINCREMENT` in create_table_sql.
+            SqlType::SmallSerial | SqlType::Serial | SqlType::BigSerial => "INTEGER".into(),
+            SqlType::SmallInt => "SMALLINT".into(),
+            SqlType::Integer => "INTEGER".into(),
+            SqlType::BigInt => "BIGINT".into(),
+            SqlType::Real => "REAL".into(),
+            SqlType::Float => "DOUBLE".into(),
+            SqlType::Decimal(_) => "NUMERIC".into(),
+            SqlType::Text => "TEXT".into(),
+            SqlType::VarChar(Some(len)) => format!("VARCHAR({})", len),
+            SqlType::Char(Some(len)) => format!("CHAR({})", len),
+            SqlType::VarChar(None) | SqlType::Char(None) => "TEXT".into(),
+            SqlType::Citext => "TEXT COLLATE NOCASE".into(),
+            SqlType::Boolean => "BOOLEAN".into(),
+            SqlType::Timestamp(_) => "TIMESTAMP".into(),
+            SqlType::Timestamptz(_) => "DATETIME".into(),
+            SqlType::Time(_) => "TIME".into(),
+            SqlType::Date => "DATE".into(),
+            SqlType::Uuid | SqlType::Json | SqlType::Jsonb => "TEXT".into(),
+            SqlType::Bytes | SqlType::Binary(_) => "BLOB".into(),
+            SqlType::Array(_) => {
+                return Err(PetrolError::Unsupported(
+                    "array columns are not available for sqlite".into(),
+                ))
+            }
+        })
+    }
+
+    /// Anything but a literal has to be parenthesized in a SQLite `DEFAULT`.
+    fn render_default(
+        &self,
+        column: &SqlColumn,
+        default: &DefaultValue,
+    ) -> Result<String, PetrolError> {
+        Ok(match default {
+            DefaultValue::Uuid => Sqlite::RANDOM_UUID.into(),
+            DefaultValue::Now => "CURRENT_TIMESTAMP".into(),
+            DefaultValue::Boolean(value) => value.to_string(),
+            DefaultValue::Int(value) => value.to_string(),
+            DefaultValue::Float(value) => value.to_string(),
+            DefaultValue::String(value) => quote_literal(value),
+            DefaultValue::DbGenerated(expr) => format!("({})", expr),
+            other => {
+                return Err(PetrolError::Unsupported(format!(
+                    "@default({}) on column {} has no SQL form",
+                    other, column.name
+                )))
+            }
+        })
+    }
+
+    /// SQLite cannot add constraints to an existing table, so foreign keys are declared
+    /// inline. It only resolves the referenced table when rows are written, so tables can
+    /// still be created in any order.
+    fn create_table_sql(&self, table: &SqlTable) -> Result<String, PetrolError> {
+        let serial = table.columns.iter().find(|column| {
+            matches!(
+                column.sql_type,
+                SqlType::SmallSerial | SqlType::Serial | SqlType::BigSerial
+            )
+        });
+        if let Some(serial) = serial {
+            if table.primary_key != [serial.name.clone()] {
+                return Err(PetrolError::Unsupported(format!(
+                    "autoincrement on {}.{} needs it to be the only @id column on sqlite",
+                    table.name, serial.name
+                )));
+            }
+        }
+
+        let mut fragments = Vec::new();
+        for column in &table.columns {
+            let mut fragment = self.column_sql(column)?;
+            if serial.is_some_and(|serial| serial.name == column.name) {
+                fragment.push_str(" PRIMARY KEY AUTOINCREMENT");
+            }
+            fragments.push(fragment);
+        }
+
+        if serial.is_none() && !table.primary_key.is_empty() {
+            fragments.push(format!(
+                "PRIMARY KEY ({})",
+                self.quote_columns(&table.primary_key)
+            ));
+        }
+        for unique in &table.uniques {
+            fragments.push(format!("UNIQUE ({})", self.quote_columns(unique)));
+        }
+        for check in &table.checks {
+            fragments.push(format!(
+                "CONSTRAINT {} CHECK ({})",
+                self.quote_identifier(&check.name),
+                check.expr
+            ));
+        }
+        for foreign_key in &table.foreign_keys {
+            fragments.push(self.foreign_key_constraint(table, foreign_key));
+        }
+
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n{}\n);\n",
+            self.table_name(table),
+            fragments.join(",\n")
+        ))
+    }
+
+    fn foreign_keys_sql(&self, _table: &SqlTable) -> Result<Vec<String>, PetrolError> {
+        Ok(Vec::new())
+    }
+
+    /// Trigger names are global in SQLite, so they carry the table name. The trigger only
+    /// fires when an update left the column untouched.
+    fn updated_at_sql(&self, tables: &[SqlTable]) -> Result<Vec<String>, PetrolError> {
+        let mut statements = Vec::new();
+        for table in tables {
+            for column in &table.updated_at {
+                let trigger = Sqlite::updated_at_trigger_name(&table.name, column);
+                let column = self.quote_identifier(column);
+                statements.push(format!(
+                    "CREATE TRIGGER IF NOT EXISTS {} AFTER UPDATE ON {} FOR EACH ROW WHEN NEW.{} IS OLD.{}\nBEGIN\n  UPDATE {} SET {} = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;\nEND;\n",
+                    self.quote_identifier(&trigger),
+                    self.table_name(table),
+                    column,
+                    column,
+                    self.table_name(table),
+                    column
+                ));
+            }
+        }
+        Ok(statements)
+    }
+
+    /// `ALTER TABLE` can add, drop and rename columns but change nothing else.
+    fn step_sql(&self, step: &MigrationStep) -> Result<Vec<String>, PetrolError> {
+        let change = match step {
+            MigrationStep::AlterColumn { table, to, .. } => {
+                Some((table, format!("column {}", to.name)))
+            }
+            MigrationStep::AddPrimaryKey(table) | MigrationStep::DropPrimaryKey(table) => {
+                Some((table, "the primary key".to_string()))
+            }
+            MigrationStep::AddUnique { table, columns }
+            | MigrationStep::DropUnique { table, columns } => {
+                Some((table, format!("unique ({})", columns.join(", "))))
+            }
+            MigrationStep::AddCheck { table, check }
+            | MigrationStep::DropCheck { table, check } => {
+                Some((table, format!("check {}", check.name)))
+            }
+            MigrationStep::AddForeignKey { table, foreign_key }
+            | MigrationStep::DropForeignKey { table, foreign_key } => Some((
+                table,
+                format!("foreign key ({})", foreign_key.columns.join(", ")),
+            )),
+            _ => None,
+        };
+        if let Some((table, change)) = change {
+            return Err(PetrolError::Unsupported(format!(
+                "changing {} of table {} is not available for sqlite, recreate the table",
+                change, table.name
+            )));
+        }
+
+        match step {
+            MigrationStep::DropUpdatedAt { table, column } => Ok(vec![format!(
+                "DROP TRIGGER IF EXISTS {};\n",
+                self.quote_identifier(&Sqlite::updated_at_trigger_name(&table.name, column))
+            )]),
+            _ => standard_step_sql(self, step),
+        }
+    }
+
+    fn transactional_ddl(&self) -> bool {
+        true
+    }
+
+    fn needs_autocommit(&self, statement: &str) -> bool {
+        statement.trim_start().to_uppercase().starts_with("VACUUM")
+    }
+}
+
+impl Sqlite {
+    /// A random version 4 UUID in its hyphenated text form; SQLite has no UUID function.
+    pub const RANDOM_UUID: &'static str = "(lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' || substr(lower(hex(randomblob(2))), 2) || '-' || substr('89ab', 1 + (abs(random()) % 4), 1) || substr(lower(hex(randomblob(2))), 2) || '-' || lower(hex(randomblob(6))))";
+
+    pub fn updated_at_trigger_name(table: &str, column: &str) -> String {
+        format!("{}_{}", table, updated_at_trigger_name(column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::SqlCheck;
+
+    fn column(name: &str, sql_type: SqlType) -> SqlColumn {
+        SqlColumn {
+            name: name.to_string(),
+            sql_type,
+            nullable: false,
+            default: None,
+            generated: None,
+            comment: None,
+        }
+    }
+
+    fn table(columns: Vec<SqlColumn>, primary_key: &[&str]) -> SqlTable {
+        SqlTable {
+            schema: None,
+            name: "Post".to_string(),
+            columns,
+            primary_key: primary_key
+                .iter()
+                .map(|column| column.to_string())
+                .collect(),
+            uniques: Vec::new(),
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+            checks: Vec::new(),
+            updated_at: Vec::new(),
+            comment: None,
+            renamed_from: None,
+            renamed_columns: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn serial_primary_key_autoincrements_inline() {
+        let mut post = table(
+            vec![
+                column("id", SqlType::Serial),
+                column("title", SqlType::Text),
+            ],
+            &["id"],
+        );
+        post.checks.push(SqlCheck {
+            name: "Post_title_check".to_string(),
+            expr: "length(title) > 0".to_string(),
+        });
+        assert_eq!(
+            Sqlite.create_table_sql(&post).unwrap(),
+            "CREATE TABLE IF NOT EXISTS \"Post\" (\n  \"id\" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,\n  \
+             \"title\" TEXT NOT NULL,\nCONSTRAINT \"Post_title_check\" CHECK (length(title) > 0)\n);\n"
+        );
+    }
+
+    #[test]
+    fn composite_primary_key_is_a_table_constraint() {
+        let tag = table(
+            vec![
+                column("postId", SqlType::Integer),
+                column("tag", SqlType::Text),
+            ],
+            &["postId", "tag"],
+        );
+        assert_eq!(
+            Sqlite.create_table_sql(&tag).unwrap(),
+            "CREATE TABLE IF NOT EXISTS \"Post\" (\n  \"postId\" INTEGER NOT NULL,\n  \
+             \"tag\" TEXT NOT NULL,\nPRIMARY KEY (\"postId\", \"tag\")\n);\n"
+        );
+
+        let serial = table(
+            vec![column("id", SqlType::Serial), column("tag", SqlType::Text)],
+            &["id", "tag"],
+        );
+        assert!(Sqlite.create_table_sql(&serial).is_err());
+    }
+}
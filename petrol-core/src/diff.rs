@@ -0,0 +1,769 @@
+use std::fmt::{self, Display};
+
+use crate::dialect::{dialect_for, Dialect};
+use crate::error::PetrolError;
+use crate::expression::same_expression;
+use crate::schema::{DefaultValue, Schema};
+use crate::sql::{schema_to_tables, SqlCheck, SqlColumn, SqlForeignKey, SqlTable, SqlType};
+
+/// One change in a migration plan. Every step but [`MigrationStep::DropTable`] carries its
+/// table as the target schema defines it.
+#[derive(Debug, Clone)]
+pub enum MigrationStep {
+    CreateTable(SqlTable),
+    /// The foreign keys of a table created earlier in the plan, once every new table exists.
+    CreateForeignKeys(SqlTable),
+    DropTable(SqlTable),
+    RenameTable {
+        table: SqlTable,
+        from: String,
+    },
+    RenameColumn {
+        table: SqlTable,
+        from: String,
+        to: String,
+    },
+    AddColumn {
+        table: SqlTable,
+        column: SqlColumn,
+    },
+    DropColumn {
+        table: SqlTable,
+        column: SqlColumn,
+    },
+    AlterColumn {
+        table: SqlTable,
+        from: SqlColumn,
+        to: SqlColumn,
+    },
+    AddPrimaryKey(SqlTable),
+    DropPrimaryKey(SqlTable),
+    AddUnique {
+        table: SqlTable,
+        columns: Vec<String>,
+    },
+    DropUnique {
+        table: SqlTable,
+        columns: Vec<String>,
+    },
+    AddIndex {
+        table: SqlTable,
+        columns: Vec<String>,
+    },
+    DropIndex {
+        table: SqlTable,
+        columns: Vec<String>,
+    },
+    AddCheck {
+        table: SqlTable,
+        check: SqlCheck,
+    },
+    DropCheck {
+        table: SqlTable,
+        check: SqlCheck,
+    },
+    AddForeignKey {
+        table: SqlTable,
+        foreign_key: SqlForeignKey,
+    },
+    DropForeignKey {
+        table: SqlTable,
+        foreign_key: SqlForeignKey,
+    },
+    SetTableComment(SqlTable),
+    SetColumnComment {
+        table: SqlTable,
+        column: SqlColumn,
+    },
+    AddUpdatedAt {
+        table: SqlTable,
+        column: String,
+    },
+    DropUpdatedAt {
+        table: SqlTable,
+        column: String,
+    },
+}
+
+impl MigrationStep {
+    /// Whether the step destroys data that undoing it cannot bring back.
+    pub fn loses_data(&self) -> bool {
+        match self {
+            MigrationStep::DropTable(_) | MigrationStep::DropColumn { .. } => true,
+            MigrationStep::AlterColumn { from, to, .. } => from.sql_type != to.sql_type,
+            _ => false,
+        }
+    }
+}
+
+impl Display for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationStep::CreateTable(table) => write!(f, "added table {}", name(table)),
+            MigrationStep::CreateForeignKeys(table) => {
+                write!(f, "added the foreign keys of table {}", name(table))
+            }
+            MigrationStep::DropTable(table) => write!(f, "removed table {}", name(table)),
+            MigrationStep::RenameTable { table, from } => {
+                write!(f, "renamed table {} to {}", from, name(table))
+            }
+            MigrationStep::RenameColumn { table, from, to } => {
+                write!(f, "renamed column {}.{} to {}", name(table), from, to)
+            }
+            MigrationStep::AddColumn { table, column } => {
+                write!(f, "added column {}.{}", name(table), column.name)
+            }
+            MigrationStep::DropColumn { table, column } => {
+                write!(f, "removed column {}.{}", name(table), column.name)
+            }
+            MigrationStep::AlterColumn { table, from, to } => {
+                let mut changes = Vec::new();
+                if from.sql_type != to.sql_type {
+                    changes.push("new type".to_string());
+                }
+                if from.nullable != to.nullable {
+                    changes.push(
+                        if to.nullable {
+                            "now nullable"
+                        } else {
+                            "now required"
+                        }
+                        .into(),
+                    );
+                }
+                if !same_default(&from.default, &to.default) {
+                    let describe = |default: &Option<DefaultValue>| {
+                        default
+                            .as_ref()
+                            .map_or_else(|| "none".to_string(), ToString::to_string)
+                    };
+                    changes.push(format!(
+                        "default {} to {}",
+                        describe(&from.default),
+                        describe(&to.default)
+                    ));
+                }
+                write!(
+                    f,
+                    "changed column {}.{}: {}",
+                    name(table),
+                    to.name,
+                    changes.join(", ")
+                )
+            }
+            MigrationStep::AddPrimaryKey(table) => write!(
+                f,
+                "added primary key ({}) on {}",
+                table.primary_key.join(", "),
+                name(table)
+            ),
+            MigrationStep::DropPrimaryKey(table) => {
+                write!(f, "removed the primary key of {}", name(table))
+            }
+            MigrationStep::AddUnique { table, columns } => write!(
+                f,
+                "added unique constraint ({}) on {}",
+                columns.join(", "),
+                name(table)
+            ),
+            MigrationStep::DropUnique { table, columns } => write!(
+                f,
+                "removed unique constraint ({}) on {}",
+                columns.join(", "),
+                name(table)
+            ),
+            MigrationStep::AddIndex { table, columns } => {
+                write!(f, "added index ({}) on {}", columns.join(", "), name(table))
+            }
+            MigrationStep::DropIndex { table, columns } => {
+                write!(
+                    f,
+                    "removed index ({}) on {}",
+                    columns.join(", "),
+                    name(table)
+                )
+            }
+            MigrationStep::AddCheck { table, check } => {
+                write!(f, "added check {} on {}", check.name, name(table))
+            }
+            MigrationStep::DropCheck { table, check } => {
+                write!(f, "removed check {} on {}", check.name, name(table))
+            }
+            MigrationStep::AddForeignKey { table, foreign_key } => write!(
+                f,
+                "added foreign key ({}) on {} referencing {}",
+                foreign_key.columns.join(", "),
+                name(table),
+                foreign_key.references_table
+            ),
+            MigrationStep::DropForeignKey { table, foreign_key } => write!(
+                f,
+                "removed foreign key ({}) on {} referencing {}",
+                foreign_key.columns.join(", "),
+                name(table),
+                foreign_key.references_table
+            ),
+            MigrationStep::SetTableComment(table) => {
+                write!(f, "changed the comment of table {}", name(table))
+            }
+            MigrationStep::SetColumnComment { table, column } => write!(
+                f,
+                "changed the comment of column {}.{}",
+                name(table),
+                column.name
+            ),
+            MigrationStep::AddUpdatedAt { table, column } => {
+                write!(f, "added @updatedAt to {}.{}", name(table), column)
+            }
+            MigrationStep::DropUpdatedAt { table, column } => {
+                write!(f, "removed @updatedAt from {}.{}", name(table), column)
+            }
+        }
+    }
+}
+
+/// `schema.table`, or just the table name outside a named schema.
+fn name(table: &SqlTable) -> String {
+    match &table.schema {
+        Some(schema) => format!("{}.{}", schema, table.name),
+        None => table.name.clone(),
+    }
+}
+
+/// The steps that turn the tables of `from` into those of `to`, in an order that can run:
+/// constraints that go away are dropped first and foreign keys are added last.
+pub fn plan_migration(from: &Schema, to: &Schema) -> Vec<MigrationStep> {
+    plan_tables(schema_to_tables(from), schema_to_tables(to), to)
+}
+
+/// The steps that undo `plan_migration(from, to)`, turning the tables of `to` back into
+/// those of `from`. Renames are reversed rather than dropped and recreated.
+pub fn plan_rollback(from: &Schema, to: &Schema) -> Vec<MigrationStep> {
+    let mut back = schema_to_tables(from);
+    for table in back.iter_mut() {
+        table.renamed_from = None;
+        table.renamed_columns.clear();
+    }
+    let mut renamed_tables = Vec::new();
+    for step in plan_migration(from, to) {
+        let (table, previous_table, column) = match step {
+            MigrationStep::RenameTable {
+                table,
+                from: previous,
+            } => {
+                renamed_tables.push((table.schema.clone(), table.name.clone(), previous.clone()));
+                (table, previous, None)
+            }
+            MigrationStep::RenameColumn {
+                table,
+                from: previous,
+                to: current,
+            } => {
+                let previous_table = renamed_tables
+                    .iter()
+                    .find(|(schema, name, _)| schema == &table.schema && name == &table.name)
+                    .map_or_else(|| table.name.clone(), |(_, _, previous)| previous.clone());
+                (table, previous_table, Some((current, previous)))
+            }
+            _ => continue,
+        };
+        let Some(previous) = back
+            .iter_mut()
+            .find(|other| other.schema == table.schema && other.name == previous_table)
+        else {
+            continue;
+        };
+        match column {
+            Some(rename) => previous.renamed_columns.push(rename),
+            None => previous.renamed_from = Some(table.name),
+        }
+    }
+    plan_tables(schema_to_tables(to), back, from)
+}
+
+fn plan_tables(
+    mut from_tables: Vec<SqlTable>,
+    to_tables: Vec<SqlTable>,
+    to: &Schema,
+) -> Vec<MigrationStep> {
+    let dialect = dialect_for(&to.datasource.provider).ok();
+
+    let mut renames = Vec::new();
+    let mut drops = Vec::new();
+    apply_renames(&mut from_tables, &to_tables, &mut renames, &mut drops);
+    let mut creates = Vec::new();
+    let mut changes = Vec::new();
+    let mut foreign_keys = Vec::new();
+    for table in &to_tables {
+        match find_table(&from_tables, table) {
+            Some(existing) => {
+                let same_type = |a: &SqlType, b: &SqlType| same_type(dialect.as_deref(), a, b);
+                diff_table(
+                    existing,
+                    table,
+                    &same_type,
+                    &mut drops,
+                    &mut changes,
+                    &mut foreign_keys,
+                )
+            }
+            None => {
+                creates.push(MigrationStep::CreateTable(table.clone()));
+                if !table.foreign_keys.is_empty() {
+                    foreign_keys.push(MigrationStep::CreateForeignKeys(table.clone()));
+                }
+            }
+        }
+    }
+
+    // A dropped table goes before the dropped tables it references.
+    let mut dropped: Vec<&SqlTable> = from_tables
+        .iter()
+        .filter(|table| find_table(&to_tables, table).is_none())
+        .collect();
+    while !dropped.is_empty() {
+        let next = dropped
+            .iter()
+            .position(|table| {
+                !dropped
+                    .iter()
+                    .any(|other| other.name != table.name && references(other, table))
+            })
+            .unwrap_or(0);
+        drops.push(MigrationStep::DropTable(dropped.remove(next).clone()));
+    }
+
+    // Changes the database has no way to store, like comments on SQLite, are no changes.
+    renames
+        .into_iter()
+        .chain(drops)
+        .chain(creates)
+        .chain(changes)
+        .chain(foreign_keys)
+        .filter(|step| {
+            !dialect
+                .as_ref()
+                .is_some_and(|dialect| dialect.step_sql(step).is_ok_and(|sql| sql.is_empty()))
+        })
+        .collect()
+}
+
+/// A table or column a plan drops while adding one just like it under another name,
+/// which may be a rename the schema does not mark with `@renamedFrom`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PossibleRename {
+    Table {
+        schema: Option<String>,
+        from: String,
+        to: String,
+    },
+    Column {
+        schema: Option<String>,
+        table: String,
+        from: String,
+        to: String,
+    },
+}
+
+pub fn possible_renames(steps: &[MigrationStep]) -> Vec<PossibleRename> {
+    let mut candidates = Vec::new();
+    for dropped in steps {
+        for added in steps {
+            match (dropped, added) {
+                (MigrationStep::DropTable(from), MigrationStep::CreateTable(to))
+                    if from.schema == to.schema
+                        && from.columns.len() == to.columns.len()
+                        && from.columns.iter().all(|column| {
+                            to.columns.iter().any(|other| {
+                                other.name == column.name && other.sql_type == column.sql_type
+                            })
+                        }) =>
+                {
+                    candidates.push(PossibleRename::Table {
+                        schema: to.schema.clone(),
+                        from: from.name.clone(),
+                        to: to.name.clone(),
+                    });
+                }
+                (
+                    MigrationStep::DropColumn {
+                        table,
+                        column: from,
+                    },
+                    MigrationStep::AddColumn {
+                        table: other,
+                        column: to,
+                    },
+                ) if table.schema == other.schema
+                    && table.name == other.name
+                    && from.name != to.name
+                    && from.sql_type == to.sql_type =>
+                {
+                    candidates.push(PossibleRename::Column {
+                        schema: table.schema.clone(),
+                        table: table.name.clone(),
+                        from: from.name.clone(),
+                        to: to.name.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    candidates
+}
+
+/// Renders a plan in the dialect of `schema`, the schema the plan leads to.
+pub fn migration_sql(schema: &Schema, steps: &[MigrationStep]) -> Result<Vec<String>, PetrolError> {
+    render_plan(schema, steps, false)
+}
+
+/// Renders a plan like [`migration_sql`], with the steps that would lock a table for a
+/// whole scan split up as [`Dialect::safe_step_sql`] does.
+pub fn safe_migration_sql(
+    schema: &Schema,
+    steps: &[MigrationStep],
+) -> Result<Vec<String>, PetrolError> {
+    render_plan(schema, steps, true)
+}
+
+fn render_plan(
+    schema: &Schema,
+    steps: &[MigrationStep],
+    safe: bool,
+) -> Result<Vec<String>, PetrolError> {
+    if steps.is_empty() {
+        return Ok(Vec::new());
+    }
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    dialect.check_schema(schema)?;
+    let mut statements = dialect.setup_sql(schema)?;
+    for step in steps {
+        statements.extend(if safe {
+            dialect.safe_step_sql(step)?
+        } else {
+            dialect.step_sql(step)?
+        });
+    }
+    statements.extend(dialect.finish_sql());
+    Ok(statements)
+}
+
+fn find_table<'a>(tables: &'a [SqlTable], table: &SqlTable) -> Option<&'a SqlTable> {
+    tables
+        .iter()
+        .find(|candidate| candidate.schema == table.schema && candidate.name == table.name)
+}
+
+/// Renames the tables and columns of `from` that `to` marks with `@@renamedFrom` or
+/// `@renamedFrom`, so the rest of the plan compares them under their new names. A hint
+/// whose old name is gone, or whose new name is already taken, has been applied before.
+fn apply_renames(
+    from: &mut [SqlTable],
+    to: &[SqlTable],
+    renames: &mut Vec<MigrationStep>,
+    drops: &mut Vec<MigrationStep>,
+) {
+    for table in to {
+        let position = |tables: &[SqlTable], name: &str| {
+            tables
+                .iter()
+                .position(|other| other.schema == table.schema && other.name == name)
+        };
+        if let Some(previous) = &table.renamed_from {
+            if let (None, Some(index), None) = (
+                position(from, &table.name),
+                position(from, previous),
+                position(to, previous),
+            ) {
+                renames.push(MigrationStep::RenameTable {
+                    table: table.clone(),
+                    from: previous.clone(),
+                });
+                from[index].name = table.name.clone();
+                for foreign_key in from.iter_mut().flat_map(|other| &mut other.foreign_keys) {
+                    if foreign_key.references_schema == table.schema
+                        && &foreign_key.references_table == previous
+                    {
+                        foreign_key.references_table = table.name.clone();
+                    }
+                }
+            }
+        }
+
+        let Some(index) = position(from, &table.name) else {
+            continue;
+        };
+        let has_column =
+            |table: &SqlTable, name: &str| table.columns.iter().any(|column| column.name == name);
+        for (previous, current) in &table.renamed_columns {
+            if !has_column(&from[index], previous)
+                || has_column(&from[index], current)
+                || has_column(table, previous)
+            {
+                continue;
+            }
+            renames.push(MigrationStep::RenameColumn {
+                table: table.clone(),
+                from: previous.clone(),
+                to: current.clone(),
+            });
+
+            let existing = &mut from[index];
+            // The `@updatedAt` trigger is tied to the column name, so it is recreated.
+            if existing.updated_at.contains(previous) {
+                drops.push(MigrationStep::DropUpdatedAt {
+                    table: table.clone(),
+                    column: previous.clone(),
+                });
+                existing.updated_at.retain(|column| column != previous);
+            }
+            let rename = |names: &mut Vec<String>| {
+                for name in names.iter_mut().filter(|name| *name == previous) {
+                    *name = current.clone();
+                }
+            };
+            for column in existing.columns.iter_mut() {
+                if &column.name == previous {
+                    column.name = current.clone();
+                }
+            }
+            rename(&mut existing.primary_key);
+            existing.uniques.iter_mut().for_each(rename);
+            existing.indexes.iter_mut().for_each(rename);
+            for foreign_key in existing.foreign_keys.iter_mut() {
+                rename(&mut foreign_key.columns);
+            }
+            for foreign_key in from.iter_mut().flat_map(|other| &mut other.foreign_keys) {
+                if foreign_key.references_schema == table.schema
+                    && foreign_key.references_table == table.name
+                {
+                    rename(&mut foreign_key.references_columns);
+                }
+            }
+        }
+    }
+
+    // Implicit many-to-many tables are named after their models and follow their renames:
+    // an old one that now matches a new one column for column is the same table.
+    for table in to.iter().filter(|table| table.name.starts_with('_')) {
+        let taken = |tables: &[SqlTable], name: &str| {
+            tables
+                .iter()
+                .any(|other| other.schema == table.schema && other.name == name)
+        };
+        if taken(from, &table.name) {
+            continue;
+        }
+        let previous = from.iter().position(|other| {
+            other.schema == table.schema
+                && other.name.starts_with('_')
+                && !taken(to, &other.name)
+                && other.columns == table.columns
+                && other.foreign_keys == table.foreign_keys
+        });
+        if let Some(index) = previous {
+            renames.push(MigrationStep::RenameTable {
+                table: table.clone(),
+                from: from[index].name.clone(),
+            });
+            from[index].name = table.name.clone();
+        }
+    }
+}
+
+fn references(table: &SqlTable, target: &SqlTable) -> bool {
+    table.foreign_keys.iter().any(|foreign_key| {
+        foreign_key.references_table == target.name
+            && foreign_key.references_schema == target.schema
+    })
+}
+
+fn diff_table(
+    from: &SqlTable,
+    to: &SqlTable,
+    same_type: &dyn Fn(&SqlType, &SqlType) -> bool,
+    drops: &mut Vec<MigrationStep>,
+    changes: &mut Vec<MigrationStep>,
+    foreign_keys: &mut Vec<MigrationStep>,
+) {
+    let table = || to.clone();
+
+    for foreign_key in &from.foreign_keys {
+        if !to.foreign_keys.contains(foreign_key) {
+            drops.push(MigrationStep::DropForeignKey {
+                table: table(),
+                foreign_key: foreign_key.clone(),
+            });
+        }
+    }
+    for check in &from.checks {
+        if !to.checks.iter().any(|other| same_check(check, other)) {
+            drops.push(MigrationStep::DropCheck {
+                table: table(),
+                check: check.clone(),
+            });
+        }
+    }
+    for columns in &from.uniques {
+        if !to.uniques.contains(columns) {
+            drops.push(MigrationStep::DropUnique {
+                table: table(),
+                columns: columns.clone(),
+            });
+        }
+    }
+    for columns in &from.indexes {
+        if !to.indexes.contains(columns) {
+            drops.push(MigrationStep::DropIndex {
+                table: table(),
+                columns: columns.clone(),
+            });
+        }
+    }
+    let primary_key_changed = from.primary_key != to.primary_key;
+    if primary_key_changed && !from.primary_key.is_empty() {
+        drops.push(MigrationStep::DropPrimaryKey(table()));
+    }
+
+    // Generated columns cannot be altered into another expression, only replaced.
+    let mut added = Vec::new();
+    for column in &from.columns {
+        match to.columns.iter().find(|other| other.name == column.name) {
+            Some(target) if same_expressions(&column.generated, &target.generated) => {}
+            Some(target) => {
+                changes.push(MigrationStep::DropColumn {
+                    table: table(),
+                    column: column.clone(),
+                });
+                added.push(target);
+            }
+            None => changes.push(MigrationStep::DropColumn {
+                table: table(),
+                column: column.clone(),
+            }),
+        }
+    }
+    added.extend(
+        to.columns
+            .iter()
+            .filter(|column| !from.columns.iter().any(|other| other.name == column.name)),
+    );
+    for column in added {
+        changes.push(MigrationStep::AddColumn {
+            table: table(),
+            column: column.clone(),
+        });
+    }
+
+    for column in &to.columns {
+        let Some(existing) = from.columns.iter().find(|other| other.name == column.name) else {
+            continue;
+        };
+        if !same_expressions(&existing.generated, &column.generated) {
+            continue;
+        }
+        if !same_type(&existing.sql_type, &column.sql_type)
+            || existing.nullable != column.nullable
+            || !same_default(&existing.default, &column.default)
+        {
+            changes.push(MigrationStep::AlterColumn {
+                table: table(),
+                from: existing.clone(),
+                to: column.clone(),
+            });
+        }
+        if existing.comment != column.comment {
+            changes.push(MigrationStep::SetColumnComment {
+                table: table(),
+                column: column.clone(),
+            });
+        }
+        if from.updated_at.contains(&column.name) && !to.updated_at.contains(&column.name) {
+            changes.push(MigrationStep::DropUpdatedAt {
+                table: table(),
+                column: column.name.clone(),
+            });
+        }
+    }
+    for column in &to.updated_at {
+        if !from.updated_at.contains(column) {
+            changes.push(MigrationStep::AddUpdatedAt {
+                table: table(),
+                column: column.clone(),
+            });
+        }
+    }
+
+    if primary_key_changed && !to.primary_key.is_empty() {
+        changes.push(MigrationStep::AddPrimaryKey(table()));
+    }
+    for columns in &to.uniques {
+        if !from.uniques.contains(columns) {
+            changes.push(MigrationStep::AddUnique {
+                table: table(),
+                columns: columns.clone(),
+            });
+        }
+    }
+    for columns in &to.indexes {
+        if !from.indexes.contains(columns) {
+            changes.push(MigrationStep::AddIndex {
+                table: table(),
+                columns: columns.clone(),
+            });
+        }
+    }
+    for check in &to.checks {
+        if !from.checks.iter().any(|other| same_check(check, other)) {
+            changes.push(MigrationStep::AddCheck {
+                table: table(),
+                check: check.clone(),
+            });
+        }
+    }
+    if from.comment != to.comment {
+        changes.push(MigrationStep::SetTableComment(table()));
+    }
+
+    for foreign_key in &to.foreign_keys {
+        if !from.foreign_keys.contains(foreign_key) {
+            foreign_keys.push(MigrationStep::AddForeignKey {
+                table: table(),
+                foreign_key: foreign_key.clone(),
+            });
+        }
+    }
+}
+
+/// Types are the same when the database cannot tell them apart, as with `Json` and
+/// `String` on SQLite.
+fn same_type(dialect: Option<&dyn Dialect>, a: &SqlType, b: &SqlType) -> bool {
+    if a == b {
+        return true;
+    }
+    match dialect.map(|dialect| (dialect.render_type(a), dialect.render_type(b))) {
+        Some((Ok(a), Ok(b))) => a == b,
+        _ => false,
+    }
+}
+
+fn same_check(a: &SqlCheck, b: &SqlCheck) -> bool {
+    a.name == b.name && same_expression(&a.expr, &b.expr)
+}
+
+fn same_default(a: &Option<DefaultValue>, b: &Option<DefaultValue>) -> bool {
+    match (a, b) {
+        (Some(DefaultValue::DbGenerated(a)), Some(DefaultValue::DbGenerated(b))) => {
+            same_expression(a, b)
+        }
+        _ => a == b,
+    }
+}
+
+fn same_expressions(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_expression(a, b),
+        _ => a == b,
+    }
+}
@@ -0,0 +1,445 @@
+//! Comparing SQL expressions the way a database stores them. Postgres prints checks,
+//! defaults and generated columns back with `::type` casts and a pair of parentheses
+//! around every operation, so both sides are parsed with SQL's operator precedence and
+//! rendered fully parenthesized: only parentheses that change the grouping count.
+
+use std::fmt::{self, Display};
+
+/// Whether two SQL expressions differ only in how a database printed them back. Case,
+/// whitespace and quotes around identifiers outside literals never matter.
+pub(crate) fn same_expression(a: &str, b: &str) -> bool {
+    normalize_expression(a) == normalize_expression(b)
+}
+
+fn normalize_expression(expr: &str) -> String {
+    let tokens = tokenize(expr);
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+    };
+    match parser.expression(0) {
+        Some(node) if parser.position == tokens.len() => node.to_string(),
+        // Syntax the parser does not know is compared token by token.
+        _ => tokens
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A keyword or identifier, lower-cased unless it was quoted.
+    Word(String),
+    /// A string or number literal as written.
+    Literal(String),
+    Operator(String),
+    Punctuation(char),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(text) | Token::Literal(text) | Token::Operator(text) => {
+                write!(f, "{}", text)
+            }
+            Token::Punctuation(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+const OPERATOR_CHARS: &str = "+-*/<>=~!@#%^&|`?";
+
+fn tokenize(expr: &str) -> Vec<Token> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == ':' && chars.get(i + 1) == Some(&':') {
+            i = skip_cast(&chars, i + 2);
+        } else if c == '\'' {
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '\'' && chars.get(i + 1) != Some(&'\'') {
+                    break;
+                }
+                i += if chars[i] == '\'' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            tokens.push(Token::Literal(chars[start..i].iter().collect()));
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start + 1..i].iter().collect()));
+            i = (i + 1).min(chars.len());
+        } else if c.is_ascii_digit() {
+            while chars
+                .get(i)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '.')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Literal(chars[start..i].iter().collect()));
+        } else if c.is_alphanumeric() || c == '_' {
+            while chars
+                .get(i)
+                .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '$' | '.'))
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(Token::Word(word.to_lowercase()));
+        } else if OPERATOR_CHARS.contains(c) {
+            while chars.get(i).is_some_and(|c| OPERATOR_CHARS.contains(*c)) {
+                i += 1;
+            }
+            tokens.push(Token::Operator(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(Token::Punctuation(c));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// Words that continue a type name after its first word, as in `double precision`.
+const TYPE_NAME_WORDS: &[&str] = &["precision", "varying", "with", "without", "time", "zone"];
+
+/// The end of the type name of a cast whose `::` ends at `start`, including arguments
+/// such as `(20)` and array brackets.
+fn skip_cast(chars: &[char], start: usize) -> usize {
+    let mut end = start;
+    let mut i = start;
+    loop {
+        while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+            i += 1;
+        }
+        let word_start = i;
+        while chars
+            .get(i)
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '"' | '.'))
+        {
+            i += 1;
+        }
+        let word = chars[word_start..i]
+            .iter()
+            .collect::<String>()
+            .to_lowercase();
+        if word.is_empty() || (end != start && !TYPE_NAME_WORDS.contains(&word.as_str())) {
+            break;
+        }
+        end = i;
+    }
+    if chars.get(end) == Some(&'(') {
+        while chars.get(end).is_some_and(|c| *c != ')') {
+            end += 1;
+        }
+        end = (end + 1).min(chars.len());
+    }
+    while chars.get(end) == Some(&'[') && chars.get(end + 1) == Some(&']') {
+        end += 2;
+    }
+    end
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Atom(String),
+    Unary(String, Box<Node>),
+    Binary(String, Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+    List(Vec<Node>),
+    Array(Vec<Node>),
+    /// `CASE ... END`, its keywords as atoms.
+    Case(Vec<Node>),
+}
+
+fn binary(op: &str, left: Node, right: Node) -> Node {
+    Node::Binary(op.to_string(), Box::new(left), Box::new(right))
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |nodes: &[Node], separator: &str| {
+            nodes
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+        match self {
+            Node::Atom(text) => write!(f, "{}", text),
+            Node::Unary(op, operand) => write!(f, "({} {})", op, operand),
+            Node::Binary(op, left, right) => write!(f, "({} {} {})", left, op, right),
+            Node::Call(name, args) => write!(f, "{}({})", name, join(args, ", ")),
+            Node::List(items) => write!(f, "({})", join(items, ", ")),
+            Node::Array(items) => write!(f, "array[{}]", join(items, ", ")),
+            Node::Case(parts) => write!(f, "{}", join(parts, " ")),
+        }
+    }
+}
+
+/// Binding powers following Postgres' operator precedence, loosest first.
+const OR: u8 = 1;
+const AND: u8 = 2;
+const NOT: u8 = 3;
+const IS: u8 = 4;
+const COMPARISON: u8 = 5;
+const PATTERN: u8 = 6;
+const OTHER: u8 = 7;
+const ADDITIVE: u8 = 8;
+const MULTIPLICATIVE: u8 = 9;
+const EXPONENT: u8 = 10;
+const SIGN: u8 = 11;
+const SUBSCRIPT: u8 = 12;
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_word(&self, offset: usize) -> Option<&str> {
+        match self.tokens.get(self.position + offset) {
+            Some(Token::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token) -> Option<()> {
+        (self.next()? == token).then_some(())
+    }
+
+    fn expect_word(&mut self, word: &str) -> Option<()> {
+        self.expect(Token::Word(word.to_string()))
+    }
+
+    /// An expression made of operators binding tighter than `min_power`.
+    fn expression(&mut self, min_power: u8) -> Option<Node> {
+        let mut left = self.prefix()?;
+        while let Some(power) = self.infix_power() {
+            if power <= min_power {
+                break;
+            }
+            left = self.infix(left, power)?;
+        }
+        Some(left)
+    }
+
+    fn infix_power(&self) -> Option<u8> {
+        Some(match self.peek()? {
+            Token::Word(word) => match word.as_str() {
+                "or" => OR,
+                "and" => AND,
+                "is" => IS,
+                "like" | "ilike" | "similar" | "in" | "between" => PATTERN,
+                "not" => match self.peek_word(1)? {
+                    "like" | "ilike" | "similar" | "in" | "between" => PATTERN,
+                    _ => return None,
+                },
+                _ => return None,
+            },
+            Token::Operator(op) => match op.as_str() {
+                "=" | "<>" | "!=" | "<" | ">" | "<=" | ">=" => COMPARISON,
+                "+" | "-" => ADDITIVE,
+                "*" | "/" | "%" => MULTIPLICATIVE,
+                "^" => EXPONENT,
+                _ => OTHER,
+            },
+            Token::Punctuation('[') => SUBSCRIPT,
+            _ => return None,
+        })
+    }
+
+    fn infix(&mut self, left: Node, power: u8) -> Option<Node> {
+        Some(match self.next()? {
+            Token::Punctuation('[') => {
+                let index = self.expression(0)?;
+                self.expect(Token::Punctuation(']'))?;
+                binary("[]", left, index)
+            }
+            Token::Operator(op) => {
+                let right = self.expression(power)?;
+                binary(&op, left, right)
+            }
+            Token::Word(word) if word == "is" => {
+                let mut op = "is".to_string();
+                if self.peek_word(0) == Some("not") {
+                    self.next();
+                    op.push_str(" not");
+                }
+                if self.peek_word(0) == Some("distinct") {
+                    self.next();
+                    self.expect_word("from")?;
+                    op.push_str(" distinct from");
+                    let right = self.expression(IS)?;
+                    binary(&op, left, right)
+                } else {
+                    let Token::Word(value) = self.next()? else {
+                        return None;
+                    };
+                    binary(&op, left, Node::Atom(value))
+                }
+            }
+            Token::Word(mut op) => {
+                if op == "not" {
+                    let Token::Word(word) = self.next()? else {
+                        return None;
+                    };
+                    op = format!("not {}", word);
+                }
+                if op.ends_with("similar") {
+                    self.expect_word("to")?;
+                    op.push_str(" to");
+                }
+                if op.ends_with("between") {
+                    let low = self.expression(PATTERN)?;
+                    self.expect_word("and")?;
+                    let high = self.expression(PATTERN)?;
+                    // Postgres stores `BETWEEN` as the two comparisons it stands for.
+                    let (join, above, below) = match op.as_str() {
+                        "between" => ("and", ">=", "<="),
+                        _ => ("or", "<", ">"),
+                    };
+                    binary(
+                        join,
+                        binary(above, left.clone(), low),
+                        binary(below, left, high),
+                    )
+                } else if op.ends_with("in") {
+                    let right = self.prefix()?;
+                    binary(&op, left, right)
+                } else {
+                    let right = self.expression(power)?;
+                    binary(&op, left, right)
+                }
+            }
+            Token::Literal(_) | Token::Punctuation(_) => return None,
+        })
+    }
+
+    fn prefix(&mut self) -> Option<Node> {
+        Some(match self.next()? {
+            Token::Literal(literal) => Node::Atom(literal),
+            Token::Operator(op) if op == "-" || op == "+" => {
+                Node::Unary(op, Box::new(self.expression(SIGN)?))
+            }
+            Token::Operator(op) if op == "*" => Node::Atom(op),
+            Token::Punctuation('(') => {
+                let items = self.list(')')?;
+                match <[Node; 1]>::try_from(items) {
+                    // Parentheses around one expression only group it.
+                    Ok([item]) => item,
+                    Err(items) => Node::List(items),
+                }
+            }
+            Token::Word(word) if word == "not" => {
+                Node::Unary(word, Box::new(self.expression(NOT)?))
+            }
+            Token::Word(word) if word == "case" => self.case()?,
+            Token::Word(word) if word == "array" => {
+                self.expect(Token::Punctuation('['))?;
+                Node::Array(self.list(']')?)
+            }
+            Token::Word(word) => {
+                if self.peek() == Some(&Token::Punctuation('(')) {
+                    self.next();
+                    Node::Call(word, self.list(')')?)
+                } else {
+                    Node::Atom(word)
+                }
+            }
+            Token::Operator(_) | Token::Punctuation(_) => return None,
+        })
+    }
+
+    /// Comma-separated expressions up to the closing `end`, which is consumed.
+    fn list(&mut self, end: char) -> Option<Vec<Node>> {
+        let mut items = Vec::new();
+        if self.peek() == Some(&Token::Punctuation(end)) {
+            self.next();
+            return Some(items);
+        }
+        loop {
+            items.push(self.expression(0)?);
+            match self.next()? {
+                Token::Punctuation(',') => continue,
+                Token::Punctuation(c) if c == end => return Some(items),
+                _ => return None,
+            }
+        }
+    }
+
+    fn case(&mut self) -> Option<Node> {
+        let mut parts = vec![Node::Atom("case".to_string())];
+        if self.peek_word(0) != Some("when") {
+            parts.push(self.expression(0)?);
+        }
+        loop {
+            let Token::Word(keyword) = self.next()? else {
+                return None;
+            };
+            match keyword.as_str() {
+                "when" | "then" | "else" => {
+                    parts.push(Node::Atom(keyword));
+                    parts.push(self.expression(0)?);
+                }
+                "end" => {
+                    parts.push(Node::Atom(keyword));
+                    return Some(Node::Case(parts));
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::same_expression;
+
+    #[test]
+    fn ignores_parentheses_and_casts_postgres_adds() {
+        assert!(same_expression("age >= 0", "(age >= 0)"));
+        assert!(same_expression(
+            "a + b * c > 0 AND name <> 'x'",
+            "(((a + (b * c)) > 0) AND (name <> 'x'::text))"
+        ));
+        assert!(same_expression(
+            "md5(random()::text)",
+            "md5((random())::text)"
+        ));
+        assert!(same_expression(
+            "start_at < end_at",
+            "(\"start_at\" < \"end_at\")"
+        ));
+        assert!(same_expression(
+            "score BETWEEN 0 AND 100",
+            "((score >= 0) AND (score <= 100))"
+        ));
+    }
+
+    #[test]
+    fn detects_changed_grouping() {
+        assert!(!same_expression("(age + 1) * 2 > 0", "age + 1 * 2 > 0"));
+        assert!(!same_expression("(a OR b) AND c", "((a OR b) AND c) OR d"));
+        assert!(!same_expression("a OR b AND c", "(a OR b) AND c"));
+    }
+}
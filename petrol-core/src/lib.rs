@@ -1,4 +1,8 @@
+pub mod dialect;
+pub mod diff;
 pub mod error;
+mod expression;
+pub mod migrations;
 pub mod schema;
 pub mod sql;
 
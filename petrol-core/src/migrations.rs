@@ -0,0 +1,266 @@
+use sha2::{Digest, Sha256};
+
+use crate::dialect::{quote_literal, Dialect};
+use crate::schema::DefaultValue;
+use crate::sql::{SqlColumn, SqlTable, SqlType};
+
+/// The table recording which migrations a database has applied.
+pub const MIGRATIONS_TABLE: &str = "_petrol_migrations";
+
+/// A migration as stored in `migrations/<name>/migration.sql`, with the script undoing
+/// it in `down.sql`. Names start with a timestamp, so sorting them gives the order they
+/// were created in.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub name: String,
+    pub sql: String,
+    pub down: Option<String>,
+}
+
+impl Migration {
+    /// SHA-256 of the script, recorded when the migration is applied so later edits
+    /// to an applied migration can be noticed.
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Sha256::digest(self.sql.as_bytes()))
+    }
+
+    pub fn statements(&self, dialect: &dyn Dialect) -> Vec<String> {
+        split_statements(&self.sql, dialect)
+    }
+
+    pub fn down_statements(&self, dialect: &dyn Dialect) -> Option<Vec<String>> {
+        self.down
+            .as_deref()
+            .map(|sql| split_statements(sql, dialect))
+    }
+}
+
+pub fn migrations_table() -> SqlTable {
+    let column = |name: &str, sql_type: SqlType, default: Option<DefaultValue>| SqlColumn {
+        name: name.to_string(),
+        sql_type,
+        nullable: false,
+        default,
+        generated: None,
+        comment: None,
+    };
+    SqlTable {
+        schema: None,
+        name: MIGRATIONS_TABLE.to_string(),
+        columns: vec![
+            column("name", SqlType::VarChar(Some(191)), None),
+            column("checksum", SqlType::VarChar(Some(64)), None),
+            column(
+                "applied_at",
+                SqlType::Timestamptz(None),
+                Some(DefaultValue::Now),
+            ),
+        ],
+        primary_key: vec!["name".to_string()],
+        uniques: Vec::new(),
+        indexes: Vec::new(),
+        foreign_keys: Vec::new(),
+        checks: Vec::new(),
+        updated_at: Vec::new(),
+        comment: None,
+        renamed_from: None,
+        renamed_columns: Vec::new(),
+    }
+}
+
+pub fn applied_migrations_sql(dialect: &dyn Dialect) -> String {
+    format!(
+        "SELECT name, checksum FROM {} ORDER BY name",
+        dialect.quote_identifier(MIGRATIONS_TABLE)
+    )
+}
+
+pub fn record_migration_sql(dialect: &dyn Dialect, migration: &Migration) -> String {
+    format!(
+        "INSERT INTO {} (name, checksum) VALUES ({}, {});\n",
+        dialect.quote_identifier(MIGRATIONS_TABLE),
+        quote_literal(&migration.name),
+        quote_literal(&migration.checksum())
+    )
+}
+
+pub fn forget_migration_sql(dialect: &dyn Dialect, name: &str) -> String {
+    format!(
+        "DELETE FROM {} WHERE name = {};\n",
+        dialect.quote_identifier(MIGRATIONS_TABLE),
+        quote_literal(name)
+    )
+}
+
+/// Splits a script at the semicolons that end statements, skipping those inside
+/// literals, quoted identifiers, comments, `$$` bodies and SQLite trigger bodies.
+/// Each statement keeps its leading comments; chunks with nothing but comments are dropped.
+pub fn split_statements(sql: &str, dialect: &dyn Dialect) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut has_code = false;
+    // Upper-cased words outside quotes, to tell where a `CREATE TRIGGER ... BEGIN` ends.
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c.to_ascii_uppercase());
+            current.push(c);
+            has_code = true;
+            i += 1;
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+
+        let end = match (c, chars.get(i + 1)) {
+            ('\'' | '"', _) => quoted_end(&chars, i + 1, c, dialect.backslash_escapes()),
+            ('`', _) => quoted_end(&chars, i + 1, c, false),
+            ('-', Some('-')) => {
+                let end = position_from(&chars, i, |c| c == '\n').unwrap_or(chars.len());
+                current.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            ('/', Some('*')) => {
+                let end = (i + 2..chars.len())
+                    .find(|&j| chars[j - 1] == '*' && chars[j] == '/' && j > i + 2)
+                    .map_or(chars.len(), |j| j + 1);
+                current.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            ('$', _) => match dollar_quote_end(&chars, i) {
+                Some(end) => end,
+                None => i + 1,
+            },
+            (';', _) => {
+                let in_trigger = words.iter().take(4).any(|word| word == "TRIGGER")
+                    && words.iter().any(|word| word == "BEGIN")
+                    && words.last().map(String::as_str) != Some("END");
+                if !in_trigger {
+                    if has_code {
+                        statements.push(format!("{};\n", current.trim()));
+                    }
+                    current.clear();
+                    has_code = false;
+                    words.clear();
+                    i += 1;
+                    continue;
+                }
+                i + 1
+            }
+            _ => i + 1,
+        };
+        if !c.is_whitespace() {
+            has_code = true;
+        }
+        if end > i + 1 {
+            // Literals count as a word so `END` has to be the last thing before `;`.
+            words.push(String::new());
+        }
+        current.extend(&chars[i..end]);
+        i = end;
+    }
+    if has_code {
+        statements.push(format!("{}\n", current.trim()));
+    }
+    statements
+}
+
+fn position_from(chars: &[char], start: usize, pred: impl Fn(char) -> bool) -> Option<usize> {
+    (start..chars.len()).find(|&j| pred(chars[j]))
+}
+
+/// The index after the quote closing a literal that opened before `start`; doubled
+/// quotes, and with `backslash_escapes` backslashed characters, are part of the literal.
+fn quoted_end(chars: &[char], start: usize, quote: char, backslash_escapes: bool) -> usize {
+    let mut i = start;
+    while i < chars.len() {
+        if backslash_escapes && chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// The index after a `$tag$ ... $tag$` body starting at `start`, or `None` when the
+/// `$` does not open one, as in a `$1` placeholder.
+fn dollar_quote_end(chars: &[char], start: usize) -> Option<usize> {
+    let tag_end = position_from(chars, start + 1, |c| !(c.is_alphanumeric() || c == '_'))?;
+    if chars[tag_end] != '$' || chars.get(start + 1).is_some_and(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let tag = &chars[start..=tag_end];
+    let body_start = tag_end + 1;
+    let close = (body_start..chars.len()).find(|&j| chars[j..].starts_with(tag));
+    Some(close.map_or(chars.len(), |j| j + tag.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::{MySql, Postgres, Sqlite};
+
+    #[test]
+    fn skips_semicolons_in_quotes_and_comments() {
+        let sql = "-- first; part\nINSERT INTO \"a;b\" VALUES ('x;''y', `z;`);\n\
+                   /* second; */ SELECT 1;\n-- trailing; comment\n";
+        assert_eq!(
+            split_statements(sql, &Postgres),
+            vec![
+                "-- first; part\nINSERT INTO \"a;b\" VALUES ('x;''y', `z;`);\n",
+                "/* second; */ SELECT 1;\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_dollar_quoted_bodies_and_placeholders() {
+        let sql = "CREATE FUNCTION f() RETURNS trigger AS $body$ BEGIN NEW.x := now(); \
+                   RETURN NEW; END; $body$ LANGUAGE plpgsql;\nPREPARE p AS SELECT $1; EXECUTE p(1);";
+        let statements = split_statements(sql, &Postgres);
+        assert_eq!(statements.len(), 3);
+        assert!(statements[0].ends_with("$body$ LANGUAGE plpgsql;\n"));
+        assert_eq!(statements[1], "PREPARE p AS SELECT $1;\n");
+    }
+
+    #[test]
+    fn keeps_sqlite_trigger_bodies() {
+        let sql = "CREATE TRIGGER t AFTER UPDATE ON a BEGIN UPDATE a SET x = 1; END;\nSELECT 1;";
+        assert_eq!(
+            split_statements(sql, &Sqlite),
+            vec![
+                "CREATE TRIGGER t AFTER UPDATE ON a BEGIN UPDATE a SET x = 1; END;\n",
+                "SELECT 1;\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn honours_backslash_escapes_on_mysql_only() {
+        let sql = "INSERT INTO t VALUES ('it\\'s; x'); SELECT '\\\\';";
+        assert_eq!(
+            split_statements(sql, &MySql),
+            vec!["INSERT INTO t VALUES ('it\\'s; x');\n", "SELECT '\\\\';\n"]
+        );
+        assert_eq!(
+            split_statements("SELECT 'a\\'; SELECT 1;", &Postgres).len(),
+            2
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::dialect::dialect_for;
 use crate::error::PetrolError;
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
@@ -18,12 +19,228 @@ impl Schema {
         }
 
         for model in &self.models {
-            model.validate()?;
+            self.validate_model(model)?;
         }
+        for model in &self.models {
+            self.validate_relations(model)?;
+        }
+        dialect_for(&self.datasource.provider)?.check_schema(self)?;
+
+        for relation in self.many_to_many() {
+            for name in [&relation.model_a, &relation.model_b] {
+                if self
+                    .find_model(name)
+                    .map(Model::id_fields)
+                    .unwrap_or_default()
+                    .len()
+                    != 1
+                {
+                    return Err(PetrolError::validation(format!(
+                        "many-to-many relation {}.{} <-> {}.{} needs a single-field @id on {}",
+                        relation.model_a,
+                        relation.field_a,
+                        relation.model_b,
+                        relation.field_b,
+                        name
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pairs of list fields that point at each other without `@relation(fields: ...)`.
+    pub fn many_to_many(&self) -> Vec<ManyToMany> {
+        let mut relations = Vec::new();
+        for model in &self.models {
+            for field in &model.fields {
+                let Some(target) = field.implicit_list_target() else {
+                    continue;
+                };
+                let [back] = self.opposite_fields(model, field)[..] else {
+                    continue;
+                };
+                if back.implicit_list_target() != Some(model.name.as_str()) {
+                    continue;
+                }
+                // Each pair is found from both sides; keep the one seen from the first field.
+                if (target, back.name.as_str()) < (model.name.as_str(), field.name.as_str()) {
+                    continue;
+                }
+                relations.push(ManyToMany {
+                    name: field.relation_name().map(str::to_string),
+                    model_a: model.name.clone(),
+                    field_a: field.name.clone(),
+                    model_b: target.to_string(),
+                    field_b: back.name.clone(),
+                });
+            }
+        }
+        relations
+    }
+
+    /// Candidates for the other side of relation field `field` on `model`: relation fields
+    /// on the target model pointing back at `model` under the same relation name.
+    pub fn opposite_fields<'a>(&'a self, model: &Model, field: &Field) -> Vec<&'a Field> {
+        let Some(target) = field
+            .relation_target()
+            .and_then(|name| self.find_model(name))
+        else {
+            return Vec::new();
+        };
+        target
+            .fields
+            .iter()
+            .filter(|other| {
+                other.relation_target() == Some(model.name.as_str())
+                    && other.relation_name() == field.relation_name()
+                    && !(target.name == model.name && other.name == field.name)
+            })
+            .collect()
+    }
+
+    fn validate_relations(&self, model: &Model) -> Result<(), PetrolError> {
+        for field in &model.fields {
+            let Some(target_name) = field.relation_target() else {
+                continue;
+            };
+            let target = self.find_model(target_name).ok_or_else(|| {
+                PetrolError::validation(format!(
+                    "field {}.{} refers to unknown model {}",
+                    model.name, field.name, target_name
+                ))
+            })?;
+            let relation = match field.relation_name() {
+                Some(name) => format!("relation \"{}\"", name),
+                None => "unnamed relation".to_string(),
+            };
 
+            // A self-relation pairs two fields of the same model, anything beyond that
+            // cannot be told apart.
+            let mut siblings: Vec<&str> = model
+                .fields
+                .iter()
+                .filter(|other| {
+                    other.relation_target() == Some(target_name)
+                        && other.relation_name() == field.relation_name()
+                })
+                .map(|other| other.name.as_str())
+                .collect();
+            let allowed = if target.name == model.name { 2 } else { 1 };
+            if siblings.len() > allowed {
+                siblings.sort();
+                return Err(PetrolError::validation(format!(
+                    "model {} has an ambiguous {} to {} ({}), name each one with @relation(\"...\")",
+                    model.name,
+                    relation,
+                    target.name,
+                    siblings.join(", ")
+                )));
+            }
+
+            if let Some(attribute) = field.relation() {
+                if attribute.fields.len() != attribute.references.len() {
+                    return Err(PetrolError::validation(format!(
+                        "field {}.{}: @relation needs as many `references` as `fields`",
+                        model.name, field.name
+                    )));
+                }
+                for name in &attribute.fields {
+                    if !model
+                        .fields
+                        .iter()
+                        .any(|f| &f.name == name && f.is_scalar())
+                    {
+                        return Err(PetrolError::validation(format!(
+                            "field {}.{}: @relation refers to unknown field `{}`",
+                            model.name, field.name, name
+                        )));
+                    }
+                }
+                for name in &attribute.references {
+                    if !target
+                        .fields
+                        .iter()
+                        .any(|f| &f.name == name && f.is_scalar())
+                    {
+                        return Err(PetrolError::validation(format!(
+                            "field {}.{}: @relation references unknown field {}.{}",
+                            model.name, field.name, target.name, name
+                        )));
+                    }
+                }
+            }
+
+            match self.opposite_fields(model, field).as_slice() {
+                [] => {
+                    if let Some(name) = field.relation_name() {
+                        return Err(PetrolError::validation(format!(
+                            "field {}.{}: relation \"{}\" has no opposite field on {}",
+                            model.name, field.name, name, target.name
+                        )));
+                    }
+                }
+                [back] => {
+                    if field.owns_foreign_key() && back.owns_foreign_key() {
+                        return Err(PetrolError::validation(format!(
+                            "relation {}.{} <-> {}.{} declares `fields` on both sides, keep them on one",
+                            model.name, field.name, target.name, back.name
+                        )));
+                    }
+                    let list = |f: &Field| f.r#type.modifiers().list;
+                    if !list(field) && !field.owns_foreign_key() && !back.owns_foreign_key() {
+                        return Err(PetrolError::validation(format!(
+                            "relation {}.{} <-> {}.{} needs @relation(fields: [...], references: [...]) on one side",
+                            model.name, field.name, target.name, back.name
+                        )));
+                    }
+                }
+                several => {
+                    return Err(PetrolError::validation(format!(
+                        "field {}.{}: {} has several opposite fields on {} ({}), name each one with @relation(\"...\")",
+                        model.name,
+                        field.name,
+                        relation,
+                        target.name,
+                        several
+                            .iter()
+                            .map(|f| f.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )));
+                }
+            }
+        }
         Ok(())
     }
 
+    pub fn validate_model(&self, model: &Model) -> Result<(), PetrolError> {
+        model.validate()?;
+        if let Some(namespace) = model.schema_name() {
+            if self.datasource.schemas.is_empty() {
+                return Err(PetrolError::validation(format!(
+                    "model {} uses @@schema but the datasource does not declare `schemas`",
+                    model.name
+                )));
+            }
+            if !self.datasource.schemas.contains(&namespace) {
+                return Err(PetrolError::validation(format!(
+                    "model {} uses schema \"{}\" which is not listed in the datasource `schemas`",
+                    model.name, namespace
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Models without `@@schema` live in the first schema listed on the datasource.
+    pub fn namespace_for(&self, model: &Model) -> Option<String> {
+        model
+            .schema_name()
+            .or_else(|| self.datasource.schemas.first().cloned())
+    }
+
     pub fn find_model(&self, name: &str) -> Option<&Model> {
         self.models.iter().find(|m| m.name == name)
     }
@@ -36,10 +253,46 @@ impl Schema {
     }
 }
 
+/// An implicit many-to-many relation, stored in a join table with the id of `model_a`
+/// in column `A` and the id of `model_b` in column `B`. `(model_a, field_a)` sorts first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManyToMany {
+    pub name: Option<String>,
+    pub model_a: String,
+    pub field_a: String,
+    pub model_b: String,
+    pub field_b: String,
+}
+
+impl ManyToMany {
+    pub fn table_name(&self) -> String {
+        match &self.name {
+            Some(name) => format!("_{}", name),
+            None => format!("_{}To{}", self.model_a, self.model_b),
+        }
+    }
+
+    /// The relation as seen from `field` on `model`: its own join column, the other
+    /// side's join column and the other model. Fields keep self-relations apart.
+    pub fn side(&self, model: &str, field: &str) -> Option<(&'static str, &'static str, &str)> {
+        if model == self.model_a && field == self.field_a {
+            Some(("A", "B", &self.model_b))
+        } else if model == self.model_b && field == self.field_b {
+            Some(("B", "A", &self.model_a))
+        } else {
+            None
+        }
+    }
+}
+
 impl Display for Schema {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "datasource {} {{", self.datasource.name)?;
-        writeln!(f, "  provider = \"{}\"", self.datasource.provider)?;
+        writeln!(
+            f,
+            "  provider = {}",
+            quote_string(&self.datasource.provider)
+        )?;
         if let Some(url) = &self.datasource.raw_url {
             writeln!(f, "  url      = {}", url)?;
         } else {
@@ -51,18 +304,39 @@ impl Display for Schema {
         if let Some(timeout) = self.datasource.pool_timeout_seconds {
             writeln!(f, "  poolTimeout     = {}", timeout)?;
         }
+        if !self.datasource.schemas.is_empty() {
+            let schemas: Vec<String> = self
+                .datasource
+                .schemas
+                .iter()
+                .map(|name| quote_string(name))
+                .collect();
+            writeln!(f, "  schemas  = [{}]", schemas.join(", "))?;
+        }
+        if !self.datasource.extensions.is_empty() {
+            writeln!(
+                f,
+                "  extensions = [{}]",
+                self.datasource.extensions.join(", ")
+            )?;
+        }
         writeln!(f, "}}\n")?;
 
         writeln!(f, "generator {} {{", self.generator.name)?;
-        writeln!(f, "  provider = \"{}\"", self.generator.provider)?;
+        writeln!(f, "  provider = {}", quote_string(&self.generator.provider))?;
         if let Some(output) = &self.generator.output {
-            writeln!(f, "  output   = \"{}\"", output)?;
+            writeln!(f, "  output   = {}", quote_string(output))?;
+        }
+        if self.generator.updated_at != UpdatedAtStrategy::default() {
+            writeln!(f, "  updatedAt = \"{}\"", self.generator.updated_at)?;
         }
         writeln!(f, "}}\n")?;
 
         for model in &self.models {
+            write_doc_comment(f, "", model.documentation.as_deref())?;
             writeln!(f, "model {} {{", model.name)?;
             for field in &model.fields {
+                write_doc_comment(f, "  ", field.documentation.as_deref())?;
                 writeln!(f, "  {}", field)?;
             }
             for attr in &model.attributes {
@@ -75,6 +349,23 @@ impl Display for Schema {
     }
 }
 
+fn write_doc_comment(
+    f: &mut fmt::Formatter<'_>,
+    indent: &str,
+    documentation: Option<&str>,
+) -> fmt::Result {
+    if let Some(documentation) = documentation {
+        for line in documentation.lines() {
+            if line.is_empty() {
+                writeln!(f, "{}///", indent)?;
+            } else {
+                writeln!(f, "{}/// {}", indent, line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatasourceBlock {
     pub name: String,
@@ -87,6 +378,10 @@ pub struct DatasourceBlock {
     pub connection_limit: Option<u32>,
     #[serde(default)]
     pub pool_timeout_seconds: Option<u32>,
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
 }
 
 impl DatasourceBlock {
@@ -98,6 +393,8 @@ impl DatasourceBlock {
             raw_url: None,
             connection_limit: None,
             pool_timeout_seconds: None,
+            schemas: Vec::new(),
+            extensions: Vec::new(),
         }
     }
 }
@@ -108,6 +405,8 @@ pub struct GeneratorBlock {
     pub provider: String,
     #[serde(default)]
     pub output: Option<String>,
+    #[serde(default)]
+    pub updated_at: UpdatedAtStrategy,
 }
 
 impl GeneratorBlock {
@@ -116,6 +415,25 @@ impl GeneratorBlock {
             name: "client".into(),
             provider: provider.into(),
             output: None,
+            updated_at: UpdatedAtStrategy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UpdatedAtStrategy {
+    /// A `BEFORE UPDATE` trigger installed by push keeps the column current.
+    #[default]
+    Database,
+    /// Generated code sets the column before writing.
+    Client,
+}
+
+impl Display for UpdatedAtStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdatedAtStrategy::Database => write!(f, "database"),
+            UpdatedAtStrategy::Client => write!(f, "client"),
         }
     }
 }
@@ -126,6 +444,8 @@ pub struct Model {
     pub fields: Vec<Field>,
     #[serde(default)]
     pub attributes: Vec<ModelAttribute>,
+    #[serde(default)]
+    pub documentation: Option<String>,
 }
 
 impl Model {
@@ -136,17 +456,139 @@ impl Model {
                 self.name
             )));
         }
-        if !self
-            .fields
+        self.validate_id()?;
+        for field in &self.fields {
+            field.validate(&self.name)?;
+        }
+        for attr in &self.attributes {
+            let (kind, fields) = match attr {
+                ModelAttribute::Check { expr, .. } => {
+                    if expr.trim().is_empty() {
+                        return Err(PetrolError::validation(format!(
+                            "model {}: @@check needs an expression",
+                            self.name
+                        )));
+                    }
+                    continue;
+                }
+                ModelAttribute::Id(fields) => ("@@id", fields),
+                ModelAttribute::Unique(fields) => ("@@unique", fields),
+                ModelAttribute::Index(fields) => ("@@index", fields),
+                _ => continue,
+            };
+            let repeated = fields
+                .iter()
+                .enumerate()
+                .find(|(index, name)| fields[..*index].contains(name));
+            if let Some((_, name)) = repeated {
+                return Err(PetrolError::validation(format!(
+                    "model {}: {} lists field `{}` more than once",
+                    self.name, kind, name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_id(&self) -> Result<(), PetrolError> {
+        let id_fields: Vec<&Field> = self.fields.iter().filter(|f| f.is_id()).collect();
+        let compound: Vec<&Vec<String>> = self
+            .attributes
             .iter()
-            .any(|f| f.attributes.iter().any(|a| matches!(a, FieldAttribute::Id)))
-        {
-            return Err(PetrolError::validation(format!(
-                "model {} must declare an @id field",
+            .filter_map(|attr| match attr {
+                ModelAttribute::Id(fields) => Some(fields),
+                _ => None,
+            })
+            .collect();
+
+        match (id_fields.as_slice(), compound.as_slice()) {
+            ([], []) => Err(PetrolError::validation(format!(
+                "model {} must declare an @id field or @@id",
                 self.name
-            )));
+            ))),
+            ([_], []) => Ok(()),
+            (_, []) => Err(PetrolError::validation(format!(
+                "model {} marks several fields with @id, use @@id([{}]) for a composite key",
+                self.name,
+                id_fields
+                    .iter()
+                    .map(|f| f.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+            ([], [fields]) => {
+                if fields.is_empty() {
+                    return Err(PetrolError::validation(format!(
+                        "model {}: @@id needs at least one field",
+                        self.name
+                    )));
+                }
+                for name in fields.iter() {
+                    let field = self
+                        .fields
+                        .iter()
+                        .find(|f| &f.name == name)
+                        .ok_or_else(|| {
+                            PetrolError::validation(format!(
+                                "model {}: @@id refers to unknown field `{}`",
+                                self.name, name
+                            ))
+                        })?;
+                    let FieldType::Scalar(_, modifiers) = &field.r#type else {
+                        return Err(PetrolError::validation(format!(
+                            "model {}: @@id field `{}` must be a scalar",
+                            self.name, name
+                        )));
+                    };
+                    if modifiers.optional || modifiers.list {
+                        return Err(PetrolError::validation(format!(
+                            "model {}: @@id field `{}` cannot be optional or a list",
+                            self.name, name
+                        )));
+                    }
+                }
+                Ok(())
+            }
+            ([field, ..], _) => Err(PetrolError::validation(format!(
+                "model {} declares @@id, remove @id from field {}",
+                self.name, field.name
+            ))),
+            ([], _) => Err(PetrolError::validation(format!(
+                "model {} declares @@id more than once",
+                self.name
+            ))),
         }
-        Ok(())
+    }
+
+    /// Names of the fields forming the primary key, from `@@id` or the `@id` field.
+    pub fn id_fields(&self) -> Vec<String> {
+        self.attributes
+            .iter()
+            .find_map(|attr| match attr {
+                ModelAttribute::Id(fields) => Some(fields.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| {
+                self.fields
+                    .iter()
+                    .filter(|field| field.is_id())
+                    .map(|field| field.name.clone())
+                    .collect()
+            })
+    }
+
+    /// Column names for a list of field names, leaving unknown names as they are.
+    pub fn column_names(&self, fields: &[String]) -> Vec<String> {
+        fields
+            .iter()
+            .map(|name| {
+                self.fields
+                    .iter()
+                    .find(|field| &field.name == name)
+                    .map(Field::column_name)
+                    .unwrap_or_else(|| name.clone())
+            })
+            .collect()
     }
 
     pub fn table_name(&self) -> String {
@@ -157,6 +599,20 @@ impl Model {
         }
         self.name.clone()
     }
+
+    pub fn schema_name(&self) -> Option<String> {
+        self.attributes.iter().find_map(|attr| match attr {
+            ModelAttribute::Schema(name) => Some(name.clone()),
+            _ => None,
+        })
+    }
+
+    pub fn renamed_from(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attr| match attr {
+            ModelAttribute::RenamedFrom(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -165,6 +621,8 @@ pub struct Field {
     pub r#type: FieldType,
     #[serde(default)]
     pub attributes: Vec<FieldAttribute>,
+    #[serde(default)]
+    pub documentation: Option<String>,
 }
 
 impl Field {
@@ -176,6 +634,205 @@ impl Field {
         }
         self.name.clone()
     }
+
+    /// The target model of a list relation that has no `@relation(fields: ...)`.
+    pub fn implicit_list_target(&self) -> Option<&str> {
+        let FieldType::Relation(info) = &self.r#type else {
+            return None;
+        };
+        (info.modifiers.list && !self.owns_foreign_key()).then_some(info.model.as_str())
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        matches!(self.r#type, FieldType::Scalar(..))
+    }
+
+    pub fn relation_target(&self) -> Option<&str> {
+        match &self.r#type {
+            FieldType::Relation(info) => Some(&info.model),
+            FieldType::Scalar(..) => None,
+        }
+    }
+
+    pub fn relation(&self) -> Option<&RelationAttribute> {
+        self.attributes.iter().find_map(|attr| match attr {
+            FieldAttribute::Relation(relation) => Some(relation),
+            _ => None,
+        })
+    }
+
+    pub fn relation_name(&self) -> Option<&str> {
+        self.relation()?.name.as_deref()
+    }
+
+    /// Whether this side holds the foreign key, i.e. has `@relation(fields: ...)`.
+    pub fn owns_foreign_key(&self) -> bool {
+        self.relation()
+            .is_some_and(|relation| !relation.fields.is_empty())
+    }
+
+    pub fn is_id(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attr| matches!(attr, FieldAttribute::Id))
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attr| matches!(attr, FieldAttribute::Unique))
+    }
+
+    pub fn is_updated_at(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attr| matches!(attr, FieldAttribute::UpdatedAt))
+    }
+
+    pub fn default_value(&self) -> Option<&DefaultValue> {
+        self.attributes.iter().find_map(|attr| match attr {
+            FieldAttribute::Default(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn generated_expression(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attr| match attr {
+            FieldAttribute::Generated(expr) => Some(expr.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn renamed_from(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attr| match attr {
+            FieldAttribute::RenamedFrom(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn native_type(&self) -> Option<&NativeType> {
+        self.attributes.iter().find_map(|attr| match attr {
+            FieldAttribute::NativeType(native) => Some(native),
+            _ => None,
+        })
+    }
+
+    pub fn validate(&self, model: &str) -> Result<(), PetrolError> {
+        if let (Some(native), FieldType::Scalar(scalar, _)) = (self.native_type(), &self.r#type) {
+            if !native.supports(scalar) {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: @db.{} is not compatible with {}",
+                    model,
+                    self.name,
+                    native.name(),
+                    scalar
+                )));
+            }
+        }
+        if let FieldType::Scalar(_, modifiers) = &self.r#type {
+            if modifiers.list && modifiers.optional {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: scalar lists cannot be optional",
+                    model, self.name
+                )));
+            }
+            if modifiers.list
+                && self.attributes.iter().any(|attr| {
+                    matches!(
+                        attr,
+                        FieldAttribute::Id | FieldAttribute::Default(DefaultValue::AutoIncrement)
+                    )
+                })
+            {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: scalar lists cannot be @id or autoincrement",
+                    model, self.name
+                )));
+            }
+        }
+        if let (Some(default), FieldType::Scalar(scalar, _)) = (self.default_value(), &self.r#type)
+        {
+            let supported = match default {
+                DefaultValue::Uuid | DefaultValue::UuidV7 => {
+                    matches!(scalar, ScalarType::Uuid | ScalarType::String)
+                }
+                DefaultValue::Cuid | DefaultValue::NanoId(_) => *scalar == ScalarType::String,
+                _ => true,
+            };
+            if !supported {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: @default({}) is not compatible with {}",
+                    model, self.name, default, scalar
+                )));
+            }
+        }
+        if self.is_updated_at()
+            && !matches!(self.r#type, FieldType::Scalar(ScalarType::DateTime, _))
+        {
+            return Err(PetrolError::validation(format!(
+                "field {}.{}: @updatedAt is only allowed on DateTime fields",
+                model, self.name
+            )));
+        }
+        if let (Some(_), FieldType::Relation(_)) = (self.native_type(), &self.r#type) {
+            return Err(PetrolError::validation(format!(
+                "field {}.{}: native type attributes are only allowed on scalar fields",
+                model, self.name
+            )));
+        }
+        if let (Some(_), FieldType::Relation(_)) = (self.renamed_from(), &self.r#type) {
+            return Err(PetrolError::validation(format!(
+                "field {}.{}: @renamedFrom is only allowed on scalar fields",
+                model, self.name
+            )));
+        }
+        if let Some(expr) = self.generated_expression() {
+            let conflicting = self.attributes.iter().find_map(|attr| match attr {
+                FieldAttribute::Id => Some("@id"),
+                FieldAttribute::Default(_) => Some("@default"),
+                FieldAttribute::UpdatedAt => Some("@updatedAt"),
+                _ => None,
+            });
+            if let Some(conflicting) = conflicting {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: @generated columns cannot also use {}",
+                    model, self.name, conflicting
+                )));
+            }
+            if !self.is_scalar() || expr.trim().is_empty() {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: @generated needs a SQL expression on a scalar field",
+                    model, self.name
+                )));
+            }
+        }
+        if let Some(DefaultValue::DbGenerated(expr)) = self.default_value() {
+            if expr.trim().is_empty() {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: dbgenerated() needs a SQL expression",
+                    model, self.name
+                )));
+            }
+        }
+        for attr in &self.attributes {
+            let FieldAttribute::Check(expr) = attr else {
+                continue;
+            };
+            if !self.is_scalar() {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: @check is only allowed on scalar fields",
+                    model, self.name
+                )));
+            }
+            if expr.trim().is_empty() {
+                return Err(PetrolError::validation(format!(
+                    "field {}.{}: @check needs an expression",
+                    model, self.name
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Display for Field {
@@ -228,7 +885,7 @@ impl Display for FieldType {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TypeModifiers {
     pub optional: bool,
     pub list: bool,
@@ -252,15 +909,6 @@ impl TypeModifiers {
     }
 }
 
-impl Default for TypeModifiers {
-    fn default() -> Self {
-        Self {
-            optional: false,
-            list: false,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelationInfo {
     pub model: String,
@@ -310,6 +958,12 @@ pub enum FieldAttribute {
     Map(String),
     Relation(RelationAttribute),
     Default(DefaultValue),
+    NativeType(NativeType),
+    Check(String),
+    /// A `GENERATED ALWAYS AS (...) STORED` column computed by the database.
+    Generated(String),
+    /// The column's previous name, so migrations rename it instead of dropping it.
+    RenamedFrom(String),
 }
 
 impl Display for FieldAttribute {
@@ -318,33 +972,153 @@ impl Display for FieldAttribute {
             FieldAttribute::Id => write!(f, "@id"),
             FieldAttribute::Unique => write!(f, "@unique"),
             FieldAttribute::UpdatedAt => write!(f, "@updatedAt"),
-            FieldAttribute::Map(name) => write!(f, "@map(\"{}\")", name),
-            FieldAttribute::Relation(attr) => write!(
-                f,
-                "@relation(fields: [{}], references: [{}])",
-                attr.fields.join(", "),
-                attr.references.join(", ")
-            ),
+            FieldAttribute::Map(name) => write!(f, "@map({})", quote_string(name)),
+            FieldAttribute::Relation(attr) => {
+                let mut arguments = Vec::new();
+                if let Some(name) = &attr.name {
+                    arguments.push(quote_string(name));
+                }
+                if !attr.fields.is_empty() {
+                    arguments.push(format!("fields: [{}]", attr.fields.join(", ")));
+                    arguments.push(format!("references: [{}]", attr.references.join(", ")));
+                }
+                write!(f, "@relation({})", arguments.join(", "))
+            }
             FieldAttribute::Default(value) => write!(f, "@default({})", value),
+            FieldAttribute::NativeType(native) => write!(f, "@db.{}", native),
+            FieldAttribute::Check(expr) => write!(f, "@check({})", quote_string(expr)),
+            FieldAttribute::Generated(expr) => {
+                write!(f, "@generated({}, stored)", quote_string(expr))
+            }
+            FieldAttribute::RenamedFrom(name) => write!(f, "@renamedFrom({})", quote_string(name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NativeType {
+    Text,
+    VarChar(Option<u32>),
+    Char(Option<u32>),
+    Citext,
+    SmallInt,
+    Integer,
+    BigInt,
+    Real,
+    DoublePrecision,
+    Decimal(Option<(u32, u32)>),
+    Boolean,
+    Timestamp(Option<u32>),
+    Timestamptz(Option<u32>),
+    Time(Option<u32>),
+    Date,
+    Uuid,
+    Json,
+    JsonB,
+    ByteA,
+    Binary(Option<u32>),
+}
+
+impl NativeType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NativeType::Text => "Text",
+            NativeType::VarChar(_) => "VarChar",
+            NativeType::Char(_) => "Char",
+            NativeType::Citext => "Citext",
+            NativeType::SmallInt => "SmallInt",
+            NativeType::Integer => "Integer",
+            NativeType::BigInt => "BigInt",
+            NativeType::Real => "Real",
+            NativeType::DoublePrecision => "DoublePrecision",
+            NativeType::Decimal(_) => "Decimal",
+            NativeType::Boolean => "Boolean",
+            NativeType::Timestamp(_) => "Timestamp",
+            NativeType::Timestamptz(_) => "Timestamptz",
+            NativeType::Time(_) => "Time",
+            NativeType::Date => "Date",
+            NativeType::Uuid => "Uuid",
+            NativeType::Json => "Json",
+            NativeType::JsonB => "JsonB",
+            NativeType::ByteA => "ByteA",
+            NativeType::Binary(_) => "Binary",
+        }
+    }
+
+    pub fn supports(&self, scalar: &ScalarType) -> bool {
+        match self {
+            NativeType::Text
+            | NativeType::VarChar(_)
+            | NativeType::Char(_)
+            | NativeType::Citext => *scalar == ScalarType::String,
+            NativeType::SmallInt | NativeType::Integer => *scalar == ScalarType::Int,
+            NativeType::BigInt => *scalar == ScalarType::BigInt,
+            NativeType::Real | NativeType::DoublePrecision => *scalar == ScalarType::Float,
+            NativeType::Decimal(_) => *scalar == ScalarType::Decimal,
+            NativeType::Boolean => *scalar == ScalarType::Boolean,
+            NativeType::Timestamp(_) | NativeType::Timestamptz(_) | NativeType::Time(_) => {
+                *scalar == ScalarType::DateTime
+            }
+            NativeType::Date => matches!(scalar, ScalarType::Date | ScalarType::DateTime),
+            // The client reads UUID columns as `uuid::Uuid`, which a `String` field cannot hold.
+            NativeType::Uuid => *scalar == ScalarType::Uuid,
+            NativeType::Json | NativeType::JsonB => *scalar == ScalarType::Json,
+            NativeType::ByteA => *scalar == ScalarType::Bytes,
+            NativeType::Binary(_) => matches!(scalar, ScalarType::Bytes | ScalarType::Uuid),
+        }
+    }
+}
+
+impl Display for NativeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        match self {
+            NativeType::VarChar(Some(len))
+            | NativeType::Char(Some(len))
+            | NativeType::Timestamp(Some(len))
+            | NativeType::Timestamptz(Some(len))
+            | NativeType::Time(Some(len))
+            | NativeType::Binary(Some(len)) => write!(f, "({})", len),
+            NativeType::Decimal(Some((precision, scale))) => {
+                write!(f, "({}, {})", precision, scale)
+            }
+            _ => Ok(()),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelationAttribute {
+    #[serde(default)]
+    pub name: Option<String>,
     pub fields: Vec<String>,
     pub references: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum DefaultValue {
     AutoIncrement,
     Uuid,
+    UuidV7,
+    Cuid,
+    NanoId(Option<u32>),
     Now,
     Boolean(bool),
     Int(i64),
     Float(f64),
     String(String),
+    /// A SQL expression used verbatim as the column default.
+    DbGenerated(String),
+}
+
+impl DefaultValue {
+    /// Defaults the generated client fills in because Postgres has no built-in generator.
+    pub fn is_client_generated(&self) -> bool {
+        matches!(
+            self,
+            DefaultValue::UuidV7 | DefaultValue::Cuid | DefaultValue::NanoId(_)
+        )
+    }
 }
 
 impl Display for DefaultValue {
@@ -352,28 +1126,76 @@ impl Display for DefaultValue {
         match self {
             DefaultValue::AutoIncrement => write!(f, "autoincrement()"),
             DefaultValue::Uuid => write!(f, "uuid()"),
+            DefaultValue::UuidV7 => write!(f, "uuid(7)"),
+            DefaultValue::Cuid => write!(f, "cuid()"),
+            DefaultValue::NanoId(None) => write!(f, "nanoid()"),
+            DefaultValue::NanoId(Some(size)) => write!(f, "nanoid({})", size),
             DefaultValue::Now => write!(f, "now()"),
             DefaultValue::Boolean(value) => write!(f, "{}", value),
             DefaultValue::Int(value) => write!(f, "{}", value),
             DefaultValue::Float(value) => write!(f, "{}", value),
-            DefaultValue::String(value) => write!(f, "\"{}\"", value),
+            DefaultValue::String(value) => write!(f, "{}", quote_string(value)),
+            DefaultValue::DbGenerated(expr) => write!(f, "dbgenerated({})", quote_string(expr)),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ModelAttribute {
+    Id(Vec<String>),
     Map(String),
     Unique(Vec<String>),
     Index(Vec<String>),
+    Schema(String),
+    Check {
+        name: Option<String>,
+        expr: String,
+    },
+    /// The table's previous name, so migrations rename it instead of dropping it.
+    RenamedFrom(String),
 }
 
 impl Display for ModelAttribute {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ModelAttribute::Map(name) => write!(f, "@@map(\"{}\")", name),
+            ModelAttribute::Id(fields) => write!(f, "@@id([{}])", fields.join(", ")),
+            ModelAttribute::Map(name) => write!(f, "@@map({})", quote_string(name)),
             ModelAttribute::Unique(fields) => write!(f, "@@unique([{}])", fields.join(", ")),
             ModelAttribute::Index(fields) => write!(f, "@@index([{}])", fields.join(", ")),
+            ModelAttribute::Schema(name) => write!(f, "@@schema({})", quote_string(name)),
+            ModelAttribute::Check {
+                name: Some(name),
+                expr,
+            } => write!(
+                f,
+                "@@check(name: {}, expr: {})",
+                quote_string(name),
+                quote_string(expr)
+            ),
+            ModelAttribute::Check { name: None, expr } => {
+                write!(f, "@@check({})", quote_string(expr))
+            }
+            ModelAttribute::RenamedFrom(name) => {
+                write!(f, "@@renamedFrom({})", quote_string(name))
+            }
+        }
+    }
+}
+
+/// A schema string literal for `value`, escaping quotes, backslashes and control characters.
+pub fn quote_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(ch),
         }
     }
+    out.push('"');
+    out
 }
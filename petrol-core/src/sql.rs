@@ -1,56 +1,98 @@
+use crate::dialect::dialect_for;
+use crate::error::PetrolError;
 use crate::schema::{
-    DefaultValue, Field, FieldAttribute, FieldType, Model, ModelAttribute, ScalarType, Schema,
+    DefaultValue, Field, FieldAttribute, FieldType, ManyToMany, Model, ModelAttribute, NativeType,
+    ScalarType, Schema, UpdatedAtStrategy,
 };
 
+/// A table as Petrol models it, independent of any database. A [`Dialect`] renders it.
+///
+/// [`Dialect`]: crate::dialect::Dialect
+
 #[derive(Debug, Clone)]
 pub struct SqlTable {
+    pub schema: Option<String>,
     pub name: String,
     pub columns: Vec<SqlColumn>,
     pub primary_key: Vec<String>,
     pub uniques: Vec<Vec<String>>,
+    pub indexes: Vec<Vec<String>>,
+    pub foreign_keys: Vec<SqlForeignKey>,
+    pub checks: Vec<SqlCheck>,
+    /// `@updatedAt` columns the database keeps current, none with `updatedAt = "client"`.
+    pub updated_at: Vec<String>,
+    pub comment: Option<String>,
+    /// Previous table name from `@@renamedFrom`.
+    pub renamed_from: Option<String>,
+    /// `(previous, current)` column names from `@renamedFrom`.
+    pub renamed_columns: Vec<(String, String)>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlCheck {
+    pub name: String,
+    pub expr: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlForeignKey {
+    pub columns: Vec<String>,
+    pub references_schema: Option<String>,
+    pub references_table: String,
+    pub references_columns: Vec<String>,
+    pub on_delete: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct SqlColumn {
     pub name: String,
     pub sql_type: SqlType,
     pub nullable: bool,
-    pub default: Option<String>,
+    /// Only defaults the database computes; see `column_default`.
+    pub default: Option<DefaultValue>,
+    /// Expression of a `GENERATED ALWAYS AS (...) STORED` column.
+    pub generated: Option<String>,
+    pub comment: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+/// Column types by meaning rather than spelling. The serial variants are integers the
+/// database assigns on insert, however the dialect expresses that.
+#[derive(Debug, Clone, PartialEq)]
 pub enum SqlType {
+    SmallSerial,
     Serial,
     BigSerial,
+    SmallInt,
     Integer,
     BigInt,
+    Real,
     Float,
-    Decimal,
+    Decimal(Option<(u32, u32)>),
     Text,
+    VarChar(Option<u32>),
+    Char(Option<u32>),
+    Citext,
     Boolean,
-    Timestamp,
+    Timestamp(Option<u32>),
+    Timestamptz(Option<u32>),
+    Time(Option<u32>),
     Date,
     Uuid,
+    Json,
     Jsonb,
     Bytes,
+    Binary(Option<u32>),
+    Array(Box<SqlType>),
 }
 
 impl SqlType {
-    pub fn render(&self) -> &'static str {
+    /// The type of a column referencing this one: serials become their plain integer type.
+    pub fn without_serial(&self) -> SqlType {
         match self {
-            SqlType::Serial => "SERIAL",
-            SqlType::BigSerial => "BIGSERIAL",
-            SqlType::Integer => "INTEGER",
-            SqlType::BigInt => "BIGINT",
-            SqlType::Float => "DOUBLE PRECISION",
-            SqlType::Decimal => "DECIMAL",
-            SqlType::Text => "TEXT",
-            SqlType::Boolean => "BOOLEAN",
-            SqlType::Timestamp => "TIMESTAMPTZ",
-            SqlType::Date => "DATE",
-            SqlType::Uuid => "UUID",
-            SqlType::Jsonb => "JSONB",
-            SqlType::Bytes => "BYTEA",
+            SqlType::SmallSerial => SqlType::SmallInt,
+            SqlType::Serial => SqlType::Integer,
+            SqlType::BigSerial => SqlType::BigInt,
+            other => other.clone(),
         }
     }
 }
@@ -58,115 +100,243 @@ impl SqlType {
 impl SqlTable {
     pub fn from_model(model: &Model) -> Self {
         let mut columns = Vec::new();
-        let mut primary = Vec::new();
         let mut uniques = Vec::new();
+        let mut updated_at = Vec::new();
+        let mut checks = Vec::new();
+        let mut renamed_columns = Vec::new();
+        let table = model.table_name();
 
         for field in &model.fields {
             if let Some(column) = SqlColumn::from_field(field) {
                 columns.push(column);
             }
-
-            if field
-                .attributes
-                .iter()
-                .any(|attr| matches!(attr, FieldAttribute::Id))
-            {
-                primary.push(field.column_name());
+            if field.is_updated_at() {
+                updated_at.push(field.column_name());
             }
-            if field
-                .attributes
-                .iter()
-                .any(|attr| matches!(attr, FieldAttribute::Unique))
-            {
+            if field.is_unique() {
                 uniques.push(vec![field.column_name()]);
             }
+            if let Some(previous) = field.renamed_from() {
+                renamed_columns.push((previous.to_string(), field.column_name()));
+            }
+            for attr in &field.attributes {
+                if let FieldAttribute::Check(expr) = attr {
+                    checks.push(SqlCheck {
+                        name: format!("{}_{}_check", table, field.column_name()),
+                        expr: expr.clone(),
+                    });
+                }
+            }
         }
 
+        let mut indexes = Vec::new();
+        // Unnamed model checks follow Postgres' own naming: `_check`, `_check1`, ...
+        let mut unnamed_checks = 0;
         for attr in &model.attributes {
-            if let ModelAttribute::Unique(fields) = attr {
-                uniques.push(fields.clone());
+            match attr {
+                ModelAttribute::Unique(fields) => uniques.push(model.column_names(fields)),
+                ModelAttribute::Index(fields) => indexes.push(model.column_names(fields)),
+                ModelAttribute::Check { name, expr } => {
+                    let name = name.clone().unwrap_or_else(|| {
+                        let name = match unnamed_checks {
+                            0 => format!("{}_check", table),
+                            n => format!("{}_check{}", table, n),
+                        };
+                        unnamed_checks += 1;
+                        name
+                    });
+                    checks.push(SqlCheck {
+                        name,
+                        expr: expr.clone(),
+                    });
+                }
+                _ => {}
             }
         }
 
         Self {
-            name: model.table_name(),
+            schema: model.schema_name(),
+            name: table,
             columns,
-            primary_key: primary,
+            primary_key: model.column_names(&model.id_fields()),
             uniques,
+            indexes,
+            foreign_keys: Vec::new(),
+            checks,
+            updated_at,
+            comment: model.documentation.clone(),
+            renamed_from: model.renamed_from().map(str::to_string),
+            renamed_columns,
         }
     }
 
-    pub fn to_sql(&self) -> String {
-        let mut buffer = String::new();
-        buffer.push_str(&format!("CREATE TABLE IF NOT EXISTS \"{}\" (\n", self.name));
-
-        let mut column_fragments: Vec<String> =
-            self.columns.iter().map(|column| column.to_sql()).collect();
-
-        if !self.primary_key.is_empty() {
-            column_fragments.push(format!(
-                "PRIMARY KEY ({})",
-                self.primary_key
-                    .iter()
-                    .map(|name| format!("\"{}\"", name))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
-
-        for unique in &self.uniques {
-            column_fragments.push(format!(
-                "UNIQUE ({})",
-                unique
-                    .iter()
-                    .map(|name| format!("\"{}\"", name))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
+    /// The join table of an implicit many-to-many relation: one column per side holding
+    /// that model's id, a composite primary key, and an index on `B` for reverse lookups.
+    pub fn join_table(schema: &Schema, relation: &ManyToMany) -> Option<Self> {
+        let mut columns = Vec::new();
+        let mut foreign_keys = Vec::new();
+        for (column, model_name) in [("A", &relation.model_a), ("B", &relation.model_b)] {
+            let model = schema.find_model(model_name)?;
+            let id = model.fields.iter().find(|field| field.is_id())?;
+            let id_column = SqlColumn::from_field(id)?;
+            columns.push(SqlColumn {
+                name: column.to_string(),
+                sql_type: id_column.sql_type.without_serial(),
+                nullable: false,
+                default: None,
+                generated: None,
+                comment: None,
+            });
+            foreign_keys.push(SqlForeignKey {
+                columns: vec![column.to_string()],
+                references_schema: schema.namespace_for(model),
+                references_table: model.table_name(),
+                references_columns: vec![id_column.name],
+                on_delete: "CASCADE",
+            });
         }
 
-        buffer.push_str(&column_fragments.join(",\n"));
-        buffer.push_str("\n);\n");
-        buffer
+        let owner = schema.find_model(&relation.model_a)?;
+        Some(Self {
+            schema: schema.namespace_for(owner),
+            name: relation.table_name(),
+            columns,
+            primary_key: vec!["A".into(), "B".into()],
+            uniques: Vec::new(),
+            indexes: vec![vec!["B".into()]],
+            foreign_keys,
+            checks: Vec::new(),
+            updated_at: Vec::new(),
+            comment: None,
+            renamed_from: None,
+            renamed_columns: Vec::new(),
+        })
     }
 }
 
+pub const UPDATED_AT_FUNCTION: &str = "petrol_set_updated_at";
+
+pub fn updated_at_trigger_name(column: &str) -> String {
+    format!("petrol_updated_at_{}", column)
+}
+
 impl SqlColumn {
     pub fn from_field(field: &Field) -> Option<Self> {
         match &field.r#type {
             FieldType::Scalar(scalar, modifiers) => {
-                let sql_type = scalar_to_sql_type(scalar, field);
+                let sql_type = if modifiers.list {
+                    SqlType::Array(Box::new(scalar_to_sql_type(scalar, field)))
+                } else {
+                    scalar_to_sql_type(scalar, field)
+                };
                 let nullable = modifiers.optional;
-                let default = default_clause(field);
+                let default = column_default(field);
 
                 Some(Self {
                     name: field.column_name(),
                     sql_type,
                     nullable,
                     default,
+                    generated: field.generated_expression().map(str::to_string),
+                    comment: field.documentation.clone(),
                 })
             }
             FieldType::Relation(_) => None,
         }
     }
+}
 
-    fn to_sql(&self) -> String {
-        let mut fragment = format!("  \"{}\" {}", self.name, self.sql_type.render());
-        if !self.nullable {
-            fragment.push_str(" NOT NULL");
-        }
-        if let Some(default) = &self.default {
-            fragment.push_str(&format!(" DEFAULT {}", default));
-        }
-        fragment
+pub fn schema_to_tables(schema: &Schema) -> Vec<SqlTable> {
+    let mut tables: Vec<SqlTable> = schema
+        .models
+        .iter()
+        .map(|model| {
+            let mut table = SqlTable::from_model(model);
+            table.schema = schema.namespace_for(model);
+            table.foreign_keys = relation_foreign_keys(schema, model);
+            if schema.generator.updated_at == UpdatedAtStrategy::Client {
+                table.updated_at.clear();
+            }
+            table
+        })
+        .collect();
+    // Join tables come after the model tables they connect.
+    tables.extend(
+        schema
+            .many_to_many()
+            .iter()
+            .filter_map(|relation| SqlTable::join_table(schema, relation)),
+    );
+    tables
+}
+
+/// DDL for the whole schema in the dialect of the datasource `provider`.
+pub fn schema_to_sql(schema: &Schema) -> Result<Vec<String>, PetrolError> {
+    let dialect = dialect_for(&schema.datasource.provider)?;
+    dialect.check_schema(schema)?;
+    let tables = schema_to_tables(schema);
+    let mut statements = dialect.setup_sql(schema)?;
+    for table in &tables {
+        statements.push(dialect.create_table_sql(table)?);
+    }
+    for table in &tables {
+        statements.extend(dialect.foreign_keys_sql(table)?);
+    }
+    for table in &tables {
+        statements.extend(dialect.indexes_sql(table));
     }
+    for table in &tables {
+        statements.extend(dialect.comments_sql(table));
+    }
+
+    if tables.iter().any(|table| !table.updated_at.is_empty()) {
+        statements.extend(dialect.updated_at_sql(&tables)?);
+    }
+
+    statements.extend(dialect.finish_sql());
+    Ok(statements)
 }
 
-pub fn schema_to_tables(schema: &Schema) -> Vec<SqlTable> {
-    schema.models.iter().map(SqlTable::from_model).collect()
+/// Foreign keys for the relation fields of `model` that declare `@relation(fields: ...)`.
+/// Deleting a referenced row is refused while required references exist and clears
+/// optional ones.
+fn relation_foreign_keys(schema: &Schema, model: &Model) -> Vec<SqlForeignKey> {
+    model
+        .fields
+        .iter()
+        .filter_map(|field| {
+            let relation = field
+                .relation()
+                .filter(|relation| !relation.fields.is_empty())?;
+            let target = schema.find_model(field.relation_target()?)?;
+            Some(SqlForeignKey {
+                columns: model.column_names(&relation.fields),
+                references_schema: schema.namespace_for(target),
+                references_table: target.table_name(),
+                references_columns: target.column_names(&relation.references),
+                on_delete: if field.r#type.modifiers().optional {
+                    "SET NULL"
+                } else {
+                    "RESTRICT"
+                },
+            })
+        })
+        .collect()
+}
+
+/// Schema files spell extensions as identifiers, so `uuid_ossp` stands in for `uuid-ossp`.
+pub fn extension_sql_name(name: &str) -> &str {
+    match name {
+        "uuid_ossp" => "uuid-ossp",
+        other => other,
+    }
 }
 
 fn scalar_to_sql_type(scalar: &ScalarType, field: &Field) -> SqlType {
+    if let Some(native) = field.native_type() {
+        return native_to_sql_type(native, has_autoincrement(field));
+    }
+
     match scalar {
         ScalarType::Int => {
             if has_autoincrement(field) {
@@ -183,10 +353,10 @@ fn scalar_to_sql_type(scalar: &ScalarType, field: &Field) -> SqlType {
             }
         }
         ScalarType::Float => SqlType::Float,
-        ScalarType::Decimal => SqlType::Decimal,
+        ScalarType::Decimal => SqlType::Decimal(None),
         ScalarType::String => SqlType::Text,
         ScalarType::Boolean => SqlType::Boolean,
-        ScalarType::DateTime => SqlType::Timestamp,
+        ScalarType::DateTime => SqlType::Timestamptz(None),
         ScalarType::Date => SqlType::Date,
         ScalarType::Uuid => SqlType::Uuid,
         ScalarType::Json => SqlType::Jsonb,
@@ -194,6 +364,34 @@ fn scalar_to_sql_type(scalar: &ScalarType, field: &Field) -> SqlType {
     }
 }
 
+fn native_to_sql_type(native: &NativeType, autoincrement: bool) -> SqlType {
+    match native {
+        NativeType::Text => SqlType::Text,
+        NativeType::VarChar(len) => SqlType::VarChar(*len),
+        NativeType::Char(len) => SqlType::Char(*len),
+        NativeType::Citext => SqlType::Citext,
+        NativeType::SmallInt if autoincrement => SqlType::SmallSerial,
+        NativeType::SmallInt => SqlType::SmallInt,
+        NativeType::Integer if autoincrement => SqlType::Serial,
+        NativeType::Integer => SqlType::Integer,
+        NativeType::BigInt if autoincrement => SqlType::BigSerial,
+        NativeType::BigInt => SqlType::BigInt,
+        NativeType::Real => SqlType::Real,
+        NativeType::DoublePrecision => SqlType::Float,
+        NativeType::Decimal(precision) => SqlType::Decimal(*precision),
+        NativeType::Boolean => SqlType::Boolean,
+        NativeType::Timestamp(precision) => SqlType::Timestamp(*precision),
+        NativeType::Timestamptz(precision) => SqlType::Timestamptz(*precision),
+        NativeType::Time(precision) => SqlType::Time(*precision),
+        NativeType::Date => SqlType::Date,
+        NativeType::Uuid => SqlType::Uuid,
+        NativeType::Json => SqlType::Json,
+        NativeType::JsonB => SqlType::Jsonb,
+        NativeType::ByteA => SqlType::Bytes,
+        NativeType::Binary(len) => SqlType::Binary(*len),
+    }
+}
+
 fn has_autoincrement(field: &Field) -> bool {
     field
         .attributes
@@ -201,19 +399,14 @@ fn has_autoincrement(field: &Field) -> bool {
         .any(|attr| matches!(attr, FieldAttribute::Default(DefaultValue::AutoIncrement)))
 }
 
-fn default_clause(field: &Field) -> Option<String> {
-    for attr in &field.attributes {
-        if let FieldAttribute::Default(value) = attr {
-            return match value {
-                DefaultValue::AutoIncrement => None,
-                DefaultValue::Uuid => Some("uuid_generate_v4()".to_string()),
-                DefaultValue::Now => Some("now()".to_string()),
-                DefaultValue::Boolean(v) => Some(v.to_string()),
-                DefaultValue::Int(v) => Some(v.to_string()),
-                DefaultValue::Float(v) => Some(v.to_string()),
-                DefaultValue::String(v) => Some(format!("'{}'", v.replace("'", "''"))),
-            };
-        }
+/// The default the database fills in, if any. Autoincrement is part of the column type
+/// and client-generated ids are filled in by the generated client.
+fn column_default(field: &Field) -> Option<DefaultValue> {
+    match field.default_value() {
+        Some(DefaultValue::AutoIncrement) => None,
+        Some(default) if default.is_client_generated() => None,
+        Some(default) => Some(default.clone()),
+        None if field.is_updated_at() => Some(DefaultValue::Now),
+        None => None,
     }
-    None
 }
@@ -0,0 +1,263 @@
+use petrol_parser::cst::split_comment;
+
+/// A range on one line, its columns counted in UTF-16 code units as LSP positions are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Span {
+    pub fn contains(&self, line: u32, character: u32) -> bool {
+        self.line == line && self.start <= character && character <= self.end
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockSymbol {
+    pub keyword: String,
+    pub name: String,
+    pub name_span: Span,
+    pub start_line: u32,
+    pub end_line: u32,
+    pub fields: Vec<FieldSymbol>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldSymbol {
+    pub name: String,
+    pub name_span: Span,
+    pub type_name: String,
+    pub type_span: Span,
+}
+
+/// Positions of blocks and fields in a schema document, built from the raw text so it
+/// stays usable while the document does not parse.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaIndex {
+    pub blocks: Vec<BlockSymbol>,
+}
+
+impl SchemaIndex {
+    pub fn build(text: &str) -> Self {
+        let mut blocks = Vec::new();
+        let mut current: Option<BlockSymbol> = None;
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line_no = line_no as u32;
+            let (code, _) = split_comment(line);
+            let words = words(&code, line_no);
+
+            if code.trim() == "}" {
+                if let Some(mut block) = current.take() {
+                    block.end_line = line_no;
+                    blocks.push(block);
+                }
+                continue;
+            }
+
+            match current.as_mut() {
+                None => {
+                    if let [(keyword, _), (name, name_span), ..] = words.as_slice() {
+                        if code.trim_end().ends_with('{') {
+                            current = Some(BlockSymbol {
+                                keyword: keyword.clone(),
+                                name: name.clone(),
+                                name_span: *name_span,
+                                start_line: line_no,
+                                end_line: line_no,
+                                fields: Vec::new(),
+                            });
+                        }
+                    }
+                }
+                Some(block) if block.keyword == "model" => {
+                    if let [(name, name_span), (ty, type_span), ..] = words.as_slice() {
+                        if !name.starts_with('@') {
+                            block.fields.push(FieldSymbol {
+                                name: name.clone(),
+                                name_span: *name_span,
+                                type_name: ty.clone(),
+                                type_span: *type_span,
+                            });
+                        }
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some(mut block) = current {
+            block.end_line = text.lines().count() as u32;
+            blocks.push(block);
+        }
+
+        Self { blocks }
+    }
+
+    pub fn models(&self) -> impl Iterator<Item = &BlockSymbol> {
+        self.blocks.iter().filter(|block| block.keyword == "model")
+    }
+
+    pub fn model(&self, name: &str) -> Option<&BlockSymbol> {
+        self.models().find(|block| block.name == name)
+    }
+
+    pub fn block_at(&self, line: u32) -> Option<&BlockSymbol> {
+        self.blocks
+            .iter()
+            .find(|block| block.start_line <= line && line <= block.end_line)
+    }
+
+    pub fn field_at(&self, line: u32) -> Option<(&BlockSymbol, &FieldSymbol)> {
+        let block = self.block_at(line)?;
+        let field = block
+            .fields
+            .iter()
+            .find(|field| field.name_span.line == line)?;
+        Some((block, field))
+    }
+}
+
+/// Strips `?` and `[]` from a field type.
+pub fn base_type(type_name: &str) -> &str {
+    type_name.trim_end_matches("[]").trim_end_matches('?')
+}
+
+pub fn is_ident_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+/// Length of `text` in UTF-16 code units.
+pub fn utf16_len(text: &str) -> u32 {
+    text.encode_utf16().count() as u32
+}
+
+/// Byte offset of the UTF-16 column `character` in `line`, clamped to its end.
+pub fn byte_offset(line: &str, character: u32) -> usize {
+    let mut column = 0;
+    for (index, ch) in line.char_indices() {
+        if column >= character {
+            return index;
+        }
+        column += ch.len_utf16() as u32;
+    }
+    line.len()
+}
+
+/// The identifier under the cursor, together with its span.
+pub fn word_at(text: &str, line: u32, character: u32) -> Option<(String, Span)> {
+    let source = text.lines().nth(line as usize)?;
+    // Identifiers are ASCII, so scanning bytes never stops inside a character.
+    let bytes = source.as_bytes();
+    let cursor = byte_offset(source, character);
+
+    let mut start = cursor;
+    while start > 0 && is_ident_char(bytes[start - 1] as char) {
+        start -= 1;
+    }
+    let mut end = cursor;
+    while end < bytes.len() && is_ident_char(bytes[end] as char) {
+        end += 1;
+    }
+    if start == end {
+        return None;
+    }
+
+    let start_column = utf16_len(&source[..start]);
+    Some((
+        source[start..end].to_string(),
+        Span {
+            line,
+            start: start_column,
+            end: start_column + utf16_len(&source[start..end]),
+        },
+    ))
+}
+
+/// Spans of every identifier equal to `name` inside the `[...]` list that follows
+/// `keyword` on a line, or inside any list when `keyword` is `None`.
+pub fn list_references(source: &str, line: u32, keyword: Option<&str>, name: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut search_from = 0;
+
+    loop {
+        let open = match keyword {
+            Some(keyword) => source[search_from..].find(keyword).and_then(|at| {
+                source[search_from + at..]
+                    .find('[')
+                    .map(|b| search_from + at + b)
+            }),
+            None => source[search_from..].find('[').map(|at| search_from + at),
+        };
+        let Some(open) = open else { break };
+        let Some(close) = source[open..].find(']').map(|at| open + at) else {
+            break;
+        };
+
+        let offset = utf16_len(&source[..open]);
+        for (word, span) in identifiers(&source[open..close], line, offset) {
+            if word == name {
+                spans.push(span);
+            }
+        }
+        search_from = close;
+    }
+
+    spans
+}
+
+fn words(code: &str, line: u32) -> Vec<(String, Span)> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+    let mut column = 0;
+    let mut depth = 0;
+
+    for ch in code.chars() {
+        match ch {
+            '(' | '[' => depth += 1,
+            ')' | ']' if depth > 0 => depth -= 1,
+            _ => {}
+        }
+        if ch.is_whitespace() && depth == 0 {
+            if !current.is_empty() {
+                words.push((std::mem::take(&mut current), span(line, start, column)));
+            }
+        } else {
+            if current.is_empty() {
+                start = column;
+            }
+            current.push(ch);
+        }
+        column += ch.len_utf16() as u32;
+    }
+    if !current.is_empty() {
+        words.push((current, span(line, start, column)));
+    }
+    words
+}
+
+fn identifiers(source: &str, line: u32, offset: u32) -> Vec<(String, Span)> {
+    let mut identifiers = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+    let mut column = offset;
+    for ch in source.chars().chain(std::iter::once(' ')) {
+        if is_ident_char(ch) {
+            if current.is_empty() {
+                start = column;
+            }
+            current.push(ch);
+        } else if !current.is_empty() {
+            identifiers.push((std::mem::take(&mut current), span(line, start, column)));
+        }
+        column += ch.len_utf16() as u32;
+    }
+    identifiers
+}
+
+fn span(line: u32, start: u32, end: u32) -> Span {
+    Span { line, start, end }
+}
@@ -0,0 +1,633 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use petrol_codegen::rust_type_name;
+use petrol_core::dialect::{dialect_for, Dialect};
+use petrol_core::schema::Schema;
+use petrol_core::sql::{schema_to_tables, SqlColumn};
+use petrol_parser::{format_schema, parse_schema, schema_files, ParserError, SchemaLoader};
+use tokio::sync::RwLock;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+use tower_lsp::{Client, LanguageServer, LspService, Server};
+
+pub mod index;
+
+use index::{base_type, byte_offset, list_references, utf16_len, word_at, SchemaIndex, Span};
+
+const SCALAR_TYPES: &[&str] = &[
+    "Int", "BigInt", "Float", "Decimal", "String", "Boolean", "DateTime", "Date", "Uuid", "Json",
+    "Bytes",
+];
+
+const FIELD_ATTRIBUTES: &[&str] = &[
+    "@id",
+    "@unique",
+    "@default()",
+    "@relation()",
+    "@map()",
+    "@updatedAt",
+    "@check()",
+    "@generated()",
+    "@renamedFrom()",
+];
+
+const MODEL_ATTRIBUTES: &[&str] = &[
+    "@@id([])",
+    "@@map()",
+    "@@unique([])",
+    "@@index([])",
+    "@@schema()",
+    "@@check()",
+    "@@renamedFrom()",
+];
+
+const NATIVE_TYPES: &[&str] = &[
+    "Text",
+    "VarChar()",
+    "Char()",
+    "Citext",
+    "SmallInt",
+    "Integer",
+    "BigInt",
+    "Real",
+    "DoublePrecision",
+    "Decimal()",
+    "Boolean",
+    "Timestamp()",
+    "Timestamptz()",
+    "Time()",
+    "Date",
+    "Uuid",
+    "Json",
+    "JsonB",
+    "ByteA",
+    "Binary()",
+];
+
+const DATASOURCE_KEYS: &[&str] = &[
+    "provider",
+    "url",
+    "schemas",
+    "extensions",
+    "connectionLimit",
+    "poolTimeout",
+];
+
+const GENERATOR_KEYS: &[&str] = &["provider", "output", "updatedAt"];
+
+const BLOCK_KEYWORDS: &[&str] = &["model", "datasource", "generator", "import"];
+
+pub struct Backend {
+    client: Client,
+    documents: RwLock<HashMap<Url, String>>,
+}
+
+impl Backend {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            documents: RwLock::new(HashMap::new()),
+        }
+    }
+
+    async fn document(&self, uri: &Url) -> Option<String> {
+        self.documents.read().await.get(uri).cloned()
+    }
+
+    /// The document followed by the other files of its schema, open ones as edited.
+    async fn schema_documents(&self, uri: &Url) -> Vec<(Url, String)> {
+        let documents = self.documents.read().await;
+        let Some(text) = documents.get(uri) else {
+            return Vec::new();
+        };
+        let mut result = vec![(uri.clone(), text.clone())];
+        let Some(path) = file_path(uri) else {
+            return result;
+        };
+
+        let open: Vec<(PathBuf, &Url, &String)> = documents
+            .iter()
+            .filter_map(|(uri, text)| Some((file_path(uri)?, uri, text)))
+            .collect();
+        let loader = open
+            .iter()
+            .fold(SchemaLoader::new(), |loader, (path, _, text)| {
+                loader.with_source(path, text.as_str())
+            });
+        let target = schema_target(&path, text);
+        // Imports are unknown while a file does not parse, a directory's files are not.
+        let files = loader.files(target).or_else(|err| {
+            if target.is_dir() {
+                schema_files(target)
+            } else {
+                Err(err)
+            }
+        });
+
+        for file in files.unwrap_or_default() {
+            if same_file(&file, &path) {
+                continue;
+            }
+            match open.iter().find(|(open, _, _)| same_file(open, &file)) {
+                Some((_, uri, text)) => result.push(((*uri).clone(), (*text).clone())),
+                None => {
+                    if let (Ok(uri), Ok(text)) =
+                        (Url::from_file_path(&file), std::fs::read_to_string(&file))
+                    {
+                        result.push((uri, text));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    async fn on_change(&self, uri: Url, text: String, version: Option<i32>) {
+        let diagnostics = diagnostics(file_path(&uri).as_deref(), &text);
+        self.documents.write().await.insert(uri.clone(), text);
+        self.client
+            .publish_diagnostics(uri, diagnostics, version)
+            .await;
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+        Ok(InitializeResult {
+            server_info: Some(ServerInfo {
+                name: "petrol-lsp".into(),
+                version: Some(env!("CARGO_PKG_VERSION").into()),
+            }),
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::FULL,
+                )),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["@".into(), ".".into()]),
+                    ..CompletionOptions::default()
+                }),
+                definition_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                rename_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                ..ServerCapabilities::default()
+            },
+        })
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let doc = params.text_document;
+        self.on_change(doc.uri, doc.text, Some(doc.version)).await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        if let Some(change) = params.content_changes.into_iter().last() {
+            let doc = params.text_document;
+            self.on_change(doc.uri, change.text, Some(doc.version))
+                .await;
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.documents
+            .write()
+            .await
+            .remove(&params.text_document.uri);
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let position = params.text_document_position.position;
+        let Some(text) = self
+            .document(&params.text_document_position.text_document.uri)
+            .await
+        else {
+            return Ok(None);
+        };
+        Ok(Some(CompletionResponse::Array(completions(
+            &text, position,
+        ))))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let documents = self.schema_documents(&uri).await;
+        Ok(definition(&documents, position).map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let Some(text) = self.document(&uri).await else {
+            return Ok(None);
+        };
+        Ok(hover(file_path(&uri).as_deref(), &text, position))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let documents = self.schema_documents(&uri).await;
+
+        let changes = rename_edits(&documents, position, &params.new_name);
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        }))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let Some(text) = self.document(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+        let Ok(formatted) = format_schema(&text) else {
+            return Ok(None);
+        };
+        if formatted == text {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: Range::new(Position::new(0, 0), end_of(&text)),
+            new_text: formatted,
+        }]))
+    }
+}
+
+pub async fn run_stdio() {
+    let (service, socket) = LspService::new(Backend::new);
+    Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
+        .serve(service)
+        .await;
+}
+
+fn file_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok()
+}
+
+/// Parses the document as part of its schema: together with the files it imports, or with
+/// the rest of its schema directory when it does not declare the datasource itself.
+pub fn load_schema(path: Option<&Path>, text: &str) -> std::result::Result<Schema, ParserError> {
+    let Some(path) = path else {
+        return parse_schema(text);
+    };
+    SchemaLoader::new()
+        .with_source(path, text)
+        .load(schema_target(path, text))
+}
+
+/// What to load for the document at `path`: its schema directory, or the document itself
+/// when it declares the datasource.
+fn schema_target<'a>(path: &'a Path, text: &str) -> &'a Path {
+    let declares_datasource = SchemaIndex::build(text)
+        .blocks
+        .iter()
+        .any(|block| block.keyword == "datasource");
+    match schema_root(path) {
+        Some(dir) if !declares_datasource => dir,
+        _ => path,
+    }
+}
+
+/// The highest directory above `path` reachable through directories that hold schema files.
+fn schema_root(path: &Path) -> Option<&Path> {
+    let mut root = path.parent()?;
+    for dir in root.ancestors().skip(1) {
+        let holds_schema = std::fs::read_dir(dir).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.path().extension().is_some_and(|ext| ext == "petrol"))
+        });
+        if !holds_schema {
+            break;
+        }
+        root = dir;
+    }
+    Some(root)
+}
+
+pub fn diagnostics(path: Option<&Path>, text: &str) -> Vec<Diagnostic> {
+    let Err(err) = load_schema(path, text) else {
+        return Vec::new();
+    };
+
+    let elsewhere = match (path, err.path()) {
+        (Some(path), Some(origin)) => !same_file(path, origin),
+        _ => false,
+    };
+    let range = match err.line_col() {
+        _ if elsewhere => Range::default(),
+        Some((line, column)) => {
+            // Parse errors count columns in characters.
+            let source = text.lines().nth(line - 1).unwrap_or_default();
+            let prefix: String = source.chars().take(column - 1).collect();
+            let position = Position::new(line as u32 - 1, utf16_len(&prefix));
+            Range::new(position, position)
+        }
+        None => locate_message(&SchemaIndex::build(text), &err),
+    };
+
+    vec![Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("petrol".into()),
+        message: err.to_string(),
+        ..Diagnostic::default()
+    }]
+}
+
+/// Validation errors name their subject as `model X` or `field X.y`; point at it when
+/// the index can find it.
+fn locate_message(index: &SchemaIndex, err: &ParserError) -> Range {
+    let message = err.to_string();
+    let subject = |prefix: &str| {
+        message.split(prefix).nth(1).map(|rest| {
+            rest.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches('.')
+                .to_string()
+        })
+    };
+
+    if let Some((model, field)) = subject("field ").as_deref().and_then(|s| s.split_once('.')) {
+        if let Some(field) = index
+            .model(model)
+            .and_then(|block| block.fields.iter().find(|f| f.name == field))
+        {
+            return range(field.name_span);
+        }
+    }
+    if let Some(block) = subject("model ").and_then(|model| index.model(&model)) {
+        return range(block.name_span);
+    }
+    Range::default()
+}
+
+pub fn completions(text: &str, position: Position) -> Vec<CompletionItem> {
+    let index = SchemaIndex::build(text);
+    let line = text.lines().nth(position.line as usize).unwrap_or_default();
+    let before = &line[..byte_offset(line, position.character)];
+    let token = before
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or_default();
+
+    let words_before = before.split_whitespace().count();
+    let at_type = if before.ends_with(char::is_whitespace) {
+        words_before == 1
+    } else {
+        words_before == 2
+    };
+
+    let block = index.block_at(position.line);
+    let items: Vec<(String, CompletionItemKind)> = match block {
+        None => keywords(BLOCK_KEYWORDS),
+        Some(block) if block.keyword == "datasource" => keywords(DATASOURCE_KEYS),
+        Some(block) if block.keyword == "generator" => keywords(GENERATOR_KEYS),
+        Some(_) if token.starts_with("@db.") => NATIVE_TYPES
+            .iter()
+            .map(|name| (name.to_string(), CompletionItemKind::TYPE_PARAMETER))
+            .collect(),
+        Some(_) if token.starts_with("@@") => MODEL_ATTRIBUTES
+            .iter()
+            .map(|name| {
+                (
+                    name.trim_start_matches("@@").to_string(),
+                    CompletionItemKind::PROPERTY,
+                )
+            })
+            .collect(),
+        Some(_) if token.starts_with('@') => FIELD_ATTRIBUTES
+            .iter()
+            .map(|name| {
+                (
+                    name.trim_start_matches('@').to_string(),
+                    CompletionItemKind::PROPERTY,
+                )
+            })
+            .chain(std::iter::once((
+                "db.".to_string(),
+                CompletionItemKind::MODULE,
+            )))
+            .collect(),
+        Some(_) if at_type => SCALAR_TYPES
+            .iter()
+            .map(|name| (name.to_string(), CompletionItemKind::TYPE_PARAMETER))
+            .chain(
+                index
+                    .models()
+                    .map(|model| (model.name.clone(), CompletionItemKind::CLASS)),
+            )
+            .collect(),
+        Some(_) => Vec::new(),
+    };
+
+    items
+        .into_iter()
+        .map(|(label, kind)| CompletionItem {
+            label,
+            kind: Some(kind),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+fn keywords(names: &[&str]) -> Vec<(String, CompletionItemKind)> {
+    names
+        .iter()
+        .map(|name| (name.to_string(), CompletionItemKind::KEYWORD))
+        .collect()
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+pub fn hover(path: Option<&Path>, text: &str, position: Position) -> Option<Hover> {
+    let index = SchemaIndex::build(text);
+    let schema = load_schema(path, text).ok()?;
+    let dialect = dialect_for(&schema.datasource.provider).ok()?;
+    let (word, span) = word_at(text, position.line, position.character)?;
+
+    let contents = if let Some(model) = index
+        .model(&word)
+        .and_then(|block| schema.find_model(&block.name))
+    {
+        model_hover(&schema, dialect.as_ref(), &model.name)?
+    } else {
+        let (block, field) = index.field_at(position.line)?;
+        let model = schema.find_model(&block.name)?;
+        let field = model.fields.iter().find(|f| f.name == field.name)?;
+        let mut contents = format!("**{}.{}** `{}`", model.name, field.name, field.r#type);
+        if let Some(column) = SqlColumn::from_field(field) {
+            let nullability = if column.nullable { "NULL" } else { "NOT NULL" };
+            contents.push_str(&format!(
+                "\n\nSQL: `{} {}`",
+                dialect.render_type(&column.sql_type).ok()?,
+                nullability
+            ));
+        }
+        if let Some(rust) = rust_type_name(field) {
+            contents.push_str(&format!("\n\nRust: `{}`", rust));
+        }
+        if let Some(docs) = &field.documentation {
+            contents.push_str(&format!("\n\n{}", docs));
+        }
+        contents
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: contents,
+        }),
+        range: Some(range(span)),
+    })
+}
+
+fn model_hover(schema: &Schema, dialect: &dyn Dialect, name: &str) -> Option<String> {
+    let model = schema.find_model(name)?;
+    let table = schema_to_tables(schema)
+        .into_iter()
+        .find(|table| table.name == model.table_name())?;
+    let mut contents = format!(
+        "**model {}**\n\nTable: `{}`",
+        model.name,
+        dialect.table_name(&table)
+    );
+    if let Some(docs) = &model.documentation {
+        contents.push_str(&format!("\n\n{}", docs));
+    }
+    Some(contents)
+}
+
+/// Where the model named under the cursor in the first document is declared, searching
+/// every document of the schema.
+pub fn definition(documents: &[(Url, String)], position: Position) -> Option<Location> {
+    let (_, text) = documents.first()?;
+    let (word, _) = word_at(text, position.line, position.character)?;
+    documents.iter().find_map(|(uri, text)| {
+        let model = SchemaIndex::build(text).model(&word)?.name_span;
+        Some(Location {
+            uri: uri.clone(),
+            range: range(model),
+        })
+    })
+}
+
+/// Renames the model or field under the cursor in the first document: a model together
+/// with every field type that refers to it, a field together with the relation and
+/// block-attribute lists that name it, in every document of the schema.
+pub fn rename_edits(
+    documents: &[(Url, String)],
+    position: Position,
+    new_name: &str,
+) -> HashMap<Url, Vec<TextEdit>> {
+    let Some((_, text)) = documents.first() else {
+        return HashMap::new();
+    };
+    let Some((word, _)) = word_at(text, position.line, position.character) else {
+        return HashMap::new();
+    };
+    let indexes: Vec<SchemaIndex> = documents
+        .iter()
+        .map(|(_, text)| SchemaIndex::build(text))
+        .collect();
+    let mut spans: Vec<Vec<Span>> = vec![Vec::new(); documents.len()];
+
+    if indexes.iter().any(|index| index.model(&word).is_some()) {
+        for (index, spans) in indexes.iter().zip(&mut spans) {
+            if let Some(model) = index.model(&word) {
+                spans.push(model.name_span);
+            }
+            for block in index.models() {
+                for field in &block.fields {
+                    if base_type(&field.type_name) == word {
+                        spans.push(Span {
+                            line: field.type_span.line,
+                            start: field.type_span.start,
+                            end: field.type_span.start + utf16_len(&word),
+                        });
+                    }
+                }
+            }
+        }
+    } else if let Some((block, field)) = indexes[0]
+        .block_at(position.line)
+        .and_then(|block| Some((block, block.fields.iter().find(|f| f.name == word)?)))
+    {
+        let lines: Vec<&str> = text.lines().collect();
+        spans[0].push(field.name_span);
+        for line in block.start_line..=block.end_line {
+            let source = lines.get(line as usize).copied().unwrap_or_default();
+            spans[0].extend(list_references(source, line, Some("fields:"), &field.name));
+            if source.trim_start().starts_with("@@") {
+                spans[0].extend(list_references(source, line, None, &field.name));
+            }
+        }
+        for ((index, spans), (_, text)) in indexes.iter().zip(&mut spans).zip(documents) {
+            let lines: Vec<&str> = text.lines().collect();
+            for other in index.models() {
+                for relation in &other.fields {
+                    if base_type(&relation.type_name) != block.name {
+                        continue;
+                    }
+                    let line = relation.name_span.line;
+                    let source = lines.get(line as usize).copied().unwrap_or_default();
+                    spans.extend(list_references(
+                        source,
+                        line,
+                        Some("references:"),
+                        &field.name,
+                    ));
+                }
+            }
+        }
+    }
+
+    documents
+        .iter()
+        .zip(spans)
+        .filter(|(_, spans)| !spans.is_empty())
+        .map(|((uri, _), mut spans)| {
+            spans.sort_by_key(|span| (span.line, span.start));
+            spans.dedup();
+            let edits = spans
+                .into_iter()
+                .map(|span| TextEdit {
+                    range: range(span),
+                    new_text: new_name.to_string(),
+                })
+                .collect();
+            (uri.clone(), edits)
+        })
+        .collect()
+}
+
+fn range(span: Span) -> Range {
+    Range::new(
+        Position::new(span.line, span.start),
+        Position::new(span.line, span.end),
+    )
+}
+
+fn end_of(text: &str) -> Position {
+    let lines = text.split('\n').count() as u32;
+    let last = text.rsplit('\n').next().unwrap_or_default();
+    Position::new(lines.saturating_sub(1), utf16_len(last))
+}
@@ -0,0 +1,4 @@
+#[tokio::main]
+async fn main() {
+    petrol_lsp::run_stdio().await;
+}
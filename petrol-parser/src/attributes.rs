@@ -0,0 +1,544 @@
+use pest::error::{Error, ErrorVariant};
+use pest::iterators::Pair;
+use pest::Span;
+use petrol_core::schema::*;
+
+use crate::{ParserError, Rule};
+
+const FIELD_ATTRIBUTES: &[&str] = &[
+    "id",
+    "unique",
+    "updatedAt",
+    "default",
+    "map",
+    "relation",
+    "check",
+    "generated",
+    "renamedFrom",
+];
+const MODEL_ATTRIBUTES: &[&str] = &[
+    "id",
+    "map",
+    "unique",
+    "index",
+    "schema",
+    "check",
+    "renamedFrom",
+];
+const DEFAULT_FUNCTIONS: &[&str] = &[
+    "autoincrement",
+    "now",
+    "uuid",
+    "cuid",
+    "nanoid",
+    "dbgenerated",
+];
+const NATIVE_TYPES: &[&str] = &[
+    "Text",
+    "VarChar",
+    "Char",
+    "Citext",
+    "SmallInt",
+    "Integer",
+    "BigInt",
+    "Real",
+    "DoublePrecision",
+    "Decimal",
+    "Boolean",
+    "Timestamp",
+    "Timestamptz",
+    "Time",
+    "Date",
+    "Uuid",
+    "Json",
+    "JsonB",
+    "ByteA",
+    "Binary",
+];
+
+enum ExprKind<'i> {
+    String(String),
+    Number(&'i str),
+    Boolean(bool),
+    Ident(&'i str),
+    Array(Vec<Expr<'i>>),
+    Call(&'i str, Vec<Expr<'i>>),
+}
+
+struct Expr<'i> {
+    kind: ExprKind<'i>,
+    span: Span<'i>,
+}
+
+impl<'i> Expr<'i> {
+    fn parse(pair: Pair<'i, Rule>) -> Self {
+        let span = pair.as_span();
+        let kind = match pair.as_rule() {
+            Rule::string => ExprKind::String(crate::unquote(pair.as_str())),
+            Rule::number => ExprKind::Number(pair.as_str()),
+            Rule::boolean => ExprKind::Boolean(pair.as_str() == "true"),
+            Rule::array => ExprKind::Array(pair.into_inner().map(Expr::parse).collect()),
+            Rule::function_call => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().map(|name| name.as_str()).unwrap_or_default();
+                ExprKind::Call(name, inner.map(Expr::parse).collect())
+            }
+            _ => ExprKind::Ident(pair.as_str()),
+        };
+        Self { kind, span }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self.kind {
+            ExprKind::String(_) => "a string",
+            ExprKind::Number(_) => "a number",
+            ExprKind::Boolean(_) => "a boolean",
+            ExprKind::Ident(_) => "an identifier",
+            ExprKind::Array(_) => "a list",
+            ExprKind::Call(..) => "a function call",
+        }
+    }
+
+    fn mismatch(&self, attribute: &str, expected: &str) -> ParserError {
+        error_at(
+            self.span,
+            format!(
+                "{} expects {}, found {} `{}`",
+                attribute,
+                expected,
+                self.describe(),
+                self.span.as_str()
+            ),
+        )
+    }
+
+    fn into_string(self, attribute: &str) -> Result<String, ParserError> {
+        match self.kind {
+            ExprKind::String(value) => Ok(value),
+            _ => Err(self.mismatch(attribute, "a string")),
+        }
+    }
+
+    fn into_u32(self, attribute: &str) -> Result<u32, ParserError> {
+        match self.kind {
+            ExprKind::Number(value) => value
+                .parse()
+                .map_err(|_| self.mismatch(attribute, "a non-negative integer")),
+            _ => Err(self.mismatch(attribute, "a non-negative integer")),
+        }
+    }
+
+    fn into_field_list(self, attribute: &str) -> Result<Vec<String>, ParserError> {
+        let ExprKind::Array(items) = self.kind else {
+            return Err(self.mismatch(attribute, "a list of field names"));
+        };
+        items
+            .into_iter()
+            .map(|item| match item.kind {
+                ExprKind::Ident(name) => Ok(name.to_string()),
+                _ => Err(item.mismatch(attribute, "a list of field names")),
+            })
+            .collect()
+    }
+}
+
+/// The arguments of one attribute, consumed as they are checked so leftovers can be
+/// reported.
+struct Arguments<'i> {
+    attribute: String,
+    span: Span<'i>,
+    positional: Vec<Expr<'i>>,
+    named: Vec<(Span<'i>, Expr<'i>)>,
+}
+
+impl<'i> Arguments<'i> {
+    fn parse(attribute: String, span: Span<'i>, pair: Option<Pair<'i, Rule>>) -> Self {
+        let mut positional = Vec::new();
+        let mut named = Vec::new();
+        for argument in pair.into_iter().flat_map(Pair::into_inner) {
+            if argument.as_rule() == Rule::named_argument {
+                let mut inner = argument.into_inner();
+                if let (Some(key), Some(value)) = (inner.next(), inner.next()) {
+                    named.push((key.as_span(), Expr::parse(value)));
+                }
+            } else {
+                positional.push(Expr::parse(argument));
+            }
+        }
+        Self {
+            attribute,
+            span,
+            positional,
+            named,
+        }
+    }
+
+    /// The argument called `key`, which may also be passed as the first positional argument.
+    fn take(&mut self, key: &str) -> Option<Expr<'i>> {
+        self.take_named(key).or_else(|| {
+            if self.positional.is_empty() {
+                None
+            } else {
+                Some(self.positional.remove(0))
+            }
+        })
+    }
+
+    fn take_named(&mut self, key: &str) -> Option<Expr<'i>> {
+        let index = self
+            .named
+            .iter()
+            .position(|(name, _)| name.as_str() == key)?;
+        Some(self.named.remove(index).1)
+    }
+
+    fn require(&mut self, key: &str) -> Result<Expr<'i>, ParserError> {
+        self.take(key).ok_or_else(|| {
+            error_at(
+                self.span,
+                format!("{} is missing its `{}` argument", self.attribute, key),
+            )
+        })
+    }
+
+    /// Fails on any argument that was not consumed, suggesting the closest of `known`.
+    fn finish(self, known: &[&str]) -> Result<(), ParserError> {
+        if let Some((key, _)) = self.named.first() {
+            return Err(error_at(
+                *key,
+                format!(
+                    "unknown argument `{}` for {}{}",
+                    key.as_str(),
+                    self.attribute,
+                    suggestion(key.as_str(), known, "", "")
+                ),
+            ));
+        }
+        if let Some(extra) = self.positional.first() {
+            return Err(error_at(
+                extra.span,
+                format!(
+                    "unexpected argument `{}` for {}",
+                    extra.span.as_str(),
+                    self.attribute
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn parse_field_attribute(pair: Pair<Rule>) -> Result<FieldAttribute, ParserError> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let name_pair = inner
+        .next()
+        .ok_or_else(|| error_at(span, "attribute missing name".into()))?;
+    let name = name_pair.as_str();
+
+    if let Some(native) = name.strip_prefix("db.") {
+        let args = Arguments::parse(format!("@{}", name), span, inner.next());
+        return parse_native_type(native, name_pair.as_span(), args)
+            .map(FieldAttribute::NativeType);
+    }
+
+    let mut args = Arguments::parse(format!("@{}", name), span, inner.next());
+    let attribute = match name {
+        "id" => FieldAttribute::Id,
+        "unique" => FieldAttribute::Unique,
+        "updatedAt" => FieldAttribute::UpdatedAt,
+        "map" => FieldAttribute::Map(args.require("name")?.into_string("@map")?),
+        "default" => FieldAttribute::Default(parse_default(args.require("value")?)?),
+        "check" => FieldAttribute::Check(args.require("expr")?.into_string("@check")?),
+        "renamedFrom" => {
+            FieldAttribute::RenamedFrom(args.require("name")?.into_string("@renamedFrom")?)
+        }
+        "generated" => {
+            let expr = args.require("expr")?.into_string("@generated")?;
+            // Postgres only computes generated columns on write, so `stored` is the one kind.
+            if let Some(kind) = args.take("kind") {
+                if !matches!(kind.kind, ExprKind::Ident("stored")) {
+                    return Err(kind.mismatch("@generated", "`stored`"));
+                }
+            }
+            FieldAttribute::Generated(expr)
+        }
+        "relation" => {
+            let fields = args.take_named("fields");
+            let references = args.take_named("references");
+            let name = args
+                .take("name")
+                .map(|name| name.into_string("@relation"))
+                .transpose()?;
+            args.finish(&["name", "fields", "references"])?;
+            let relation = match (fields, references) {
+                (Some(fields), Some(references)) => RelationAttribute {
+                    name,
+                    fields: fields.into_field_list("@relation")?,
+                    references: references.into_field_list("@relation")?,
+                },
+                (None, None) => RelationAttribute {
+                    name,
+                    fields: Vec::new(),
+                    references: Vec::new(),
+                },
+                _ => {
+                    return Err(error_at(
+                        span,
+                        "@relation needs both `fields` and `references`".into(),
+                    ))
+                }
+            };
+            return Ok(FieldAttribute::Relation(relation));
+        }
+        _ => {
+            return Err(error_at(
+                name_pair.as_span(),
+                format!(
+                    "unknown attribute `@{}`{}",
+                    name,
+                    suggestion(name, FIELD_ATTRIBUTES, "@", "")
+                ),
+            ))
+        }
+    };
+    args.finish(&[])?;
+    Ok(attribute)
+}
+
+pub(crate) fn parse_model_attribute(pair: Pair<Rule>) -> Result<ModelAttribute, ParserError> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let name_pair = inner
+        .next()
+        .ok_or_else(|| error_at(span, "model attribute missing name".into()))?;
+    let name = name_pair.as_str();
+    let attribute = format!("@@{}", name);
+    let mut args = Arguments::parse(attribute.clone(), span, inner.next());
+
+    let parsed = match name {
+        "id" => ModelAttribute::Id(args.require("fields")?.into_field_list(&attribute)?),
+        "map" => ModelAttribute::Map(args.require("name")?.into_string(&attribute)?),
+        "unique" => ModelAttribute::Unique(args.require("fields")?.into_field_list(&attribute)?),
+        "index" => ModelAttribute::Index(args.require("fields")?.into_field_list(&attribute)?),
+        "schema" => ModelAttribute::Schema(args.require("name")?.into_string(&attribute)?),
+        "renamedFrom" => {
+            ModelAttribute::RenamedFrom(args.require("name")?.into_string(&attribute)?)
+        }
+        "check" => {
+            let name = args
+                .take_named("name")
+                .map(|name| name.into_string(&attribute))
+                .transpose()?;
+            let expr = args.require("expr")?.into_string(&attribute)?;
+            args.finish(&["name", "expr"])?;
+            return Ok(ModelAttribute::Check { name, expr });
+        }
+        _ => {
+            return Err(error_at(
+                name_pair.as_span(),
+                format!(
+                    "unknown attribute `@@{}`{}",
+                    name,
+                    suggestion(name, MODEL_ATTRIBUTES, "@@", "")
+                ),
+            ))
+        }
+    };
+    args.finish(&[])?;
+    Ok(parsed)
+}
+
+fn parse_default(value: Expr) -> Result<DefaultValue, ParserError> {
+    let default = match value.kind {
+        ExprKind::String(value) => DefaultValue::String(value),
+        ExprKind::Boolean(value) => DefaultValue::Boolean(value),
+        ExprKind::Number(number) if number.contains('.') => DefaultValue::Float(
+            number
+                .parse()
+                .map_err(|_| value.mismatch("@default", "a number"))?,
+        ),
+        ExprKind::Number(number) => DefaultValue::Int(
+            number
+                .parse()
+                .map_err(|_| value.mismatch("@default", "a 64-bit integer"))?,
+        ),
+        ExprKind::Call("dbgenerated", ref args) => match args.as_slice() {
+            [Expr {
+                kind: ExprKind::String(expr),
+                ..
+            }] => DefaultValue::DbGenerated(expr.clone()),
+            [other] => return Err(other.mismatch("dbgenerated()", "a SQL expression string")),
+            _ => {
+                return Err(error_at(
+                    value.span,
+                    "dbgenerated() takes a single SQL expression string".into(),
+                ))
+            }
+        },
+        ExprKind::Call(name, ref args) => {
+            let function = format!("{}()", name);
+            let mut numbers = Vec::new();
+            for arg in args {
+                match arg.kind {
+                    ExprKind::Number(number) => numbers.push(number),
+                    // Unknown functions are reported by name below.
+                    _ if DEFAULT_FUNCTIONS.contains(&name) => {
+                        return Err(arg.mismatch(&function, "a number"))
+                    }
+                    _ => {}
+                }
+            }
+            match (name, numbers.as_slice()) {
+                ("autoincrement", []) => DefaultValue::AutoIncrement,
+                ("now", []) => DefaultValue::Now,
+                ("uuid", [] | ["4"]) => DefaultValue::Uuid,
+                ("uuid", ["7"]) => DefaultValue::UuidV7,
+                ("cuid", []) => DefaultValue::Cuid,
+                ("nanoid", []) => DefaultValue::NanoId(None),
+                ("nanoid", [size]) => DefaultValue::NanoId(Some(size.parse().map_err(|_| {
+                    error_at(value.span, format!("invalid nanoid size `{}`", size))
+                })?)),
+                ("uuid", _) => {
+                    return Err(error_at(
+                        value.span,
+                        "uuid() takes an optional version, 4 or 7".into(),
+                    ))
+                }
+                _ if DEFAULT_FUNCTIONS.contains(&name) => {
+                    return Err(error_at(
+                        value.span,
+                        format!("unexpected arguments for {}", function),
+                    ))
+                }
+                _ => {
+                    return Err(error_at(
+                        value.span,
+                        format!(
+                            "unknown function `{}` in @default{}",
+                            function,
+                            suggestion(name, DEFAULT_FUNCTIONS, "", "()")
+                        ),
+                    ))
+                }
+            }
+        }
+        ExprKind::Ident(_) | ExprKind::Array(_) => {
+            return Err(value.mismatch("@default", "a literal or a function call"))
+        }
+    };
+    Ok(default)
+}
+
+fn parse_native_type(
+    name: &str,
+    name_span: Span,
+    mut args: Arguments,
+) -> Result<NativeType, ParserError> {
+    let attribute = args.attribute.clone();
+    let mut numbers = Vec::new();
+    for arg in std::mem::take(&mut args.positional) {
+        numbers.push(arg.into_u32(&attribute)?);
+    }
+    args.finish(&[])?;
+
+    let too_many = |max: usize| {
+        error_at(
+            name_span,
+            format!(
+                "{} takes at most {} argument(s), found {}",
+                attribute,
+                max,
+                numbers.len()
+            ),
+        )
+    };
+
+    let native = match name {
+        "Text" => NativeType::Text,
+        "VarChar" => NativeType::VarChar(numbers.first().copied()),
+        "Char" => NativeType::Char(numbers.first().copied()),
+        "Citext" => NativeType::Citext,
+        "SmallInt" => NativeType::SmallInt,
+        "Integer" => NativeType::Integer,
+        "BigInt" => NativeType::BigInt,
+        "Real" => NativeType::Real,
+        "DoublePrecision" => NativeType::DoublePrecision,
+        "Decimal" => match numbers.as_slice() {
+            [] => NativeType::Decimal(None),
+            [precision, scale] => NativeType::Decimal(Some((*precision, *scale))),
+            _ => {
+                return Err(error_at(
+                    name_span,
+                    "@db.Decimal takes either no arguments or (precision, scale)".into(),
+                ))
+            }
+        },
+        "Boolean" => NativeType::Boolean,
+        "Timestamp" => NativeType::Timestamp(numbers.first().copied()),
+        "Timestamptz" => NativeType::Timestamptz(numbers.first().copied()),
+        "Time" => NativeType::Time(numbers.first().copied()),
+        "Date" => NativeType::Date,
+        "Uuid" => NativeType::Uuid,
+        "Json" => NativeType::Json,
+        "JsonB" => NativeType::JsonB,
+        "ByteA" => NativeType::ByteA,
+        "Binary" => NativeType::Binary(numbers.first().copied()),
+        _ => {
+            return Err(error_at(
+                name_span,
+                format!(
+                    "unknown native type `{}`{}",
+                    attribute,
+                    suggestion(name, NATIVE_TYPES, "@db.", "")
+                ),
+            ))
+        }
+    };
+
+    let max = match native {
+        NativeType::VarChar(_)
+        | NativeType::Char(_)
+        | NativeType::Timestamp(_)
+        | NativeType::Timestamptz(_)
+        | NativeType::Time(_)
+        | NativeType::Binary(_) => 1,
+        NativeType::Decimal(_) => 2,
+        _ => 0,
+    };
+    if numbers.len() > max {
+        return Err(too_many(max));
+    }
+
+    Ok(native)
+}
+
+fn error_at(span: Span, message: String) -> ParserError {
+    Error::new_from_span(ErrorVariant::<Rule>::CustomError { message }, span).into()
+}
+
+/// `, did you mean `@unique`?` when one of `candidates` is close enough to `name`.
+fn suggestion(name: &str, candidates: &[&str], prefix: &str, suffix: &str) -> String {
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| format!(", did you mean `{}{}{}`?", prefix, candidate, suffix))
+        .unwrap_or_default()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
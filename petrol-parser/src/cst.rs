@@ -0,0 +1,616 @@
+use petrol_core::PetrolError;
+
+use crate::ParserError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub items: Vec<TopItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TopItem {
+    Blank,
+    Comment(String),
+    Import {
+        path: String,
+        comment: Option<String>,
+    },
+    Block(Block),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub keyword: String,
+    pub name: String,
+    pub comment: Option<String>,
+    pub members: Vec<Member>,
+    pub closing_comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Member {
+    Blank,
+    Comment(String),
+    Entry {
+        key: String,
+        value: String,
+        comment: Option<String>,
+    },
+    Field {
+        name: String,
+        r#type: String,
+        attributes: Vec<String>,
+        comment: Option<String>,
+    },
+    BlockAttribute {
+        text: String,
+        comment: Option<String>,
+    },
+}
+
+pub fn parse_document(input: &str) -> Result<Document, ParserError> {
+    let mut items = Vec::new();
+    let mut current: Option<Block> = None;
+
+    for (line_no, line) in logical_lines(input)? {
+        let (code, comment) = split_trailing_comment(&line);
+        let mut code = code.trim();
+
+        let block = match current.as_mut() {
+            Some(block) => block,
+            None => {
+                if code.is_empty() {
+                    items.push(match comment {
+                        Some(comment) => TopItem::Comment(comment),
+                        None => TopItem::Blank,
+                    });
+                    continue;
+                }
+                if let Some(path) = code.strip_prefix("import ") {
+                    items.push(TopItem::Import {
+                        path: path.trim().to_string(),
+                        comment,
+                    });
+                    continue;
+                }
+                let (header, body) = code.split_once('{').ok_or_else(|| {
+                    syntax_error(line_no, "expected a block header `<keyword> <name> {`")
+                })?;
+                let mut words = header.split_whitespace();
+                let (Some(keyword), Some(name), None) = (words.next(), words.next(), words.next())
+                else {
+                    return Err(syntax_error(line_no, "expected `<keyword> <name> {`"));
+                };
+                code = body.trim();
+                let block = current.insert(Block {
+                    keyword: keyword.to_string(),
+                    name: name.to_string(),
+                    comment: None,
+                    members: Vec::new(),
+                    closing_comment: None,
+                });
+                if code.is_empty() {
+                    block.comment = comment;
+                    continue;
+                }
+                block
+            }
+        };
+
+        // A block may close on the line of its last member, or of its header.
+        let (body, closes) = match code.strip_suffix('}') {
+            Some(body) => (body.trim_end(), true),
+            None => (code, false),
+        };
+        if body.is_empty() && !closes {
+            block.members.push(match comment {
+                Some(comment) => Member::Comment(comment),
+                None => Member::Blank,
+            });
+            continue;
+        }
+
+        let members = parse_members(&block.keyword, body, line_no)?;
+        block.members.extend(members);
+        if closes {
+            if let Some(mut block) = current.take() {
+                block.closing_comment = comment;
+                items.push(TopItem::Block(block));
+            }
+        } else if let Some(member) = block.members.last_mut() {
+            set_comment(member, comment);
+        }
+    }
+
+    if let Some(block) = current {
+        return Err(PetrolError::SchemaParse(format!(
+            "{} {} is missing its closing `}}`",
+            block.keyword, block.name
+        ))
+        .into());
+    }
+
+    Ok(Document { items })
+}
+
+/// The members on one line of a block's body, usually one but as many as the line holds.
+fn parse_members(keyword: &str, code: &str, line_no: usize) -> Result<Vec<Member>, ParserError> {
+    let mut members = Vec::new();
+    let mut tokens = split_tokens(code).into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if token.starts_with("@@") {
+            members.push(Member::BlockAttribute {
+                text: token,
+                comment: None,
+            });
+        } else if keyword == "model" {
+            let Some(r#type) = tokens.next() else {
+                return Err(syntax_error(line_no, "expected `<field> <Type>`"));
+            };
+            let mut attributes = Vec::new();
+            while let Some(attribute) =
+                tokens.next_if(|token| token.starts_with('@') && !token.starts_with("@@"))
+            {
+                attributes.push(attribute);
+            }
+            members.push(Member::Field {
+                name: token,
+                r#type,
+                attributes,
+                comment: None,
+            });
+        } else {
+            let mut entry = token;
+            while entry
+                .split_once('=')
+                .is_none_or(|(_, value)| value.trim().is_empty())
+            {
+                let Some(token) = tokens.next() else { break };
+                entry.push(' ');
+                entry.push_str(&token);
+            }
+            let (key, value) = split_entry(&entry)
+                .ok_or_else(|| syntax_error(line_no, "expected `<key> = <value>`"))?;
+            members.push(Member::Entry {
+                key,
+                value,
+                comment: None,
+            });
+        }
+    }
+    Ok(members)
+}
+
+fn set_comment(member: &mut Member, trailing: Option<String>) {
+    match member {
+        Member::Entry { comment, .. }
+        | Member::Field { comment, .. }
+        | Member::BlockAttribute { comment, .. } => *comment = trailing,
+        Member::Blank | Member::Comment(_) => {}
+    }
+}
+
+pub fn format_document(document: &Document) -> String {
+    let mut out = String::new();
+    let mut previous_blank = true;
+    let mut after_block = false;
+    let mut after_import = false;
+
+    for item in &document.items {
+        match item {
+            TopItem::Blank => {
+                if !previous_blank {
+                    out.push('\n');
+                }
+                previous_blank = true;
+                after_block = false;
+                after_import = false;
+                continue;
+            }
+            TopItem::Comment(comment) => {
+                if after_block {
+                    out.push('\n');
+                }
+                out.push_str(comment);
+                out.push('\n');
+            }
+            TopItem::Import { path, comment } => {
+                if after_block {
+                    out.push('\n');
+                }
+                out.push_str(&format!("import {}", path));
+                push_comment(&mut out, comment.as_deref());
+                out.push('\n');
+            }
+            TopItem::Block(block) => {
+                if after_block || after_import {
+                    out.push('\n');
+                }
+                format_block(block, &mut out);
+            }
+        }
+        previous_blank = false;
+        after_block = matches!(item, TopItem::Block(_));
+        after_import = matches!(item, TopItem::Import { .. });
+    }
+
+    let trimmed = out.trim_end();
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", trimmed)
+    }
+}
+
+pub fn format_schema(input: &str) -> Result<String, ParserError> {
+    Ok(format_document(&parse_document(input)?))
+}
+
+fn format_block(block: &Block, out: &mut String) {
+    out.push_str(&format!("{} {} {{", block.keyword, block.name));
+    push_comment(out, block.comment.as_deref());
+    out.push('\n');
+
+    let members = trim_blank_members(&block.members);
+    let mut index = 0;
+    while index < members.len() {
+        match &members[index] {
+            Member::Blank => {
+                out.push('\n');
+                index += 1;
+                while matches!(members.get(index), Some(Member::Blank)) {
+                    index += 1;
+                }
+            }
+            Member::Comment(comment) => {
+                out.push_str(&format!("  {}\n", comment));
+                index += 1;
+            }
+            Member::BlockAttribute { text, comment } => {
+                push_member(out, &format!("  {}", text), comment.as_deref());
+                index += 1;
+            }
+            Member::Field { .. } => {
+                let end = group_end(members, index, |m| matches!(m, Member::Field { .. }));
+                format_fields(&members[index..end], out);
+                index = end;
+            }
+            Member::Entry { .. } => {
+                let end = group_end(members, index, |m| matches!(m, Member::Entry { .. }));
+                format_entries(&members[index..end], out);
+                index = end;
+            }
+        }
+    }
+
+    out.push('}');
+    push_comment(out, block.closing_comment.as_deref());
+    out.push('\n');
+}
+
+fn format_fields(group: &[Member], out: &mut String) {
+    let mut name_width = 0;
+    let mut type_width = 0;
+    for member in group {
+        if let Member::Field { name, r#type, .. } = member {
+            name_width = name_width.max(name.len());
+            type_width = type_width.max(r#type.len());
+        }
+    }
+
+    for member in group {
+        if let Member::Field {
+            name,
+            r#type,
+            attributes,
+            comment,
+        } = member
+        {
+            let line = format!(
+                "  {:<name_width$} {:<type_width$} {}",
+                name,
+                r#type,
+                attributes.join(" ")
+            );
+            push_member(out, line.trim_end(), comment.as_deref());
+        }
+    }
+}
+
+fn format_entries(group: &[Member], out: &mut String) {
+    let key_width = group
+        .iter()
+        .filter_map(|member| match member {
+            Member::Entry { key, .. } => Some(key.len()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default();
+
+    for member in group {
+        if let Member::Entry {
+            key,
+            value,
+            comment,
+        } = member
+        {
+            let line = format!("  {:<key_width$} = {}", key, value);
+            push_member(out, &line, comment.as_deref());
+        }
+    }
+}
+
+/// Writes a member, indenting the lines its brackets keep open by how deeply they nest.
+fn push_member(out: &mut String, line: &str, comment: Option<&str>) {
+    let mut opened: Vec<i32> = Vec::new();
+    let mut depth = 0;
+    for (index, part) in line.split('\n').enumerate() {
+        let closing = part
+            .chars()
+            .take_while(|ch| matches!(ch, ')' | ']'))
+            .count();
+        let lead = depth - closing as i32;
+        if index > 0 {
+            while opened.last().is_some_and(|&level| level >= lead) {
+                opened.pop();
+            }
+            out.push('\n');
+            out.push_str(&"  ".repeat(opened.len() + 1));
+        }
+        out.push_str(part);
+        depth += bracket_depth(part);
+        if depth > lead {
+            opened.push(lead);
+        }
+    }
+    push_comment(out, comment);
+    out.push('\n');
+}
+
+fn push_comment(out: &mut String, comment: Option<&str>) {
+    if let Some(comment) = comment {
+        out.push(' ');
+        out.push_str(comment);
+    }
+}
+
+fn group_end(members: &[Member], start: usize, same: impl Fn(&Member) -> bool) -> usize {
+    members[start..]
+        .iter()
+        .position(|member| !same(member))
+        .map(|offset| start + offset)
+        .unwrap_or(members.len())
+}
+
+fn trim_blank_members(members: &[Member]) -> &[Member] {
+    let start = members
+        .iter()
+        .position(|m| !matches!(m, Member::Blank))
+        .unwrap_or(members.len());
+    let end = members
+        .iter()
+        .rposition(|m| !matches!(m, Member::Blank))
+        .map(|index| index + 1)
+        .unwrap_or(start);
+    &members[start..end]
+}
+
+/// Joins physical lines while brackets are open so multi-line attribute arguments
+/// become a single member. Lines with a comment inside the brackets stay separate,
+/// joined by `\n`, so the comments keep their place.
+fn logical_lines(input: &str) -> Result<Vec<(usize, String)>, ParserError> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, Vec<String>)> = None;
+
+    for (index, raw) in input.lines().enumerate() {
+        let (start, mut parts) = pending.take().unwrap_or((index + 1, Vec::new()));
+        if parts.is_empty() {
+            parts.push(raw.trim_end().to_string());
+        } else if !raw.trim().is_empty() {
+            parts.push(raw.trim().to_string());
+        }
+
+        if bracket_depth(&parts.join("\n")) > 0 {
+            pending = Some((start, parts));
+        } else {
+            lines.push((start, join_parts(parts)));
+        }
+    }
+
+    if let Some((start, _)) = pending {
+        return Err(syntax_error(start, "unclosed bracket"));
+    }
+    Ok(lines)
+}
+
+fn join_parts(parts: Vec<String>) -> String {
+    let Some((last, inner)) = parts.split_last() else {
+        return String::new();
+    };
+    if inner.iter().any(|part| split_comment(part).1.is_some()) {
+        return parts.join("\n");
+    }
+    let mut joined = String::new();
+    for part in inner.iter().chain([last]) {
+        let hugs_bracket = joined.ends_with(['(', '[']) || part.starts_with([')', ']']);
+        if !joined.is_empty() && !hugs_bracket {
+            joined.push(' ');
+        }
+        joined.push_str(part);
+    }
+    joined
+}
+
+/// Like `split_comment` for a logical line, where only a comment on the last physical line
+/// trails the code.
+fn split_trailing_comment(line: &str) -> (String, Option<String>) {
+    match line.rsplit_once('\n') {
+        Some((head, last)) => {
+            let (code, comment) = split_comment(last);
+            (format!("{}\n{}", head, code), comment)
+        }
+        None => split_comment(line),
+    }
+}
+
+/// Splits a line into its code and a trailing `//` comment, ignoring `//` inside strings.
+pub fn split_comment(line: &str) -> (String, Option<String>) {
+    let mut in_string = false;
+    let mut escaped = false;
+    let chars: Vec<char> = line.chars().collect();
+    for (index, ch) in chars.iter().enumerate() {
+        if in_string {
+            match ch {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => in_string = false,
+                _ => escaped = false,
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_string = true,
+            '/' if chars.get(index + 1) == Some(&'/') => {
+                let code: String = chars[..index].iter().collect();
+                let comment: String = chars[index..].iter().collect();
+                return (
+                    code.trim_end().to_string(),
+                    Some(comment.trim_end().to_string()),
+                );
+            }
+            _ => {}
+        }
+    }
+    (line.to_string(), None)
+}
+
+/// Brackets opened minus brackets closed, outside strings and `//` comments.
+fn bracket_depth(code: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+    let mut chars = code.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if in_comment {
+            in_comment = ch != '\n';
+            continue;
+        }
+        if in_string {
+            match ch {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => in_string = false,
+                _ => escaped = false,
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_string = true,
+            '/' if chars.peek() == Some(&'/') => in_comment = true,
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Splits on whitespace that is outside strings, comments and brackets. Arguments,
+/// `?` and `[]` written apart from what they belong to are joined to it.
+fn split_tokens(code: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+
+    let mut chars = code.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if in_comment && ch != '\n' {
+            current.push(ch);
+            continue;
+        }
+        in_comment = false;
+        if in_string {
+            current.push(ch);
+            match ch {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => in_string = false,
+                _ => escaped = false,
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_string = true,
+            '/' if chars.peek() == Some(&'/') => in_comment = true,
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ => {}
+        }
+        if ch.is_whitespace() && depth == 0 {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            if current.is_empty() && depth <= 1 && matches!(ch, '(' | '?' | '[') {
+                if let Some(previous) = tokens.pop() {
+                    current = previous;
+                }
+            }
+            current.push(ch);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn split_entry(code: &str) -> Option<(String, String)> {
+    let (key, value) = code.split_once('=')?;
+    let key = key.trim();
+    let value = value.trim();
+    if key.is_empty() || value.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key.to_string(), value.to_string()))
+}
+
+fn syntax_error(line: usize, message: &str) -> ParserError {
+    PetrolError::SchemaParse(format!("line {}: {}", line, message)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_schema;
+
+    fn assert_formats(input: &str, expected: &str) {
+        let formatted = format_schema(input).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_schema(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn keeps_comments_inside_brackets() {
+        assert_formats(
+            "model A {\n  id Int @id\n  name String\n  @@unique([\n      id, // first\n  name // second\n  ])\n}\n",
+            "model A {\n  id   Int    @id\n  name String\n  @@unique([\n    id, // first\n    name // second\n  ])\n}\n",
+        );
+    }
+
+    #[test]
+    fn keeps_a_comment_after_an_opening_bracket() {
+        assert_formats(
+            "model A {\n  id Int @id\n  name String\n  @@unique([ // composite\n    id, name]) // trailing\n}\n",
+            "model A {\n  id   Int    @id\n  name String\n  @@unique([ // composite\n    id, name]) // trailing\n}\n",
+        );
+    }
+
+    #[test]
+    fn expands_single_line_blocks() {
+        assert_formats(
+            "datasource db { provider = \"sqlite\" url = \"file:dev.db\" }\nmodel B { id Int @id } // b\n",
+            "datasource db {\n  provider = \"sqlite\"\n  url      = \"file:dev.db\"\n}\n\nmodel B {\n  id Int @id\n} // b\n",
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use pest::error::LineColLocation;
 use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
@@ -5,6 +9,13 @@ use petrol_core::schema::*;
 use petrol_core::PetrolError;
 use thiserror::Error;
 
+mod attributes;
+pub mod cst;
+mod loader;
+
+pub use cst::format_schema;
+pub use loader::{schema_files, SchemaLoader};
+
 #[derive(Parser)]
 #[grammar = "schema.pest"]
 struct PetrolDslParser;
@@ -17,38 +28,187 @@ pub enum ParserError {
     Petrol(#[from] PetrolError),
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    #[error("{}: {error}", path.display())]
+    InFile {
+        path: PathBuf,
+        error: Box<ParserError>,
+    },
+}
+
+impl ParserError {
+    /// 1-based line and column of a syntax error, when the error carries one.
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        match self {
+            ParserError::Pest(err) => Some(match err.line_col {
+                LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+            }),
+            ParserError::InFile { error, .. } => error.line_col(),
+            _ => None,
+        }
+    }
+
+    /// The schema file the error was found in, for schemas loaded from disk.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ParserError::Pest(err) => err.path().map(Path::new),
+            ParserError::InFile { path, .. } => Some(path),
+            _ => None,
+        }
+    }
 }
 
 pub fn parse_schema(input: &str) -> Result<Schema, ParserError> {
-    let mut datasource: Option<DatasourceBlock> = None;
-    let mut generator: Option<GeneratorBlock> = None;
-    let mut models: Vec<Model> = Vec::new();
+    let fragment = parse_fragment(input)?;
+    if let Some(import) = fragment.imports.first() {
+        return Err(PetrolError::Unsupported(format!(
+            "import \"{}\" needs a schema file to resolve against, use parse_schema_file",
+            import
+        ))
+        .into());
+    }
+    merge_fragments(vec![(None, fragment)])
+}
+
+/// Parses a schema file, or every `.petrol` file under a directory, following `import`s.
+pub fn parse_schema_file(path: impl AsRef<std::path::Path>) -> Result<Schema, ParserError> {
+    SchemaLoader::new().load(path)
+}
+
+/// The blocks of a single schema file, before they are merged and validated.
+#[derive(Debug, Default)]
+pub(crate) struct Fragment {
+    pub imports: Vec<String>,
+    pub datasource: Option<DatasourceBlock>,
+    pub generator: Option<GeneratorBlock>,
+    pub models: Vec<Model>,
+}
 
-    let pairs = PetrolDslParser::parse(Rule::schema, input)?;
+pub(crate) fn parse_fragment(input: &str) -> Result<Fragment, ParserError> {
+    let mut fragment = Fragment::default();
+
+    let pairs = PetrolDslParser::parse(Rule::schema, input)?
+        .next()
+        .map(Pair::into_inner)
+        .ok_or_else(|| PetrolError::validation("empty schema"))?;
     for pair in pairs {
         match pair.as_rule() {
-            Rule::datasource => datasource = Some(parse_datasource(pair)?),
-            Rule::generator => generator = Some(parse_generator(pair)?),
-            Rule::model => models.push(parse_model(pair)?),
+            Rule::import => fragment
+                .imports
+                .push(parse_string(pair.into_inner().next())?),
+            Rule::datasource => {
+                if fragment.datasource.is_some() {
+                    return Err(PetrolError::validation("duplicate datasource block").into());
+                }
+                fragment.datasource = Some(parse_datasource(pair)?);
+            }
+            Rule::generator => {
+                if fragment.generator.is_some() {
+                    return Err(PetrolError::validation("duplicate generator block").into());
+                }
+                fragment.generator = Some(parse_generator(pair)?);
+            }
+            Rule::model => fragment.models.push(parse_model(pair)?),
             Rule::EOI => {}
             _ => {}
         }
     }
 
+    Ok(fragment)
+}
+
+/// Combines parsed files into one schema. Errors that belong to a single model or block
+/// are reported against the file it came from.
+pub(crate) fn merge_fragments(
+    fragments: Vec<(Option<PathBuf>, Fragment)>,
+) -> Result<Schema, ParserError> {
+    let mut datasource: Option<(Option<PathBuf>, DatasourceBlock)> = None;
+    let mut generator: Option<(Option<PathBuf>, GeneratorBlock)> = None;
+    let mut models: Vec<Model> = Vec::new();
+    let mut origins: HashMap<String, Option<PathBuf>> = HashMap::new();
+
+    for (path, fragment) in fragments {
+        if let Some(block) = fragment.datasource {
+            if let Some((first, _)) = &datasource {
+                return Err(in_file(
+                    path,
+                    PetrolError::validation(format!(
+                        "datasource is already defined{}",
+                        defined_in(first)
+                    ))
+                    .into(),
+                ));
+            }
+            datasource = Some((path.clone(), block));
+        }
+        if let Some(block) = fragment.generator {
+            if let Some((first, _)) = &generator {
+                return Err(in_file(
+                    path,
+                    PetrolError::validation(format!(
+                        "generator is already defined{}",
+                        defined_in(first)
+                    ))
+                    .into(),
+                ));
+            }
+            generator = Some((path.clone(), block));
+        }
+        for model in fragment.models {
+            if let Some(first) = origins.get(&model.name) {
+                return Err(in_file(
+                    path,
+                    PetrolError::validation(format!(
+                        "model {} is already defined{}",
+                        model.name,
+                        defined_in(first)
+                    ))
+                    .into(),
+                ));
+            }
+            origins.insert(model.name.clone(), path.clone());
+            models.push(model);
+        }
+    }
+
     let schema = Schema {
         datasource: datasource
+            .map(|(_, block)| block)
             .ok_or_else(|| PetrolError::validation("missing datasource block"))?,
-        generator: generator.ok_or_else(|| PetrolError::validation("missing generator block"))?,
+        generator: generator
+            .map(|(_, block)| block)
+            .ok_or_else(|| PetrolError::validation("missing generator block"))?,
         models,
     };
 
-    schema.validate().map_err(ParserError::from)?;
+    for model in &schema.models {
+        schema
+            .validate_model(model)
+            .map_err(|err| in_file(origins[&model.name].clone(), err.into()))?;
+    }
+    schema.validate()?;
     Ok(schema)
 }
 
-pub fn parse_schema_file(path: impl AsRef<std::path::Path>) -> Result<Schema, ParserError> {
-    let contents = std::fs::read_to_string(path)?;
-    parse_schema(&contents)
+fn defined_in(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => format!(" in {}", path.display()),
+        None => String::new(),
+    }
+}
+
+/// Attaches the originating file to an error. Syntax errors carry the path in their own
+/// `-->` marker.
+pub(crate) fn in_file(path: Option<PathBuf>, err: ParserError) -> ParserError {
+    match (path, err) {
+        (Some(path), ParserError::Pest(err)) => {
+            ParserError::Pest(err.with_path(&path.display().to_string()))
+        }
+        (Some(path), err @ (ParserError::Petrol(_) | ParserError::Io(_))) => ParserError::InFile {
+            path,
+            error: Box::new(err),
+        },
+        (_, err) => err,
+    }
 }
 
 fn parse_datasource(pair: Pair<Rule>) -> Result<DatasourceBlock, ParserError> {
@@ -66,6 +226,8 @@ fn parse_datasource(pair: Pair<Rule>) -> Result<DatasourceBlock, ParserError> {
         raw_url: None,
         connection_limit: None,
         pool_timeout_seconds: None,
+        schemas: Vec::new(),
+        extensions: Vec::new(),
     };
 
     for entry in inner {
@@ -99,6 +261,8 @@ fn parse_datasource(pair: Pair<Rule>) -> Result<DatasourceBlock, ParserError> {
                 block.pool_timeout_seconds =
                     value_pair.and_then(|p| p.as_str().parse::<u32>().ok());
             }
+            "schemas" => block.schemas = parse_string_array(value_pair)?,
+            "extensions" => block.extensions = parse_string_array(value_pair)?,
             _ => {}
         }
     }
@@ -118,6 +282,7 @@ fn parse_generator(pair: Pair<Rule>) -> Result<GeneratorBlock, ParserError> {
         name,
         provider: "petrol-client-rust".into(),
         output: None,
+        updated_at: UpdatedAtStrategy::default(),
     };
 
     for entry in inner {
@@ -130,6 +295,18 @@ fn parse_generator(pair: Pair<Rule>) -> Result<GeneratorBlock, ParserError> {
         match key.as_str() {
             "provider" => block.provider = parse_string(value)?,
             "output" => block.output = value.map(|p| unquote(p.as_str())),
+            "updatedAt" => {
+                block.updated_at = match parse_string(value)?.as_str() {
+                    "database" => UpdatedAtStrategy::Database,
+                    "client" => UpdatedAtStrategy::Client,
+                    other => {
+                        return Err(PetrolError::validation(format!(
+                    "unknown updatedAt strategy \"{other}\", expected \"database\" or \"client\""
+                ))
+                        .into())
+                    }
+                }
+            }
             _ => {}
         }
     }
@@ -138,7 +315,8 @@ fn parse_generator(pair: Pair<Rule>) -> Result<GeneratorBlock, ParserError> {
 }
 
 fn parse_model(pair: Pair<Rule>) -> Result<Model, ParserError> {
-    let mut inner = pair.into_inner();
+    let mut inner = pair.into_inner().peekable();
+    let documentation = parse_doc_comments(&mut inner);
     let name = inner
         .next()
         .ok_or_else(|| PetrolError::validation("model missing name"))?
@@ -151,7 +329,7 @@ fn parse_model(pair: Pair<Rule>) -> Result<Model, ParserError> {
     for item in inner {
         match item.as_rule() {
             Rule::field => fields.push(parse_field(item)?),
-            Rule::model_attribute => attributes.push(parse_model_attribute(item)?),
+            Rule::model_attribute => attributes.push(attributes::parse_model_attribute(item)?),
             _ => {}
         }
     }
@@ -160,11 +338,13 @@ fn parse_model(pair: Pair<Rule>) -> Result<Model, ParserError> {
         name,
         fields,
         attributes,
+        documentation,
     })
 }
 
 fn parse_field(pair: Pair<Rule>) -> Result<Field, ParserError> {
-    let mut inner = pair.into_inner();
+    let mut inner = pair.into_inner().peekable();
+    let documentation = parse_doc_comments(&mut inner);
     let name = inner
         .next()
         .ok_or_else(|| PetrolError::validation("field missing name"))?
@@ -178,16 +358,41 @@ fn parse_field(pair: Pair<Rule>) -> Result<Field, ParserError> {
     let mut attributes = Vec::new();
 
     for attr in inner {
-        attributes.push(parse_field_attribute(attr)?);
+        attributes.push(attributes::parse_field_attribute(attr)?);
     }
 
     Ok(Field {
         name,
         r#type: field_type,
         attributes,
+        documentation,
     })
 }
 
+fn parse_doc_comments<'i>(
+    pairs: &mut std::iter::Peekable<pest::iterators::Pairs<'i, Rule>>,
+) -> Option<String> {
+    let mut lines = Vec::new();
+    while let Some(pair) = pairs.next_if(|pair| pair.as_rule() == Rule::doc_comment) {
+        let text = pair
+            .into_inner()
+            .next()
+            .map(|text| text.as_str())
+            .unwrap_or_default();
+        lines.push(
+            text.strip_prefix(' ')
+                .unwrap_or(text)
+                .trim_end()
+                .to_string(),
+        );
+    }
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
 fn parse_field_type(pair: Pair<Rule>) -> Result<FieldType, ParserError> {
     let mut inner = pair.into_inner();
     let ident = inner
@@ -233,135 +438,48 @@ fn parse_scalar(name: &str) -> Option<ScalarType> {
     }
 }
 
-fn parse_field_attribute(pair: Pair<Rule>) -> Result<FieldAttribute, ParserError> {
-    let mut inner = pair.into_inner();
-    let ident = inner
-        .next()
-        .ok_or_else(|| PetrolError::validation("attribute missing ident"))?
-        .as_str();
-    let args = inner.next().map(|a| trim_parens(a.as_str()));
-
-    let attribute = match ident {
-        "id" => FieldAttribute::Id,
-        "unique" => FieldAttribute::Unique,
-        "updatedAt" => FieldAttribute::UpdatedAt,
-        "map" => FieldAttribute::Map(args.clone().as_deref().map(unquote).unwrap_or_default()),
-        "default" => FieldAttribute::Default(parse_default(args.clone().unwrap_or_default())?),
-        "relation" => {
-            FieldAttribute::Relation(parse_relation_attribute(args.clone().unwrap_or_default())?)
-        }
-        _ => FieldAttribute::Map(format!("{}:{}", ident, args.unwrap_or_default())),
-    };
-
-    Ok(attribute)
-}
-
-fn parse_model_attribute(pair: Pair<Rule>) -> Result<ModelAttribute, ParserError> {
-    let mut inner = pair.into_inner();
-    let ident = inner
-        .next()
-        .ok_or_else(|| PetrolError::validation("model attribute missing ident"))?
-        .as_str();
-    let args = inner
-        .next()
-        .map(|a| trim_parens(a.as_str()))
-        .unwrap_or_default();
-
-    let attribute = match ident {
-        "map" => ModelAttribute::Map(unquote(&args)),
-        "unique" => ModelAttribute::Unique(parse_field_list(&args)),
-        "index" => ModelAttribute::Index(parse_field_list(&args)),
-        _ => ModelAttribute::Map(format!("{}:{}", ident, args)),
-    };
-
-    Ok(attribute)
-}
-
-fn parse_default(raw: String) -> Result<DefaultValue, ParserError> {
-    let trimmed = raw.trim();
-    let value = if trimmed.ends_with("()") {
-        match trimmed {
-            "autoincrement()" => DefaultValue::AutoIncrement,
-            "uuid()" => DefaultValue::Uuid,
-            "now()" => DefaultValue::Now,
-            _ => return Err(PetrolError::Unsupported(format!("unknown default {trimmed}")).into()),
-        }
-    } else if trimmed.starts_with('"') {
-        DefaultValue::String(unquote(trimmed))
-    } else if trimmed == "true" || trimmed == "false" {
-        DefaultValue::Boolean(trimmed == "true")
-    } else if trimmed.contains('.') {
-        DefaultValue::Float(
-            trimmed
-                .parse()
-                .map_err(|_| PetrolError::validation("invalid float default"))?,
-        )
-    } else {
-        DefaultValue::Int(
-            trimmed
-                .parse()
-                .map_err(|_| PetrolError::validation("invalid int default"))?,
-        )
-    };
-    Ok(value)
-}
-
-fn parse_relation_attribute(raw: String) -> Result<RelationAttribute, ParserError> {
-    let mut fields = Vec::new();
-    let mut references = Vec::new();
-
-    for segment in raw.split(',') {
-        let mut parts = segment.split(':');
-        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-            let key = key.trim();
-            let value = value.trim();
-            if key == "fields" {
-                fields = parse_field_list(value);
-            } else if key == "references" {
-                references = parse_field_list(value);
-            }
-        }
-    }
-
-    Ok(RelationAttribute { fields, references })
-}
-
-fn parse_field_list(raw: &str) -> Vec<String> {
-    raw.trim()
-        .trim_start_matches('[')
-        .trim_end_matches(']')
-        .split(',')
-        .filter_map(|item| {
-            let trimmed = item.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(trimmed.trim_matches('"').to_string())
-            }
-        })
-        .collect()
-}
-
 fn parse_string(pair: Option<Pair<Rule>>) -> Result<String, ParserError> {
     pair.map(|p| unquote(p.as_str()))
         .ok_or_else(|| PetrolError::validation("expected string").into())
 }
 
-fn trim_parens(value: &str) -> String {
-    value
-        .trim()
-        .trim_start_matches('(')
-        .trim_end_matches(')')
-        .trim()
-        .to_string()
+fn parse_string_array(pair: Option<Pair<Rule>>) -> Result<Vec<String>, ParserError> {
+    let pair = pair.ok_or_else(|| PetrolError::validation("expected array"))?;
+    if pair.as_rule() != Rule::array {
+        return Err(
+            PetrolError::validation(format!("expected array, found {}", pair.as_str())).into(),
+        );
+    }
+    Ok(pair
+        .into_inner()
+        .map(|item| unquote(item.as_str()))
+        .collect())
 }
 
-fn unquote(value: &str) -> String {
-    value
-        .trim()
-        .trim_start_matches('"')
-        .trim_end_matches('"')
-        .replace("\\\"", "\"")
+/// Strips the quotes from a string literal and resolves its escapes.
+pub(crate) fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|inner| inner.strip_suffix('"'))
+        .unwrap_or(value);
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
 }
 
 fn parse_env_call(pair: Pair<Rule>) -> String {
@@ -0,0 +1,111 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use petrol_core::schema::Schema;
+use petrol_core::PetrolError;
+
+use crate::{in_file, merge_fragments, parse_fragment, Fragment, ParserError};
+
+const SCHEMA_EXTENSION: &str = "petrol";
+
+/// Loads a schema that may be spread over several files, either as every `.petrol` file
+/// in a directory or through `import "other.petrol"` statements.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaLoader {
+    sources: HashMap<PathBuf, String>,
+}
+
+impl SchemaLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses `contents` instead of reading `path` from disk, e.g. for an unsaved editor buffer.
+    pub fn with_source(mut self, path: impl AsRef<Path>, contents: impl Into<String>) -> Self {
+        self.sources
+            .insert(normalize(path.as_ref()), contents.into());
+        self
+    }
+
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<Schema, ParserError> {
+        merge_fragments(self.fragments(path.as_ref())?)
+    }
+
+    /// The files `load` reads for `path`, imported ones included.
+    pub fn files(&self, path: impl AsRef<Path>) -> Result<Vec<PathBuf>, ParserError> {
+        Ok(self
+            .fragments(path.as_ref())?
+            .into_iter()
+            .filter_map(|(file, _)| file)
+            .collect())
+    }
+
+    fn fragments(&self, path: &Path) -> Result<Vec<(Option<PathBuf>, Fragment)>, ParserError> {
+        let mut queue: VecDeque<PathBuf> = if path.is_dir() {
+            schema_files(path)?.into()
+        } else {
+            VecDeque::from([path.to_path_buf()])
+        };
+
+        let mut seen = HashSet::new();
+        let mut fragments = Vec::new();
+        while let Some(file) = queue.pop_front() {
+            if !seen.insert(normalize(&file)) {
+                continue;
+            }
+
+            let fragment = self
+                .read(&file)
+                .and_then(|contents| parse_fragment(&contents))
+                .map_err(|err| in_file(Some(file.clone()), err))?;
+
+            let base = file.parent().unwrap_or(Path::new(""));
+            for import in &fragment.imports {
+                let target = base.join(import);
+                if !target.is_file() && !self.sources.contains_key(&normalize(&target)) {
+                    return Err(in_file(
+                        Some(file.clone()),
+                        PetrolError::SchemaParse(format!(
+                            "imported file \"{}\" does not exist",
+                            import
+                        ))
+                        .into(),
+                    ));
+                }
+                queue.push_back(target);
+            }
+            fragments.push((Some(file), fragment));
+        }
+        Ok(fragments)
+    }
+
+    fn read(&self, path: &Path) -> Result<String, ParserError> {
+        match self.sources.get(&normalize(path)) {
+            Some(contents) => Ok(contents.clone()),
+            None => Ok(fs::read_to_string(path)?),
+        }
+    }
+}
+
+/// Every `.petrol` file under `dir`, in a stable order.
+pub fn schema_files(dir: &Path) -> Result<Vec<PathBuf>, ParserError> {
+    let mut files = Vec::new();
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(schema_files(&entry)?);
+        } else if entry.extension().is_some_and(|ext| ext == SCHEMA_EXTENSION) {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}